@@ -0,0 +1,387 @@
+//! # Client implementation.
+//!
+//! This module contains an async client that takes care of the JSON-RPC
+//! framing against the request and response types of the services.
+
+use crate::service::{
+    group_service::{GroupCreateRequest, GroupCreateResponse, GroupJoinRequest, GroupJoinResponse},
+    session_service::{
+        SessionCreateRequest, SessionCreateResponse, SessionLoginRequest, SessionLoginResponse,
+        SessionMessageRequest, SessionMessageResponse, SessionSignupRequest, SessionSignupResponse,
+        SessionStandbyResponse,
+    },
+    system_service::SystemInfoResponse,
+};
+use crate::state::{
+    group::{GroupId, JoinCode},
+    session::{SessionId, SessionPartyNumber},
+};
+use futures_util::{stream::SplitSink, SinkExt, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use thiserror::Error;
+use tokio::{
+    net::TcpStream,
+    sync::{mpsc, oneshot, Mutex},
+};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type WsSink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+type PendingResponses = Arc<Mutex<HashMap<u64, oneshot::Sender<RpcResponse>>>>;
+
+/// Error type for client operations.
+#[derive(Debug, Error)]
+pub enum ClientError {
+    /// Error generated by the websocket connection.
+    #[error("websocket error: {0}")]
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+    /// Error generated while encoding or decoding a message.
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    /// Error returned by the server.
+    #[error("server error {code}: {message}")]
+    Rpc {
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+    /// Error generated when the connection closed before a response arrived.
+    #[error("connection closed")]
+    ConnectionClosed,
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for ClientError {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(err))
+    }
+}
+
+/// Notification pushed by the server.
+#[derive(Debug, Clone)]
+pub struct ClientNotification {
+    /// Event name, e.g. `session_ready`.
+    pub method: String,
+    /// Event payload.
+    pub params: Value,
+}
+
+impl ClientNotification {
+    /// Deserializes the payload into one of the notification types.
+    pub fn params_as<T: DeserializeOwned>(&self) -> Result<T, ClientError> {
+        Ok(serde_json::from_value(self.params.clone())?)
+    }
+}
+
+/// Result of signing up to a session.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum SessionSignupResult {
+    /// Client was assigned a party number.
+    Party(SessionSignupResponse),
+    /// Client was queued as a standby because the session is full.
+    Standby(SessionStandbyResponse),
+}
+
+/// JSON-RPC error object.
+#[derive(Deserialize)]
+struct RpcErrorObject {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+/// JSON-RPC response or notification received from the server.
+#[derive(Deserialize)]
+struct RpcResponse {
+    id: Option<u64>,
+    method: Option<String>,
+    params: Option<Value>,
+    result: Option<Value>,
+    error: Option<RpcErrorObject>,
+}
+
+/// JSON-RPC request sent to the server, a notification if `id` is unset.
+#[derive(Serialize)]
+struct RpcRequest<'a, T: Serialize> {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    method: &'a str,
+    params: T,
+}
+
+/// Async client of an mpc-manager server.
+pub struct MpcClient {
+    /// Sending half of the websocket.
+    sink: Mutex<WsSink>,
+    /// Requests waiting for a response, mapped by id.
+    pending: PendingResponses,
+    /// Id assigned to the next request.
+    next_id: AtomicU64,
+    /// Notifications pushed by the server, until taken by the user.
+    notifications: Option<mpsc::UnboundedReceiver<ClientNotification>>,
+}
+
+impl MpcClient {
+    /// Connects to a server, e.g. `ws://127.0.0.1:8080`.
+    ///
+    /// Incoming messages are read in a background task until the
+    /// connection is closed.
+    pub async fn connect(url: &str) -> Result<Self, ClientError> {
+        let (ws, _) = connect_async(url).await?;
+        let (sink, mut stream) = ws.split();
+        let pending: PendingResponses = Arc::new(Mutex::new(HashMap::new()));
+        let (notifications_tx, notifications_rx) = mpsc::unbounded_channel();
+
+        let pending_c = pending.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(msg)) = stream.next().await {
+                let text = match msg {
+                    Message::Text(text) => text,
+                    Message::Close(_) => break,
+                    _ => continue,
+                };
+                let Ok(response) = serde_json::from_str::<RpcResponse>(&text) else {
+                    continue;
+                };
+                match (response.id, &response.method) {
+                    (Some(id), _) => {
+                        if let Some(tx) = pending_c.lock().await.remove(&id) {
+                            let _ = tx.send(response);
+                        }
+                    }
+                    (None, Some(method)) => {
+                        let _ = notifications_tx.send(ClientNotification {
+                            method: method.clone(),
+                            params: response.params.unwrap_or_default(),
+                        });
+                    }
+                    (None, None) => {}
+                }
+            }
+            // Fail requests still waiting for a response
+            pending_c.lock().await.clear();
+        });
+
+        Ok(Self {
+            sink: Mutex::new(sink),
+            pending,
+            next_id: AtomicU64::new(1),
+            notifications: Some(notifications_rx),
+        })
+    }
+
+    /// Takes the stream of notifications pushed by the server.
+    ///
+    /// Returns `None` if the stream was already taken.
+    pub fn notifications(&mut self) -> Option<UnboundedReceiverStream<ClientNotification>> {
+        self.notifications.take().map(UnboundedReceiverStream::new)
+    }
+
+    /// Returns the server version and the capabilities it supports.
+    pub async fn server_info(&self) -> Result<SystemInfoResponse, ClientError> {
+        self.request("system_info", ()).await
+    }
+
+    /// Creates a group, joining it.
+    pub async fn create_group(
+        &self,
+        params: GroupCreateRequest,
+    ) -> Result<GroupCreateResponse, ClientError> {
+        self.request("group_create", params).await
+    }
+
+    /// Joins a group.
+    pub async fn join_group(&self, group_id: GroupId) -> Result<GroupJoinResponse, ClientError> {
+        let params = GroupJoinRequest {
+            group_id: Some(group_id),
+            join_code: None,
+        };
+        self.request("group_join", params).await
+    }
+
+    /// Joins a group with its join code.
+    pub async fn join_group_by_code(
+        &self,
+        join_code: JoinCode,
+    ) -> Result<GroupJoinResponse, ClientError> {
+        let params = GroupJoinRequest {
+            group_id: None,
+            join_code: Some(join_code),
+        };
+        self.request("group_join", params).await
+    }
+
+    /// Creates a session in a group.
+    pub async fn create_session(
+        &self,
+        params: SessionCreateRequest,
+    ) -> Result<SessionCreateResponse, ClientError> {
+        self.request("session_create", params).await
+    }
+
+    /// Signs up to a session, getting a party number or a standby position.
+    pub async fn signup(
+        &self,
+        group_id: GroupId,
+        session_id: SessionId,
+    ) -> Result<SessionSignupResult, ClientError> {
+        self.request(
+            "session_signup",
+            SessionSignupRequest {
+                group_id,
+                session_id,
+            },
+        )
+        .await
+    }
+
+    /// Logs in to a session with a given party number.
+    pub async fn login(
+        &self,
+        group_id: GroupId,
+        session_id: SessionId,
+        party_number: SessionPartyNumber,
+    ) -> Result<SessionLoginResponse, ClientError> {
+        self.request(
+            "session_login",
+            SessionLoginRequest {
+                group_id,
+                session_id,
+                party_number,
+            },
+        )
+        .await
+    }
+
+    /// Sends a message to a session.
+    ///
+    /// The server only responds when acknowledgements are requested, in
+    /// which case the id of the message is returned.
+    pub async fn send_message<T: Serialize>(
+        &self,
+        params: SessionMessageRequest<T>,
+    ) -> Result<Option<SessionMessageResponse>, ClientError> {
+        if params.ack {
+            return self.request("session_message", params).await.map(Some);
+        }
+        self.send(None, "session_message", params).await?;
+        Ok(None)
+    }
+
+    /// Sends a request, waiting for its response.
+    pub async fn request<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, ClientError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().await.insert(id, tx);
+        if let Err(err) = self.send(Some(id), method, params).await {
+            self.pending.lock().await.remove(&id);
+            return Err(err);
+        }
+        let response = rx.await.map_err(|_| ClientError::ConnectionClosed)?;
+        if let Some(error) = response.error {
+            return Err(ClientError::Rpc {
+                code: error.code,
+                message: error.message,
+                data: error.data,
+            });
+        }
+        Ok(serde_json::from_value(response.result.unwrap_or_default())?)
+    }
+
+    /// Sends a request or a notification if `id` is unset.
+    async fn send<P: Serialize>(
+        &self,
+        id: Option<u64>,
+        method: &str,
+        params: P,
+    ) -> Result<(), ClientError> {
+        let request = RpcRequest {
+            jsonrpc: "2.0",
+            id,
+            method,
+            params,
+        };
+        let text = serde_json::to_string(&request)?;
+        self.sink.lock().await.send(Message::Text(text)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_async;
+
+    /// Serves a single connection, answering every request with the reply
+    /// built by `reply` from its method and params, after pushing a
+    /// notification.
+    async fn serve(reply: fn(&str, &Value) -> Value) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(stream).await.unwrap();
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                let request: Value = serde_json::from_str(&text).unwrap();
+                let notification =
+                    json!({"jsonrpc": "2.0", "method": "test_event", "params": request["params"]});
+                ws.send(Message::Text(notification.to_string()))
+                    .await
+                    .unwrap();
+                if request["id"].is_null() {
+                    continue;
+                }
+                let mut response = reply(request["method"].as_str().unwrap(), &request["params"]);
+                response["jsonrpc"] = json!("2.0");
+                response["id"] = request["id"].clone();
+                ws.send(Message::Text(response.to_string())).await.unwrap();
+            }
+        });
+        format!("ws://{addr}")
+    }
+
+    #[tokio::test]
+    async fn responses_are_matched_to_their_requests() {
+        let url =
+            serve(|method, params| json!({"result": {"method": method, "params": params}})).await;
+        let mut client = MpcClient::connect(&url).await.unwrap();
+        let mut notifications = client.notifications().unwrap();
+        assert!(client.notifications().is_none());
+
+        let result: Value = client.request("test_call", json!({"a": 1})).await.unwrap();
+        assert_eq!(result, json!({"method": "test_call", "params": {"a": 1}}));
+        let notification = notifications.next().await.unwrap();
+        assert_eq!(notification.method, "test_event");
+        assert_eq!(notification.params, json!({"a": 1}));
+    }
+
+    #[tokio::test]
+    async fn errors_are_returned_as_rpc_errors() {
+        let url = serve(|_, _| json!({"error": {"code": -32601, "message": "not found"}})).await;
+        let client = MpcClient::connect(&url).await.unwrap();
+
+        let err = client
+            .request::<_, Value>("test_call", json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ClientError::Rpc { code: -32601, ref message, data: None } if message == "not found"
+        ));
+    }
+}
@@ -2,28 +2,310 @@
 //!
 //! This module is used to retrieve the configuration from the environment variables
 //! and parse them into a struct.
+//!
+//! Settings can also be read from a TOML or YAML file whose path is given by
+//! the [`CONFIG_PATH_VAR`] environment variable, environment variables take
+//! precedence over the values of the file.
 
 use dotenv::dotenv;
-use serde::Deserialize;
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde::{Deserialize, Deserializer};
+use serde_aux::field_attributes::{deserialize_bool_from_anything, deserialize_number_from_string};
+use std::net::{AddrParseError, IpAddr, SocketAddr};
+use std::str::FromStr;
+use tracing_subscriber::filter::LevelFilter;
+
+/// Environment variable holding the path of an optional configuration file,
+/// its format is inferred from the extension.
+pub const CONFIG_PATH_VAR: &str = "MPC_CONFIG";
 
 /// Configuration settings for the server.
 #[derive(Deserialize, Clone, Debug)]
 pub struct Configuration {
-    /// Host used by the server.
+    /// Comma-separated IP addresses the server binds to, e.g. `0.0.0.0,::`
+    /// to listen on both IPv4 and IPv6.
     pub host: String,
     /// Port used to expose the server.
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
+    /// Path to the PEM encoded TLS certificate chain.
+    ///
+    /// TLS is only enabled when both `tls_cert_path` and `tls_key_path` are set.
+    pub tls_cert_path: Option<String>,
+    /// Path to the PEM encoded TLS private key.
+    pub tls_key_path: Option<String>,
+    /// Path to the PEM encoded certificate authorities used to authenticate clients.
+    ///
+    /// When set along with TLS, clients must present a valid certificate (mTLS).
+    /// The certificate only gates the connection, client ids and authorization
+    /// don't depend on its subject.
+    pub tls_client_ca_path: Option<String>,
+    /// Log level used by the server, defaults to `info`.
+    ///
+    /// The `RUST_LOG` environment variable takes precedence when present.
+    #[serde(
+        default = "default_log_level",
+        deserialize_with = "deserialize_log_level"
+    )]
+    pub log_level: String,
+    /// Maximum size in bytes of a websocket message, defaults to 64 MiB.
+    #[serde(
+        default = "default_max_message_size",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub max_message_size: usize,
+    /// Number of messages buffered for each client before it's disconnected
+    /// for not reading them, defaults to 1024.
+    #[serde(
+        default = "default_send_buffer_capacity",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub send_buffer_capacity: usize,
+    /// Times a message is retried while the send buffer of a client is full
+    /// before the client is dropped, defaults to 0.
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub send_retry_attempts: u32,
+    /// Milliseconds to wait before the first retry of a message, doubled on
+    /// every further retry, defaults to 10.
+    #[serde(
+        default = "default_send_retry_backoff_ms",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub send_retry_backoff_ms: u64,
+    /// Maximum size in bytes of a message broadcast to every party of a
+    /// session, unlimited if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub max_broadcast_message_size: Option<usize>,
+    /// Seconds to wait for connections to close after a shutdown signal,
+    /// defaults to 10.
+    #[serde(
+        default = "default_shutdown_grace_period_secs",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub shutdown_grace_period_secs: u64,
+    /// Requests per second allowed for each client, rate limiting is disabled if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub rate_limit_per_second: Option<u32>,
+    /// Maximum burst of requests allowed for each client,
+    /// defaults to `rate_limit_per_second`.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub rate_limit_burst: Option<u32>,
+    /// Maximum number of distinct methods a connection may call, unlimited if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub max_distinct_methods: Option<usize>,
+    /// Approximate bytes held in relay queues and sessions before new
+    /// sessions and messages are rejected, unlimited if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub memory_budget_bytes: Option<usize>,
+    /// Maximum number of concurrent connections, unlimited if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub max_connections: Option<usize>,
+    /// Maximum number of concurrent groups, unlimited if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub max_groups: Option<usize>,
+    /// Maximum number of sessions of each group, unlimited if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub max_sessions_per_group: Option<usize>,
+    /// Seconds without receiving frames after which a client is pinged,
+    /// closing the connection if it stays silent for another window.
+    /// Idle connections are never closed if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub idle_timeout_secs: Option<u64>,
+    /// Seconds between keepalive pings sent to every client, closing the
+    /// connection if a ping is still unanswered when the next one is due.
+    /// No keepalive pings are sent if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub ping_interval_secs: Option<u64>,
+    /// Seconds a request may take to be handled before a timeout error
+    /// is returned, requests never time out if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub request_timeout_secs: Option<u64>,
+    /// Seconds a disconnected client's memberships are reserved so it can
+    /// resume with a token, resuming is disabled if unset.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub resume_window_secs: Option<u64>,
+    /// Seconds a resume token is valid after being issued, defaults to a day.
+    #[serde(default, deserialize_with = "deserialize_option_number")]
+    pub resume_token_lifetime_secs: Option<u64>,
+    /// Seconds between scans for expired sessions, defaults to 5.
+    #[serde(
+        default = "default_reaper_interval_secs",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub reaper_interval_secs: u64,
+    /// Shared secret clients must present as bearer token when connecting,
+    /// authentication is disabled if unset.
+    #[serde(default, deserialize_with = "deserialize_option_string")]
+    pub auth_token: Option<String>,
+    /// Comma-separated origins browsers may connect from, `*` allows any
+    /// origin. Origins are not checked if unset.
+    #[serde(default, deserialize_with = "deserialize_option_string")]
+    pub allowed_origins: Option<String>,
+    /// Directory where non-sensitive group metadata is saved to recover
+    /// groups after a restart, nothing is persisted if unset.
+    #[serde(default, deserialize_with = "deserialize_option_string")]
+    pub state_store_path: Option<String>,
+    /// Seconds restored groups wait for a client to join them before being
+    /// removed, defaults to 300.
+    #[serde(
+        default = "default_state_restore_window_secs",
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub state_restore_window_secs: u64,
+    /// Whether the `ping` service is registered, disabled by default.
+    #[serde(default, deserialize_with = "deserialize_bool_from_anything")]
+    pub ping_service: bool,
+}
+
+fn default_shutdown_grace_period_secs() -> u64 {
+    10
+}
+
+fn default_reaper_interval_secs() -> u64 {
+    5
+}
+
+fn default_state_restore_window_secs() -> u64 {
+    300
 }
 
-/// Returns a configuration object from the environment variables.
+/// Deserializes an optional number that may be represented as a string,
+/// as is the case for environment variables.
+fn deserialize_option_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + Deserialize<'de>,
+    T::Err: std::fmt::Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString<T> {
+        Number(T),
+        String(String),
+    }
+
+    match Option::<NumberOrString<T>>::deserialize(deserializer)? {
+        Some(NumberOrString::Number(number)) => Ok(Some(number)),
+        Some(NumberOrString::String(string)) if string.is_empty() => Ok(None),
+        Some(NumberOrString::String(string)) => {
+            string.parse().map(Some).map_err(serde::de::Error::custom)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Deserializes an optional string, treating an empty one as unset.
+fn deserialize_option_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.filter(|string| !string.is_empty()))
+}
+
+fn default_max_message_size() -> usize {
+    crate::server::DEFAULT_MAX_MESSAGE_SIZE
+}
+
+fn default_send_buffer_capacity() -> usize {
+    crate::server::DEFAULT_SEND_BUFFER_CAPACITY
+}
+
+fn default_send_retry_backoff_ms() -> u64 {
+    10
+}
+
+fn default_log_level() -> String {
+    "info".into()
+}
+
+/// Deserializes a log level, failing if it's not a valid level.
+fn deserialize_log_level<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let level = String::deserialize(deserializer)?;
+    LevelFilter::from_str(&level).map_err(|_| {
+        serde::de::Error::custom(format!(
+            "invalid log level `{level}`, expected one of: trace, debug, info, warn, error, off"
+        ))
+    })?;
+    Ok(level)
+}
+
+impl Configuration {
+    /// Returns the socket addresses the server should bind to.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if any of the hosts is not a valid IPv4 or IPv6 address.
+    pub fn addresses(&self) -> Result<Vec<SocketAddr>, AddrParseError> {
+        self.host
+            .split(',')
+            .map(|host| {
+                let ip: IpAddr = host.trim().parse()?;
+                Ok(SocketAddr::new(ip, self.port))
+            })
+            .collect()
+    }
+}
+
+/// Returns a configuration object from the configuration file, if any,
+/// overridden by the environment variables.
+///
+/// # Errors
+///
+/// * Returns an error if the configuration file is set but can't be read.
 pub fn get_configuration() -> Result<Configuration, config::ConfigError> {
     dotenv().ok();
 
-    let configuration = config::Config::builder()
-        .add_source(config::Environment::default())
-        .build()?;
+    let mut builder = config::Config::builder();
+    if let Some(path) = std::env::var(CONFIG_PATH_VAR)
+        .ok()
+        .filter(|path| !path.is_empty())
+    {
+        builder = builder.add_source(config::File::with_name(&path));
+    }
+    let configuration = builder.add_source(config::Environment::default()).build()?;
 
     configuration.try_deserialize()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configuration(host: &str) -> Configuration {
+        serde_json::from_value(serde_json::json!({"host": host, "port": "8080"})).unwrap()
+    }
+
+    #[test]
+    fn every_listed_host_is_bound() {
+        let addresses = configuration("0.0.0.0, ::").addresses().unwrap();
+        let expected: Vec<SocketAddr> = vec![
+            "0.0.0.0:8080".parse().unwrap(),
+            "[::]:8080".parse().unwrap(),
+        ];
+        assert_eq!(addresses, expected);
+        assert!(configuration("0.0.0.0,localhost").addresses().is_err());
+    }
+
+    #[test]
+    fn files_are_overridden_by_the_environment() {
+        let path = std::env::temp_dir().join(format!("mpc-manager-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            "host = \"127.0.0.1\"\nport = 9000\nmax_groups = 10\nmax_sessions_per_group = 5\n",
+        )
+        .unwrap();
+        std::env::set_var(CONFIG_PATH_VAR, &path);
+        std::env::set_var("MAX_SESSIONS_PER_GROUP", "7");
+
+        let configuration = get_configuration();
+        std::env::remove_var(CONFIG_PATH_VAR);
+        std::env::remove_var("MAX_SESSIONS_PER_GROUP");
+        std::fs::remove_file(path).unwrap();
+        let configuration = configuration.unwrap();
+        assert_eq!(configuration.port, 9000);
+        assert_eq!(configuration.max_groups, Some(10));
+        assert_eq!(configuration.max_sessions_per_group, Some(7));
+    }
+}
@@ -12,6 +12,8 @@
 //! * Although it includes logging by default, it can be easily disabled.
 //! * It was built with security in mind: no data is stored long-term and as soon as it's not needed anymore it's deleted.
 
+#[cfg(feature = "client")]
+pub mod client;
 #[cfg(feature = "server")]
 pub mod configuration;
 
@@ -19,6 +21,8 @@ pub mod configuration;
 pub mod server;
 #[cfg(feature = "server")]
 pub mod telemetry;
+#[cfg(feature = "server")]
+pub mod tls;
 
 pub mod service;
 pub mod state;
@@ -1,74 +1,268 @@
 #[cfg(feature = "server")]
-use axum::extract::State as AxumState;
+use futures_util::future::join_all;
 #[cfg(feature = "server")]
-use axum::extract::WebSocketUpgrade;
-#[cfg(feature = "server")]
-use axum::response::IntoResponse;
-#[cfg(feature = "server")]
-use axum::routing::get;
+use mpc_manager::configuration;
 #[cfg(feature = "server")]
-use axum::Router;
+use mpc_manager::server::{
+    router, run_reaper, AllowedOrigins, AppState, Auth, RateLimit, Resume, SendRetry, ServerOptions,
+};
 #[cfg(feature = "server")]
-use mpc_manager::configuration;
+use mpc_manager::service::{ping_service, system_service::ServerFeatures, ServiceHandler};
 #[cfg(feature = "server")]
-use mpc_manager::server::Server;
+use mpc_manager::state::{store::FileStateStore, State};
 #[cfg(feature = "server")]
-use mpc_manager::service::ServiceHandler;
+use mpc_manager::telemetry::{get_subscriber, init_metrics, init_subscriber};
 #[cfg(feature = "server")]
-use mpc_manager::state::State;
+use mpc_manager::tls;
 #[cfg(feature = "server")]
-use mpc_manager::telemetry::{get_subscriber, init_subscriber};
+use socket2::{Domain, Protocol, Socket, Type};
 #[cfg(feature = "server")]
-use std::net::SocketAddr;
+use std::net::{SocketAddr, TcpListener};
 #[cfg(feature = "server")]
 use std::sync::Arc;
 #[cfg(feature = "server")]
-use tower_http::trace::{DefaultMakeSpan, TraceLayer};
+use std::time::{Duration, Instant};
+#[cfg(feature = "server")]
+use tokio_util::sync::CancellationToken;
 
+/// Binds a listener to the given address.
+///
+/// IPv6 listeners only accept IPv6 connections, so the same port can be
+/// bound on both `0.0.0.0` and `::`.
 #[cfg(feature = "server")]
-async fn ws_handler(
-    ws: WebSocketUpgrade,
-    AxumState(app_state): AxumState<Arc<AppState>>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| {
-        let state = app_state.state.clone();
-        let service_handler = app_state.service_handler.clone();
-        let server = Server::new(state, service_handler);
-        server.handle_connection(socket)
-    })
+fn bind(address: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if address.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
 }
 
+/// Waits for a SIGINT or SIGTERM signal and cancels the shutdown token.
+///
+/// The state store is frozen first so the groups dropped while closing
+/// connections are restored on the next start.
 #[cfg(feature = "server")]
-struct AppState {
-    state: Arc<State>,
-    service_handler: Arc<ServiceHandler>,
+async fn shutdown_signal(shutdown: CancellationToken, state: Arc<State>) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install SIGINT handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutdown signal received, closing connections");
+    state.freeze_store();
+    shutdown.cancel();
 }
 
 #[tokio::main]
 #[cfg(feature = "server")]
 async fn main() {
-    let subscriber = get_subscriber("mpc-manager".into(), "info".into(), std::io::stdout);
+    let configuration = configuration::get_configuration().unwrap_or_else(|error| {
+        eprintln!("Failed to build configuration: {error}");
+        std::process::exit(1);
+    });
+    let subscriber = get_subscriber(
+        "mpc-manager".into(),
+        configuration.log_level.clone(),
+        std::io::stdout,
+    );
     init_subscriber(subscriber);
-    let configuration = configuration::get_configuration().expect("Failed to build configuration.");
+    let metrics = init_metrics();
 
-    let state = Arc::new(State::new());
-    let service_handler = Arc::new(ServiceHandler::new());
+    let shutdown = CancellationToken::new();
+    let grace_period = Duration::from_secs(configuration.shutdown_grace_period_secs);
+    let mut state = State::new()
+        .with_memory_budget(configuration.memory_budget_bytes)
+        .with_max_groups(configuration.max_groups)
+        .with_max_sessions_per_group(configuration.max_sessions_per_group)
+        .with_max_connections(configuration.max_connections);
+    if let Some(path) = &configuration.state_store_path {
+        let store = FileStateStore::open(path).unwrap_or_else(|error| {
+            tracing::error!(error = %error, path, "Failed to open state store");
+            std::process::exit(1);
+        });
+        state = state.with_store(Arc::new(store));
+        let restore_window = Duration::from_secs(configuration.state_restore_window_secs);
+        match state.restore(restore_window).await {
+            Ok(restored) => tracing::info!(restored, "Restored groups from state store"),
+            Err(error) => {
+                tracing::error!(error = %error, "Failed to restore groups from state store")
+            }
+        }
+    }
+    let state = Arc::new(state);
+    let mut service_handler = ServiceHandler::builder()
+        .with_max_broadcast_size(configuration.max_broadcast_message_size)
+        .with_server_features(ServerFeatures {
+            auth: configuration.auth_token.is_some(),
+            tls: configuration.tls_cert_path.is_some() && configuration.tls_key_path.is_some(),
+            resume: configuration.resume_window_secs.is_some(),
+        })
+        .with_default_services();
+    if configuration.ping_service {
+        service_handler = service_handler.register_service(
+            ping_service::ROUTE_PREFIX.into(),
+            Box::new(ping_service::PingService {}),
+        );
+    }
+    let service_handler = Arc::new(service_handler.build());
+    let rate_limit = configuration
+        .rate_limit_per_second
+        .map(|per_second| RateLimit {
+            per_second,
+            burst: configuration.rate_limit_burst.unwrap_or(per_second),
+        });
+    let server_options = ServerOptions {
+        max_message_size: configuration.max_message_size,
+        send_buffer_capacity: configuration.send_buffer_capacity,
+        send_retry: SendRetry {
+            attempts: configuration.send_retry_attempts,
+            backoff: Duration::from_millis(configuration.send_retry_backoff_ms),
+        },
+        rate_limit,
+        max_distinct_methods: configuration.max_distinct_methods,
+        idle_timeout: configuration.idle_timeout_secs.map(Duration::from_secs),
+        ping_interval: configuration.ping_interval_secs.map(Duration::from_secs),
+        request_timeout: configuration.request_timeout_secs.map(Duration::from_secs),
+        resume: configuration.resume_window_secs.map(|secs| {
+            let resume = Resume::new(Duration::from_secs(secs));
+            match configuration.resume_token_lifetime_secs {
+                Some(secs) => resume.with_token_lifetime(Duration::from_secs(secs)),
+                None => resume,
+            }
+        }),
+        auth: configuration.auth_token.as_deref().map(Auth::new),
+        allowed_origins: configuration
+            .allowed_origins
+            .as_deref()
+            .map(AllowedOrigins::new),
+    };
+    tokio::spawn(run_reaper(
+        state.clone(),
+        Duration::from_secs(configuration.reaper_interval_secs.max(1)),
+        server_options.send_retry,
+        shutdown.clone(),
+    ));
     let app_state = Arc::new(AppState {
-        state,
+        state: state.clone(),
         service_handler,
+        server_options,
+        shutdown: shutdown.clone(),
+        metrics,
+        started_at: Instant::now(),
     });
 
-    let app = Router::new()
-        .route("/", get(ws_handler))
-        .with_state(app_state)
-        .layer(TraceLayer::new_for_http().make_span_with(DefaultMakeSpan::default()));
+    let app = router(app_state);
 
-    let address = format!("{}:{}", configuration.host, configuration.port)
-        .parse()
-        .expect("Failed to parse address");
-    tracing::info!("Listening on {}", address);
-    axum::Server::bind(&address)
-        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
-        .await
-        .expect("Failed to start server");
+    let addresses = configuration.addresses().unwrap_or_else(|error| {
+        tracing::error!(
+            error = %error,
+            host = configuration.host,
+            "Failed to parse HOST as a list of IP addresses"
+        );
+        std::process::exit(1);
+    });
+    let listeners: Vec<(SocketAddr, TcpListener)> = addresses
+        .into_iter()
+        .map(|address| {
+            let listener = bind(address).unwrap_or_else(|error| {
+                tracing::error!(error = %error, %address, "Failed to bind address");
+                std::process::exit(1);
+            });
+            (address, listener)
+        })
+        .collect();
+    tokio::spawn(shutdown_signal(shutdown.clone(), state.clone()));
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    let servers: Vec<_> = match (configuration.tls_cert_path, configuration.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let client_ca_path = configuration.tls_client_ca_path;
+            let mtls = client_ca_path.is_some();
+            let tls_config = tls::rustls_config(cert_path, key_path, client_ca_path)
+                .await
+                .unwrap_or_else(|error| {
+                    tracing::error!(error = %error, "Failed to load TLS configuration");
+                    std::process::exit(1);
+                });
+            listeners
+                .into_iter()
+                .map(|(address, listener)| {
+                    if mtls {
+                        tracing::info!("Mutual TLS enabled, listening on wss://{}", address);
+                    } else {
+                        tracing::info!("TLS enabled, listening on wss://{}", address);
+                    }
+                    let handle = axum_server::Handle::new();
+                    let handle_c = handle.clone();
+                    let shutdown = shutdown.clone();
+                    tokio::spawn(async move {
+                        shutdown.cancelled().await;
+                        handle_c.graceful_shutdown(Some(grace_period));
+                    });
+                    let server = axum_server::from_tcp_rustls(listener, tls_config.clone())
+                        .handle(handle)
+                        .serve(service.clone());
+                    tokio::spawn(async move { server.await.expect("Failed to start server") })
+                })
+                .collect()
+        }
+        (cert_path, key_path) => {
+            if cert_path.is_some()
+                || key_path.is_some()
+                || configuration.tls_client_ca_path.is_some()
+            {
+                tracing::warn!(
+                    "Both TLS_CERT_PATH and TLS_KEY_PATH are required to enable TLS, ignoring TLS settings"
+                );
+            }
+            listeners
+                .into_iter()
+                .map(|(address, listener)| {
+                    tracing::info!("TLS disabled, listening on ws://{}", address);
+                    let shutdown = shutdown.clone();
+                    let server = axum::Server::from_tcp(listener)
+                        .expect("Failed to start server")
+                        .serve(service.clone())
+                        .with_graceful_shutdown(async move { shutdown.cancelled().await });
+                    tokio::spawn(async move { server.await.expect("Failed to start server") })
+                })
+                .collect()
+        }
+    };
+    join_all(servers).await;
+
+    // Upgraded websockets are not tracked by the http server, wait for them
+    // to receive the shutdown notification and close.
+    let drained = tokio::time::timeout(grace_period, async {
+        while state.get_number_of_clients().await > 0 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+    if drained.is_err() {
+        tracing::warn!("Grace period elapsed with connections still open");
+    }
+    tracing::info!("Server stopped");
 }
@@ -2,15 +2,192 @@
 //!
 //! This module contains the server implementation using Axum.
 
+use self::rate_limiter::RateLimiter;
 use crate::{
-    service::{notification::Notification, ServiceHandler},
-    state::{ClientId, State},
+    service::{
+        group_service::{
+            GroupClosedNotification, GroupClosedReason, GroupEvent, GroupJoinRequest, GroupMethod,
+        },
+        notification::Notification,
+        session_service::{
+            SessionEvent, SessionMethod, SessionPromotedNotification, SessionReadyNotification,
+        },
+        system_service::SystemMethod,
+        ServiceHandler,
+    },
+    state::{group::Group, ClientId, Promotion, PublicClientId, State},
+    telemetry::{BYTES_RELAYED, MESSAGES_RELAYED, NOTIFICATIONS_SENT, SLOW_CLIENTS_DROPPED},
 };
-use axum::extract::ws::{self, WebSocket};
-use futures_util::{SinkExt, StreamExt};
-use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use axum::extract::ws;
+use futures_util::{future::try_join_all, Sink, SinkExt, Stream, StreamExt};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex as StdMutex,
+    },
+    time::Duration,
+};
+use strum::{Display, EnumString};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    Mutex, Notify,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+pub use self::{
+    auth::Auth,
+    codec::Codec,
+    origin::AllowedOrigins,
+    rate_limiter::RateLimit,
+    reaper::run_reaper,
+    resume::{Resume, ResumeClaims, DEFAULT_TOKEN_LIFETIME},
+    router::{router, AppState},
+};
+
+mod auth;
+mod codec;
+mod origin;
+mod rate_limiter;
+mod reaper;
+mod resume;
+mod router;
+#[cfg(feature = "testing")]
+pub mod test_harness;
+
+/// JSON-RPC error code returned when a client exceeds its rate limit.
+pub const RATE_LIMIT_EXCEEDED_CODE: isize = -32000;
+/// JSON-RPC error code returned when a client exceeds the number of
+/// distinct methods it may call.
+pub const DISTINCT_METHODS_EXCEEDED_CODE: isize = -32001;
+/// JSON-RPC error code returned when a service doesn't handle a request
+/// within the request timeout.
+pub const REQUEST_TIMEOUT_CODE: isize = -32003;
+
+/// Default maximum size of a websocket message, matching tungstenite's default.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// Default number of messages buffered for a client before it's dropped.
+pub const DEFAULT_SEND_BUFFER_CAPACITY: usize = 1024;
+
+/// Period at which connections check if their resume token must be
+/// refreshed, catching memberships changed by other clients and tokens
+/// past half their lifetime.
+pub const RESUME_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Id of the `group_join` response sent to clients that requested to join
+/// a group when connecting.
+pub const GROUP_JOIN_REQUEST_ID: &str = "connect";
+
+/// Retries of a message queued to a client whose send buffer is full.
+///
+/// The `n`-th retry waits `backoff * 2^n`, a client whose buffer is still
+/// full after every attempt is dropped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SendRetry {
+    /// Number of retries, messages are never retried if zero.
+    pub attempts: u32,
+    /// Wait before the first retry.
+    pub backoff: Duration,
+}
+
+impl SendRetry {
+    /// Returns the wait before the given retry, starting at 0.
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff.saturating_mul(1 << attempt.min(16))
+    }
+}
+
+/// Available server events.
+#[derive(Debug, Display, EnumString)]
+pub enum ServerEvent {
+    /// The server is shutting down and the connection is about to be closed.
+    #[strum(serialize = "server_shutdown")]
+    ServerShutdown,
+    /// The client was registered, always the first message of a connection.
+    #[strum(serialize = "client_registered")]
+    ClientRegistered,
+    /// Token the client can present when reconnecting to resume its session.
+    #[strum(serialize = "client_resume_token")]
+    ClientResumeToken,
+}
+
+/// Notification carrying the id assigned to a client.
+#[derive(Debug, Serialize)]
+pub struct ClientRegisteredNotification {
+    #[serde(rename = "clientId")]
+    pub client_id: PublicClientId,
+}
+
+/// Notification carrying the resume token of a client.
+#[derive(Debug, Serialize)]
+pub struct ClientResumeTokenNotification {
+    /// Token to present in the `resume` query parameter when reconnecting.
+    pub token: String,
+    /// Seconds memberships are reserved after disconnecting.
+    #[serde(rename = "windowSecs")]
+    pub window_secs: u64,
+    /// Unix time in seconds the token expires at.
+    #[serde(rename = "expiresAt")]
+    pub expires_at: u64,
+}
+
+/// Options applied to every connection handled by a [`Server`].
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// Maximum size in bytes of a message sent to or received from a client.
+    pub max_message_size: usize,
+    /// Number of messages buffered for a client that doesn't read them fast
+    /// enough, the client is disconnected once the buffer is full.
+    pub send_buffer_capacity: usize,
+    /// Retries of messages queued while the send buffer is full.
+    pub send_retry: SendRetry,
+    /// Rate limit applied to incoming requests, disabled if `None`.
+    pub rate_limit: Option<RateLimit>,
+    /// Maximum number of distinct methods a connection may call,
+    /// unlimited if `None`.
+    pub max_distinct_methods: Option<usize>,
+    /// Time without receiving frames after which the peer is pinged, closing
+    /// the connection if the window elapses again, disabled if `None`.
+    pub idle_timeout: Option<Duration>,
+    /// Interval between keepalive pings sent regardless of traffic, closing
+    /// the connection if a ping isn't answered before the next one is due,
+    /// disabled if `None`.
+    pub ping_interval: Option<Duration>,
+    /// Time a service may take to handle a request before a timeout error
+    /// is returned, unlimited if `None`. The request still runs to
+    /// completion, only its late response is dropped.
+    pub request_timeout: Option<Duration>,
+    /// Signer of resume tokens, clients can't resume if `None`.
+    pub resume: Option<Resume>,
+    /// Verifier of the bearer token clients must present when connecting,
+    /// anyone can connect if `None`.
+    pub auth: Option<Auth>,
+    /// Origins browsers may connect from, any origin is allowed if `None`.
+    pub allowed_origins: Option<AllowedOrigins>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            send_buffer_capacity: DEFAULT_SEND_BUFFER_CAPACITY,
+            send_retry: SendRetry::default(),
+            rate_limit: None,
+            max_distinct_methods: None,
+            idle_timeout: None,
+            ping_interval: None,
+            request_timeout: None,
+            resume: None,
+            auth: None,
+            allowed_origins: None,
+        }
+    }
+}
 
 /// Server handler that manages websocket communications.
 pub struct Server {
@@ -20,104 +197,613 @@ pub struct Server {
     client_id: ClientId,
     /// Service handler for json-rpc requests.
     service_handler: Arc<ServiceHandler>,
+    /// Connection options.
+    options: ServerOptions,
+    /// Codec negotiated with the client.
+    codec: Codec,
+    /// Token cancelled when the server is shutting down.
+    shutdown: CancellationToken,
+    /// Token cancelled once the client said goodbye, closing the connection
+    /// after pending messages are flushed.
+    goodbye: CancellationToken,
+    /// Token cancelled when the client is too slow to read its messages,
+    /// closing the connection without flushing them.
+    disconnect: CancellationToken,
+    /// Rate limiter of incoming requests, if enabled.
+    rate_limiter: Option<StdMutex<RateLimiter>>,
+    /// Distinct methods invoked by the client, bounded by `max_distinct_methods`.
+    invoked_methods: StdMutex<HashSet<String>>,
+    /// Claims of the resume token presented when connecting, if any.
+    resumed: Option<ResumeClaims>,
+    /// Claims of the last resume token sent to the client.
+    resume_claims: StdMutex<Option<ResumeClaims>>,
+    /// Group joined on behalf of the client once it's registered, if any.
+    group_join: Option<GroupJoinRequest>,
 }
 
 impl Server {
     /// Creates a new server object.
     pub fn new(state: Arc<State>, service_handler: Arc<ServiceHandler>) -> Self {
         let client_id = state.new_client_id();
+        Self::with_client_id(state, service_handler, client_id)
+    }
+
+    /// Creates a new server object using an externally provisioned
+    /// client id (e.g. derived from authentication claims) instead of
+    /// generating a random one.
+    ///
+    /// Uniqueness is validated when the connection is handled: if another
+    /// connected client already uses the same id the connection is closed.
+    pub fn with_client_id(
+        state: Arc<State>,
+        service_handler: Arc<ServiceHandler>,
+        client_id: ClientId,
+    ) -> Self {
         Self {
             state,
             client_id,
             service_handler,
+            options: ServerOptions::default(),
+            codec: Codec::default(),
+            shutdown: CancellationToken::new(),
+            goodbye: CancellationToken::new(),
+            disconnect: CancellationToken::new(),
+            rate_limiter: None,
+            invoked_methods: StdMutex::new(HashSet::new()),
+            resumed: None,
+            resume_claims: StdMutex::new(None),
+            group_join: None,
         }
     }
 
+    /// Sets the connection options.
+    pub fn with_options(mut self, options: ServerOptions) -> Self {
+        self.rate_limiter = options
+            .rate_limit
+            .map(|limit| StdMutex::new(RateLimiter::new(limit)));
+        self.options = options;
+        self
+    }
+
+    /// Reclaims the reservation of the client a verified resume token was
+    /// issued to when the connection is handled.
+    ///
+    /// The connection is closed if the reservation expired or the memberships
+    /// of the client changed since the token was issued.
+    pub fn with_resume_claims(mut self, claims: ResumeClaims) -> Self {
+        self.client_id = claims.client_id;
+        self.resumed = Some(claims);
+        self
+    }
+
+    /// Sets the codec used to encode and decode messages of the connection.
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Joins the client to a group as soon as it's registered, sparing
+    /// simple clients the `group_join` round trip.
+    ///
+    /// The `group_join` response follows the registration messages, with
+    /// [`GROUP_JOIN_REQUEST_ID`] as its id.
+    pub fn with_group_join(mut self, group_join: GroupJoinRequest) -> Self {
+        self.group_join = Some(group_join);
+        self
+    }
+
+    /// Sets the token observed to close the connection when the server shuts down.
+    ///
+    /// Once cancelled, a `server_shutdown` notification is sent to the client
+    /// before closing the websocket.
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
     /// Handles incoming websocket connection, consuming
     /// self in the process, not allowing more than one
     /// connection to be processed with the same `Server` instance.
     ///
+    /// The socket is usually an axum `WebSocket`, any stream and sink of
+    /// websocket messages is accepted so connections can also be driven
+    /// in memory.
+    ///
     /// # Implementation notes
     ///
-    /// Heartbeat is implemented automatically by tokio-tungstenite
-    /// so there's no need to implement manually.
+    /// Idle peers are not closed by axum, half-open connections are detected
+    /// with the `idle_timeout` option, pinging the peer once before closing.
+    /// The `ping_interval` option keeps pinging healthy peers that send no
+    /// requests, e.g. to hold NAT state open, closing only those that stop
+    /// answering pongs.
     ///
     /// `register_client` must be called before handle_connection otherwise server will panic
-    #[tracing::instrument(name = "Handling connection", skip_all, fields(client_id = self.client_id.to_string()))]
-    pub async fn handle_connection(self, socket: WebSocket) {
+    #[tracing::instrument(name = "Handling connection", skip_all, fields(client_id = self.client_id.to_string(), label))]
+    pub async fn handle_connection<S>(self, socket: S)
+    where
+        S: Stream<Item = Result<ws::Message, axum::Error>>
+            + Sink<ws::Message, Error = axum::Error>
+            + Send
+            + 'static,
+    {
         let (mut ws_tx, mut ws_rx) = socket.split();
-        let (internal_tx, internal_rx) = mpsc::unbounded_channel::<String>();
-        let mut internal_rx = UnboundedReceiverStream::new(internal_rx);
+        let (internal_tx, internal_rx) =
+            mpsc::channel::<Vec<u8>>(self.options.send_buffer_capacity.max(1));
+        let mut internal_rx = ReceiverStream::new(internal_rx);
+
+        // Reclaimed once upgraded, so a failed upgrade doesn't consume the reservation
+        if let Some(claims) = &self.resumed {
+            if !self
+                .state
+                .reclaim_client(self.client_id, |memberships| claims.matches(memberships))
+                .await
+            {
+                tracing::warn!("Rejecting resume, reservation expired or memberships changed");
+                let _ = ws_tx
+                    .send(ws::Message::Close(Some(ws::CloseFrame {
+                        code: ws::close_code::POLICY,
+                        reason: "Invalid or expired resume token".into(),
+                    })))
+                    .await;
+                return;
+            }
+            tracing::info!("Client resumed");
+        }
 
         // Save client
-        self.state.add_client(self.client_id, internal_tx).await;
+        if let Err(error) = self
+            .state
+            .add_client(
+                self.client_id,
+                internal_tx,
+                self.codec,
+                self.disconnect.clone(),
+            )
+            .await
+        {
+            tracing::error!(error = ?error, "Error while registering client");
+            return;
+        }
+        // Queued before any other message so it's the first the client receives
+        if let Err(error) = self.send_client_registered().await {
+            tracing::error!(error = ?error, "Error while sending client id");
+        }
+        if let Err(error) = self.send_resume_token().await {
+            tracing::error!(error = ?error, "Error while sending resume token");
+        }
 
         let self_c = Arc::new(self);
+        if let Err(error) = self_c.join_requested_group().await {
+            tracing::error!(error = ?error, "Error while joining requested group");
+        }
         let self_cc = self_c.clone();
-        let receive_task = tokio::spawn(async move {
-            while let Some(Ok(msg)) = ws_rx.next().await {
-                // Ignore messages that are not text
-                if let ws::Message::Text(txt) = msg {
-                    if let Err(error) = self_cc.handle_incoming_message(txt).await {
-                        tracing::error!(error = ?error, "Error while handling incoming message");
-                        break;
+        let connection_span = tracing::Span::current();
+        let ping = Arc::new(Notify::new());
+        let ping_c = ping.clone();
+        let awaiting_pong = Arc::new(AtomicBool::new(false));
+        let awaiting_pong_c = awaiting_pong.clone();
+        let receive_task = tokio::spawn(
+            async move {
+                let mut pinged = false;
+                loop {
+                    let next = match self_cc.options.idle_timeout {
+                        Some(idle_timeout) => {
+                            match tokio::time::timeout(idle_timeout, ws_rx.next()).await {
+                                Ok(next) => next,
+                                Err(_) if !pinged => {
+                                    tracing::debug!("Connection idle, sending ping");
+                                    pinged = true;
+                                    ping_c.notify_one();
+                                    continue;
+                                }
+                                Err(_) => {
+                                    tracing::info!("Closing idle connection");
+                                    break;
+                                }
+                            }
+                        }
+                        None => ws_rx.next().await,
+                    };
+                    let Some(Ok(msg)) = next else { break };
+                    pinged = false;
+                    if matches!(msg, ws::Message::Pong(_)) {
+                        awaiting_pong_c.store(false, Ordering::Relaxed);
+                    }
+
+                    // Ignore control messages
+                    if let Some(payload) = Codec::payload(msg) {
+                        if let Err(error) = self_cc.handle_incoming_message(payload).await {
+                            tracing::error!(error = ?error, "Error while handling incoming message");
+                            break;
+                        }
+                        // Subsequent spans of this connection inherit the label
+                        if let Some(label) = self_cc.state.get_client_label(&self_cc.client_id).await {
+                            tracing::Span::current().record("label", label);
+                        }
+                        if self_cc.goodbye.is_cancelled() {
+                            break;
+                        }
                     }
                 }
             }
-        });
+            .instrument(connection_span),
+        );
 
-        let send_task = tokio::spawn(async move {
-            while let Some(msg) = internal_rx.next().await {
-                if let Err(err) = ws_tx.send(ws::Message::Text(msg)).await {
-                    tracing::error!(error = ?err, "Error while sending message to websocket");
-                    break;
+        let shutdown = self_c.shutdown.clone();
+        let goodbye = self_c.goodbye.clone();
+        let disconnect = self_c.disconnect.clone();
+        let state = self_c.state.clone();
+        let codec = self_c.codec;
+        let mut keepalive = self_c.options.ping_interval.map(|period| {
+            let mut interval =
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            interval
+        });
+        let refresh_task = self_c.options.resume.is_some().then(|| {
+            let server = self_c.clone();
+            tokio::spawn(
+                async move {
+                    let start = tokio::time::Instant::now() + RESUME_REFRESH_INTERVAL;
+                    let mut interval = tokio::time::interval_at(start, RESUME_REFRESH_INTERVAL);
+                    loop {
+                        interval.tick().await;
+                        if let Err(error) = server.refresh_resume_token().await {
+                            tracing::error!(error = ?error, "Error while refreshing resume token");
+                        }
+                    }
+                }
+                .in_current_span(),
+            )
+        });
+        let mut send_task = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    msg = internal_rx.next() => {
+                        let Some(msg) = msg else { break };
+                        state.memory().release(msg.len());
+                        if let Err(err) = ws_tx.send(codec.message(msg)).await {
+                            tracing::error!(error = ?err, "Error while sending message to websocket");
+                            break;
+                        }
+                    }
+                    _ = ping.notified() => {
+                        if ws_tx.send(ws::Message::Ping(Vec::new())).await.is_err() {
+                            break;
+                        }
+                    }
+                    _ = async { keepalive.as_mut().unwrap().tick().await }, if keepalive.is_some() => {
+                        // The peer may be silent as long as it answers pings
+                        if awaiting_pong.swap(true, Ordering::Relaxed) {
+                            tracing::info!("Closing connection, keepalive ping not answered");
+                            let _ = ws_tx.send(ws::Message::Close(None)).await;
+                            break;
+                        }
+                        if ws_tx.send(ws::Message::Ping(Vec::new())).await.is_err() {
+                            break;
+                        }
+                    }
+                    _ = shutdown.cancelled() => {
+                        let req = json_rpc2::Request::new(
+                            None,
+                            ServerEvent::ServerShutdown.to_string(),
+                            None,
+                        );
+                        if let Ok(msg) = codec.encode(&req) {
+                            let _ = ws_tx.send(codec.message(msg)).await;
+                        }
+                        let _ = ws_tx.send(ws::Message::Close(None)).await;
+                        break;
+                    }
+                    _ = goodbye.cancelled() => {
+                        // Responses are queued before the token is cancelled,
+                        // flush them so the goodbye summary is delivered.
+                        while let Ok(msg) = internal_rx.as_mut().try_recv() {
+                            state.memory().release(msg.len());
+                            if ws_tx.send(codec.message(msg)).await.is_err() {
+                                break;
+                            }
+                        }
+                        let _ = ws_tx.send(ws::Message::Close(None)).await;
+                        break;
+                    }
+                    _ = disconnect.cancelled() => {
+                        let _ = ws_tx.send(ws::Message::Close(None)).await;
+                        break;
+                    }
                 }
             }
+            // Release messages that will never be delivered
+            let mut internal_rx = internal_rx.into_inner();
+            internal_rx.close();
+            while let Ok(msg) = internal_rx.try_recv() {
+                state.memory().release(msg.len());
+            }
         });
 
         tokio::select! {
             _ = receive_task => tracing::info!("Closing connection due to rx channel closed"),
-            _ = send_task => tracing::info!("Closing connection due to tx channel closed"),
+            _ = &mut send_task => tracing::info!("Closing connection due to tx channel closed, server shutdown or slow client"),
+        }
+        if let Some(refresh_task) = refresh_task {
+            refresh_task.abort();
+        }
+        // After a goodbye the summary must reach the client before its state is dropped.
+        if self_c.goodbye.is_cancelled() && !send_task.is_finished() {
+            let _ = send_task.await;
         }
 
-        // Perform any operation needed after connection closed
-        self_c.state.drop_client(self_c.client_id).await;
+        // Perform any operation needed after connection closed, clients
+        // that may resume keep their memberships until the window elapses.
+        // Slow clients are dropped right away, resuming would refill their buffer.
+        match &self_c.options.resume {
+            Some(resume)
+                if !self_c.goodbye.is_cancelled()
+                    && !self_c.shutdown.is_cancelled()
+                    && !self_c.disconnect.is_cancelled() =>
+            {
+                let window = resume.window();
+                let send_retry = self_c.options.send_retry;
+                let state = self_c.state.clone();
+                let client_id = self_c.client_id;
+                state.reserve_client(client_id, window).await;
+                tokio::spawn(async move {
+                    tokio::time::sleep(window).await;
+                    let (closed, promotions) = state.expire_reservation(client_id).await;
+                    notify_promotions(&state, send_retry, promotions).await;
+                    notify_group_closed(&state, send_retry, closed, GroupClosedReason::Empty).await;
+                });
+            }
+            _ => {
+                let (closed, promotions) = self_c.state.drop_client(self_c.client_id).await;
+                notify_promotions(&self_c.state, self_c.options.send_retry, promotions).await;
+                notify_group_closed(
+                    &self_c.state,
+                    self_c.options.send_retry,
+                    closed,
+                    GroupClosedReason::Empty,
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Sends the client id to the client.
+    async fn send_client_registered(&self) -> anyhow::Result<()> {
+        let notification = ClientRegisteredNotification {
+            client_id: self.client_id.public_id(),
+        };
+        let request = json_rpc2::Request::new(
+            None,
+            ServerEvent::ClientRegistered.to_string(),
+            Some(serde_json::to_value(notification)?),
+        );
+        self.send_rpc_request(&request, &self.client_id).await
+    }
+
+    /// Joins the group requested when connecting, if any, responding as if
+    /// the client had called `group_join` itself.
+    async fn join_requested_group(self: &Arc<Self>) -> anyhow::Result<()> {
+        let Some(group_join) = &self.group_join else {
+            return Ok(());
+        };
+        let request = json_rpc2::Request::new(
+            Some(GROUP_JOIN_REQUEST_ID.into()),
+            GroupMethod::GroupJoin.to_string(),
+            Some(serde_json::to_value(group_join)?),
+        );
+        self.handle_rpc_request(&request).await
+    }
+
+    /// Sends the resume token to the client if resuming is enabled.
+    async fn send_resume_token(&self) -> anyhow::Result<()> {
+        let Some(resume) = &self.options.resume else {
+            return Ok(());
+        };
+        let claims = resume.claims(self.client_id, &self.state.memberships(self.client_id));
+        let notification = ClientResumeTokenNotification {
+            token: resume.issue(&claims),
+            window_secs: resume.window().as_secs(),
+            expires_at: claims.expires_at,
+        };
+        *self.resume_claims.lock().unwrap() = Some(claims);
+        let request = json_rpc2::Request::new(
+            None,
+            ServerEvent::ClientResumeToken.to_string(),
+            Some(serde_json::to_value(notification)?),
+        );
+        self.send_rpc_request(&request, &self.client_id).await
     }
 
-    /// Handle incoming text message.
-    #[tracing::instrument(name = "Handling incoming message", skip_all, fields(client_id = self.client_id.to_string(), method))]
-    async fn handle_incoming_message(&self, msg: String) -> anyhow::Result<()> {
-        match json_rpc2::from_str(&msg) {
+    /// Sends a new resume token to the client if the memberships bound to the
+    /// last one changed or it's past half its lifetime.
+    async fn refresh_resume_token(&self) -> anyhow::Result<()> {
+        let Some(resume) = &self.options.resume else {
+            return Ok(());
+        };
+        let memberships = self.state.memberships(self.client_id);
+        let refresh = self
+            .resume_claims
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_none_or(|claims| resume.needs_refresh(claims, &memberships));
+        if !refresh {
+            return Ok(());
+        }
+        self.send_resume_token().await
+    }
+
+    /// Handle incoming message, encoded with the negotiated codec.
+    #[tracing::instrument(name = "Handling incoming message", skip_all, fields(client_id = self.client_id.to_string(), method, request_id))]
+    async fn handle_incoming_message(self: &Arc<Self>, payload: Vec<u8>) -> anyhow::Result<()> {
+        let allowed = self.acquire_rate_limit();
+        match self.codec.decode(&payload) {
+            Ok(req) if !allowed => {
+                tracing::warn!(
+                    method = req.method(),
+                    "Rate limit exceeded, rejecting request"
+                );
+                self.send_rpc_error(&req, RATE_LIMIT_EXCEEDED_CODE, "Rate limit exceeded")
+                    .await?;
+            }
+            Ok(req) if !self.track_method(req.method()) => {
+                tracing::warn!(
+                    method = req.method(),
+                    "Distinct methods limit exceeded, rejecting request"
+                );
+                self.send_rpc_error(
+                    &req,
+                    DISTINCT_METHODS_EXCEEDED_CODE,
+                    "Distinct methods limit exceeded",
+                )
+                .await?;
+            }
             Ok(req) => self.handle_rpc_request(&req).await?,
-            Err(err) => tracing::warn!(
-                client_id = self.client_id.to_string(),
-                message = msg,
-                error = ?err,
-                "Error decoding incoming message as json-rpc"
-            ),
+            Err(err) => {
+                tracing::warn!(
+                    client_id = self.client_id.to_string(),
+                    codec = self.codec.to_string(),
+                    message = String::from_utf8_lossy(&payload).into_owned(),
+                    error = ?err,
+                    "Error decoding incoming message as json-rpc"
+                );
+                let res = self.codec.decode_error(&payload, &err);
+                self.send_rpc_response(&res, &self.client_id).await?;
+            }
         };
         Ok(())
     }
 
+    /// Takes a token from the rate limiter, returning a boolean
+    /// indicating if the request is allowed.
+    fn acquire_rate_limit(&self) -> bool {
+        match &self.rate_limiter {
+            Some(limiter) => limiter
+                .lock()
+                .expect("rate limiter lock poisoned")
+                .try_acquire(),
+            None => true,
+        }
+    }
+
+    /// Records a method invoked by the client, returning a boolean
+    /// indicating if it's within the distinct methods limit.
+    fn track_method(&self, method: &str) -> bool {
+        let Some(max_distinct_methods) = self.options.max_distinct_methods else {
+            return true;
+        };
+        let mut invoked_methods = self
+            .invoked_methods
+            .lock()
+            .expect("invoked methods lock poisoned");
+        if invoked_methods.contains(method) {
+            return true;
+        }
+        if invoked_methods.len() >= max_distinct_methods {
+            return false;
+        }
+        invoked_methods.insert(method.to_string());
+        true
+    }
+
     /// Handle json-rpc request.
-    async fn handle_rpc_request(&self, req: &json_rpc2::Request) -> anyhow::Result<()> {
+    ///
+    /// The request is served in its own task, so one that times out still
+    /// runs to completion: its late response is dropped but the
+    /// notifications it produced are sent once it's done, other parties are
+    /// never left unaware of a change.
+    async fn handle_rpc_request(self: &Arc<Self>, req: &json_rpc2::Request) -> anyhow::Result<()> {
         tracing::Span::current().record("method", req.method());
+        // Notifications have no id, their responses can't be correlated anyway
+        if let Some(id) = req.id() {
+            tracing::Span::current().record("request_id", id.to_string());
+        }
 
         let notifications = Arc::new(Mutex::new(vec![]));
 
-        let res = self
-            .service_handler
-            .serve(
-                req,
-                (self.state.clone(), notifications.clone()),
-                self.client_id,
-            )
-            .await;
+        let mut serve = tokio::spawn({
+            let server = self.clone();
+            let req = req.clone();
+            let notifications = notifications.clone();
+            async move {
+                server
+                    .service_handler
+                    .serve(
+                        &req,
+                        (server.state.clone(), notifications),
+                        server.client_id,
+                    )
+                    .await
+            }
+            .in_current_span()
+        });
+        let res = match self.options.request_timeout {
+            Some(request_timeout) => {
+                match tokio::time::timeout(request_timeout, &mut serve).await {
+                    Ok(res) => res?,
+                    Err(_) => {
+                        tracing::warn!(
+                            method = req.method(),
+                            timeout_ms = request_timeout.as_millis() as u64,
+                            "Request timed out, dropping its response"
+                        );
+                        self.send_rpc_error(req, REQUEST_TIMEOUT_CODE, "Request timed out")
+                            .await?;
+                        let server = self.clone();
+                        let req = req.clone();
+                        tokio::spawn(
+                        async move {
+                            if serve.await.is_err() {
+                                return;
+                            }
+                            if let Err(error) = server.send_notifications(&notifications).await {
+                                tracing::error!(error = ?error, "Error while sending late notifications");
+                            }
+                            server.refresh_memberships_token(&req).await;
+                        }
+                        .in_current_span(),
+                    );
+                        return Ok(());
+                    }
+                }
+            }
+            None => serve.await?,
+        };
         if let Some(res) = res {
             self.send_rpc_response(&res, &self.client_id).await?;
+            if res.error().is_none() && req.method() == SystemMethod::SystemGoodbye.to_string() {
+                tracing::info!("Client said goodbye, closing connection");
+                self.goodbye.cancel();
+            }
         }
+        self.send_notifications(&notifications).await?;
+        self.refresh_memberships_token(req).await;
+        Ok(())
+    }
+
+    /// Refreshes the resume token after a request that may have changed
+    /// the memberships of the client, other requests never trigger the
+    /// lookup of its memberships.
+    async fn refresh_memberships_token(&self, req: &json_rpc2::Request) {
+        if !changes_memberships(req.method()) {
+            return;
+        }
+        if let Err(error) = self.refresh_resume_token().await {
+            tracing::error!(error = ?error, "Error while refreshing resume token");
+        }
+    }
+
+    /// Sends the notifications produced by a request.
+    ///
+    /// Notifications are handled in order so clients receive them in the
+    /// same order they were produced, each one is fanned out concurrently.
+    async fn send_notifications(
+        &self,
+        notifications: &Mutex<Vec<Notification>>,
+    ) -> anyhow::Result<()> {
         for notification in notifications.lock().await.iter() {
-            self.handle_rpc_notification(notification).await?; // TODO: perhaps this could be parallelized?
+            self.handle_rpc_notification(notification).await?;
         }
         Ok(())
     }
@@ -131,7 +817,8 @@ impl Server {
                 method,
                 message,
             } => {
-                let Ok(mut client_ids) = self.state.get_client_ids_from_group(group_id).await else {
+                let Ok(mut client_ids) = self.state.get_client_ids_from_group(group_id).await
+                else {
                     tracing::warn!(
                         group_id = group_id.to_string(),
                         "Group not found while sending group notification"
@@ -139,14 +826,13 @@ impl Server {
                     return Ok(());
                 };
                 let request = json_rpc2::Request::new(None, method.into(), Some(message.clone()));
-                let filtered_clients: Vec<ClientId> = client_ids
-                    .drain(..)
-                    .filter(|client_id| !filter.iter().any(|c| c == client_id))
-                    .collect();
-                for client_id in filtered_clients {
-                    self.send_rpc_request(&request, &client_id).await?;
+                if !self.fits_max_message_size(&request)? {
+                    return Ok(());
                 }
-                Ok(())
+                let filtered_clients = client_ids
+                    .drain(..)
+                    .filter(|client_id| !filter.iter().any(|c| c == client_id));
+                self.broadcast_rpc_request(&request, filtered_clients).await
             }
             Notification::Session {
                 group_id,
@@ -156,51 +842,118 @@ impl Server {
                 message,
             } => {
                 tracing::info!("Sending notification to session");
-                let Ok(mut client_ids) = self.state.get_client_ids_from_session(group_id, session_id).await else {
+                let Ok(mut client_ids) = self
+                    .state
+                    .get_client_ids_from_session(group_id, session_id)
+                    .await
+                else {
                     tracing::warn!(
                         group_id = group_id.to_string(),
                         session_id = session_id.to_string(),
                         "Session not found while sending session notification"
                     );
-                    return Ok(())
+                    return Ok(());
                 };
                 let request = json_rpc2::Request::new(None, method.into(), Some(message.clone()));
+                if !self.fits_max_message_size(&request)? {
+                    return Ok(());
+                }
                 let filtered_clients = client_ids
                     .drain(..)
-                    .filter(|client_id| !filter.iter().any(|c| c == client_id))
-                    .filter(|client_id| *client_id != self.client_id);
-                for client_id in filtered_clients {
-                    self.send_rpc_request(&request, &client_id).await?;
-                }
-                Ok(())
+                    .filter(|client_id| !filter.iter().any(|c| c == client_id));
+                self.broadcast_rpc_request(&request, filtered_clients).await
             }
             Notification::Relay { method, messages } => {
+                // Group messages by recipient to send them concurrently
+                // across clients but sequentially for each client.
+                let mut queues: Vec<(ClientId, Vec<json_rpc2::Request>)> = Vec::new();
+                let mut positions: HashMap<ClientId, usize> = HashMap::new();
                 for (client_id, message) in messages {
                     let request =
                         json_rpc2::Request::new(None, method.into(), Some(message.clone()));
-                    self.send_rpc_request(&request, client_id).await?;
+                    if !self.fits_max_message_size(&request)? {
+                        continue;
+                    }
+                    let position = *positions.entry(*client_id).or_insert_with(|| {
+                        queues.push((*client_id, Vec::new()));
+                        queues.len() - 1
+                    });
+                    queues[position].1.push(request);
                 }
+                try_join_all(queues.iter().map(|(client_id, requests)| async move {
+                    for request in requests {
+                        self.send_rpc_request(request, client_id).await?;
+                    }
+                    anyhow::Ok(())
+                }))
+                .await?;
                 Ok(())
             }
         }
     }
 
+    /// Returns a boolean indicating if the serialized notification fits in
+    /// the maximum message size, logging a warning otherwise.
+    ///
+    /// Oversized notifications are skipped since clients would reject them anyway.
+    /// The size is measured as JSON, recipients may negotiate different codecs.
+    fn fits_max_message_size(&self, req: &json_rpc2::Request) -> anyhow::Result<bool> {
+        let size = serde_json::to_vec(req)?.len();
+        if size > self.options.max_message_size {
+            tracing::warn!(
+                method = req.method(),
+                size,
+                max_message_size = self.options.max_message_size,
+                "Skipping notification exceeding the maximum message size"
+            );
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
     /// Sends json-rpc response.
     async fn send_rpc_response(
         &self,
         res: &json_rpc2::Response,
         client_id: &ClientId,
     ) -> anyhow::Result<()> {
-        tracing::debug!(client_id = client_id.to_string(), "Sending response");
-        let Some(tx) = self.state.get_client(client_id).await else {
+        tracing::debug!(
+            client_id = client_id.to_string(),
+            request_id = res.id().as_ref().map(ToString::to_string),
+            "Sending response"
+        );
+        let Some((tx, codec)) = self.state.get_client(client_id).await else {
             tracing::warn!(client_id = client_id.to_string(), "Client not found");
             return Ok(());
         };
-        let message = serde_json::to_string(&res)?;
-        tx.send(message)?;
+        let message = codec.encode(res)?;
+        enqueue(
+            &self.state,
+            self.options.send_retry,
+            client_id,
+            &tx,
+            message,
+        )
+        .await?;
         Ok(())
     }
 
+    /// Sends a json-rpc error response with an application defined code.
+    async fn send_rpc_error(
+        &self,
+        req: &json_rpc2::Request,
+        code: isize,
+        message: &str,
+    ) -> anyhow::Result<()> {
+        let err = json_rpc2::RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        };
+        self.send_rpc_response(&(req, err).into(), &self.client_id)
+            .await
+    }
+
     /// Sends json-rpc request. This method is especially used for notifications.
     async fn send_rpc_request(
         &self,
@@ -208,12 +961,42 @@ impl Server {
         client_id: &ClientId,
     ) -> anyhow::Result<()> {
         tracing::debug!(client_id = client_id.to_string(), "Sending request");
-        let Some(tx) = self.state.get_client(client_id).await else {
+        let Some((tx, codec)) = self.state.get_client(client_id).await else {
             tracing::warn!(client_id = client_id.to_string(), "Client not found");
             return Ok(());
         };
-        let message = serde_json::to_string(&req)?;
-        tx.send(message)?;
+        // Each recipient is encoded with its own codec
+        let message = codec.encode(req)?;
+        let size = message.len();
+        if !enqueue(
+            &self.state,
+            self.options.send_retry,
+            client_id,
+            &tx,
+            message,
+        )
+        .await?
+        {
+            return Ok(());
+        }
+        metrics::increment_counter!(NOTIFICATIONS_SENT);
+        if req.method() == SessionEvent::SessionMessage.to_string() {
+            metrics::increment_counter!(MESSAGES_RELAYED);
+            metrics::counter!(BYTES_RELAYED, size as u64);
+        }
+        Ok(())
+    }
+
+    /// Sends the same json-rpc request to many clients concurrently.
+    async fn broadcast_rpc_request(
+        &self,
+        req: &json_rpc2::Request,
+        client_ids: impl Iterator<Item = ClientId>,
+    ) -> anyhow::Result<()> {
+        try_join_all(
+            client_ids.map(|client_id| async move { self.send_rpc_request(req, &client_id).await }),
+        )
+        .await?;
         Ok(())
     }
 
@@ -222,3 +1005,1444 @@ impl Server {
         self.client_id
     }
 }
+
+/// Notifies the connected clients still part of removed groups that the
+/// groups were closed.
+async fn notify_group_closed(
+    state: &State,
+    send_retry: SendRetry,
+    closed: Vec<(Group, Vec<ClientId>)>,
+    reason: GroupClosedReason,
+) {
+    for (group, client_ids) in closed {
+        if client_ids.is_empty() {
+            continue;
+        }
+        let notification = GroupClosedNotification { group, reason };
+        if let Err(error) = notify(
+            state,
+            send_retry,
+            GroupEvent::GroupClosed.to_string(),
+            notification,
+            client_ids,
+        )
+        .await
+        {
+            tracing::error!(error = ?error, "Error while sending group closed notification");
+        }
+    }
+}
+
+/// Notifies standbys promoted to the party numbers of a dropped client,
+/// and the members of their group if the session became ready.
+async fn notify_promotions(state: &State, send_retry: SendRetry, promotions: Vec<Promotion>) {
+    for (group, session, promoted, party_number, threshold) in promotions {
+        tracing::info!(
+            session_id = session.id.to_string(),
+            party_number,
+            "Promoting standby client to session party"
+        );
+        let notification = SessionPromotedNotification {
+            group: group.clone(),
+            session: session.clone(),
+            party_number,
+        };
+        if let Err(error) = notify(
+            state,
+            send_retry,
+            SessionEvent::SessionPromoted.to_string(),
+            notification,
+            vec![promoted],
+        )
+        .await
+        {
+            tracing::error!(error = ?error, "Error while sending session promoted notification");
+        }
+        if !threshold {
+            continue;
+        }
+        let Ok(client_ids) = state.get_client_ids_from_group(&group.id).await else {
+            continue;
+        };
+        let notification = SessionReadyNotification {
+            group,
+            seed: session.seed.clone(),
+            parties: session.occupied_party_numbers.clone(),
+            session,
+        };
+        if let Err(error) = notify(
+            state,
+            send_retry,
+            SessionEvent::SessionReady.to_string(),
+            notification,
+            client_ids,
+        )
+        .await
+        {
+            tracing::error!(error = ?error, "Error while sending session ready notification");
+        }
+    }
+}
+
+/// Sends a json-rpc notification to the given clients, skipping the
+/// ones that are no longer connected.
+async fn notify(
+    state: &State,
+    send_retry: SendRetry,
+    method: String,
+    message: impl serde::Serialize,
+    client_ids: Vec<ClientId>,
+) -> anyhow::Result<()> {
+    let request = json_rpc2::Request::new(None, method, Some(serde_json::to_value(message)?));
+    for client_id in client_ids {
+        let Some((tx, codec)) = state.get_client(&client_id).await else {
+            continue;
+        };
+        let message = codec.encode(&request)?;
+        if !enqueue(state, send_retry, &client_id, &tx, message)
+            .await
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        metrics::increment_counter!(NOTIFICATIONS_SENT);
+    }
+    Ok(())
+}
+
+/// Queues a message to a client, accounting its size until it's dequeued.
+/// Returns a boolean indicating if the message was queued.
+///
+/// A client whose buffer is full isn't reading its socket fast enough, the
+/// message is retried according to `send_retry` to absorb bursts and the
+/// client is disconnected and dropped if the buffer doesn't drain, instead
+/// of buffering messages without bound.
+async fn enqueue(
+    state: &State,
+    send_retry: SendRetry,
+    client_id: &ClientId,
+    tx: &mpsc::Sender<Vec<u8>>,
+    mut message: Vec<u8>,
+) -> anyhow::Result<bool> {
+    let size = message.len();
+    state.memory().reserve(size);
+    let mut attempt = 0;
+    loop {
+        match tx.try_send(message) {
+            Ok(()) => return Ok(true),
+            Err(TrySendError::Full(returned)) if attempt < send_retry.attempts => {
+                tracing::debug!(
+                    client_id = client_id.to_string(),
+                    attempt,
+                    "Send buffer is full, retrying"
+                );
+                tokio::time::sleep(send_retry.delay(attempt)).await;
+                attempt += 1;
+                message = returned;
+            }
+            Err(TrySendError::Full(_)) => {
+                state.memory().release(size);
+                tracing::warn!(
+                    client_id = client_id.to_string(),
+                    capacity = tx.max_capacity(),
+                    "Dropping slow client, send buffer is full"
+                );
+                metrics::increment_counter!(SLOW_CLIENTS_DROPPED);
+                state.disconnect_client(client_id).await;
+                return Ok(false);
+            }
+            Err(err) => {
+                state.memory().release(size);
+                return Err(err.into());
+            }
+        }
+    }
+}
+
+/// Returns a boolean indicating if a method may change the group or
+/// session memberships of the client calling it.
+fn changes_memberships(method: &str) -> bool {
+    matches!(
+        GroupMethod::from_str(method),
+        Ok(GroupMethod::GroupCreate
+            | GroupMethod::GroupJoin
+            | GroupMethod::GroupLeave
+            | GroupMethod::GroupKick
+            | GroupMethod::GroupClose)
+    ) || matches!(
+        SessionMethod::from_str(method),
+        Ok(SessionMethod::SessionSignup
+            | SessionMethod::SessionLogin
+            | SessionMethod::SessionClaimVacant
+            | SessionMethod::SessionClose
+            | SessionMethod::SessionAbort)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        service::MEMORY_BUDGET_EXCEEDED_CODE,
+        state::{
+            group::{Group, GroupId},
+            parameters::Parameters,
+            session::{SessionId, SessionKind, SessionOptions},
+        },
+    };
+    use serde_json::{json, Value};
+
+    /// Registers a new connection with the given options, returning its
+    /// server and the messages sent to it.
+    async fn connect(
+        state: &Arc<State>,
+        options: ServerOptions,
+    ) -> (Arc<Server>, mpsc::Receiver<Vec<u8>>) {
+        let server = Arc::new(
+            Server::new(state.clone(), Arc::new(ServiceHandler::new())).with_options(options),
+        );
+        let (tx, rx) = mpsc::channel(DEFAULT_SEND_BUFFER_CAPACITY);
+        state
+            .add_client(
+                server.client_id(),
+                tx,
+                Codec::Json,
+                server.disconnect.clone(),
+            )
+            .await
+            .unwrap();
+        (server, rx)
+    }
+
+    /// Connects `n` clients signed up to a keygen session of a new group of
+    /// `n` parties, in party number order.
+    async fn session_parties(
+        state: &Arc<State>,
+        n: u16,
+    ) -> (
+        GroupId,
+        SessionId,
+        Vec<(Arc<Server>, mpsc::Receiver<Vec<u8>>)>,
+    ) {
+        let mut group = Group::new(
+            uuid::Uuid::new_v4(),
+            Parameters::new(n, 1).unwrap(),
+            None,
+            None,
+            ClientId::new_v4(),
+        );
+        let session_id = group
+            .add_session(SessionKind::Keygen, None, SessionOptions::default())
+            .unwrap()
+            .id;
+        let mut clients = Vec::new();
+        for _ in 0..n {
+            let (server, rx) = connect(state, ServerOptions::default()).await;
+            group.add_client(server.client_id()).unwrap();
+            let session = group.get_session_mut(&session_id).unwrap();
+            session.signup(server.client_id(), n).unwrap();
+            clients.push((server, rx));
+        }
+        let group_id = group.id();
+        state.insert_group(group);
+        (group_id, session_id, clients)
+    }
+
+    /// Returns the messages sent to a connection so far.
+    fn received(rx: &mut mpsc::Receiver<Vec<u8>>) -> Vec<Value> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|msg| serde_json::from_slice(&msg).unwrap())
+            .collect()
+    }
+
+    /// Sends a request, returning the json-rpc response.
+    async fn call(
+        server: &Arc<Server>,
+        rx: &mut mpsc::Receiver<Vec<u8>>,
+        method: &str,
+        params: Value,
+    ) -> Value {
+        let req = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+        server
+            .handle_incoming_message(req.to_string().into_bytes())
+            .await
+            .unwrap();
+        // Responses are sent before the notifications they trigger
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|msg| serde_json::from_slice::<Value>(&msg).unwrap())
+            .find(|msg| msg.get("id").is_some())
+            .expect("missing response")
+    }
+
+    /// Sends a notification, which is never answered.
+    async fn notify(server: &Arc<Server>, method: &str, params: Value) {
+        let req = json!({"jsonrpc": "2.0", "method": method, "params": params});
+        server
+            .handle_incoming_message(req.to_string().into_bytes())
+            .await
+            .unwrap();
+    }
+
+    /// Sends `requests` requests, returning which ones were rate limited.
+    async fn rate_limited(
+        server: &Arc<Server>,
+        rx: &mut mpsc::Receiver<Vec<u8>>,
+        requests: usize,
+    ) -> Vec<bool> {
+        let mut limited = Vec::new();
+        for _ in 0..requests {
+            let params = json!({"groupId": uuid::Uuid::new_v4()});
+            let res = call(server, rx, "group_stats", params).await;
+            limited.push(res["error"]["code"] == RATE_LIMIT_EXCEEDED_CODE);
+        }
+        limited
+    }
+
+    #[tokio::test]
+    async fn provisioned_client_ids_are_used() {
+        let state = Arc::new(State::new());
+        let client_id = state.new_client_id();
+        let server = Server::with_client_id(state, Arc::new(ServiceHandler::new()), client_id);
+        assert_eq!(server.client_id(), client_id);
+    }
+
+    #[tokio::test]
+    async fn generated_client_ids_are_distinct() {
+        let state = Arc::new(State::new());
+        let service_handler = Arc::new(ServiceHandler::new());
+        let first = Server::new(state.clone(), service_handler.clone());
+        let second = Server::new(state, service_handler);
+        assert_ne!(first.client_id(), second.client_id());
+    }
+
+    #[tokio::test]
+    async fn clients_are_notified_of_their_id() {
+        let state = Arc::new(State::new());
+        let (server, mut rx) = connect(&state, ServerOptions::default()).await;
+
+        server.send_client_registered().await.unwrap();
+        let messages = received(&mut rx);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["method"], "client_registered");
+        assert_eq!(
+            messages[0]["params"]["clientId"],
+            server.client_id().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_notifications_are_skipped() {
+        let state = Arc::new(State::new());
+        let options = ServerOptions {
+            max_message_size: 512,
+            ..Default::default()
+        };
+        let (sender, _) = connect(&state, options.clone()).await;
+        let (receiver, mut rx) = connect(&state, options).await;
+
+        let notification = Notification::Relay {
+            method: "session_message".into(),
+            messages: vec![
+                (receiver.client_id(), json!("x".repeat(1024))),
+                (receiver.client_id(), json!("small")),
+            ],
+        };
+        sender.handle_rpc_notification(&notification).await.unwrap();
+
+        // Only the message fitting the maximum size is delivered
+        let messages = received(&mut rx);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0]["params"], "small");
+    }
+
+    #[tokio::test]
+    async fn requests_beyond_the_burst_are_rejected_until_refilled() {
+        let state = Arc::new(State::new());
+        let options = ServerOptions {
+            rate_limit: Some(RateLimit {
+                per_second: 10,
+                burst: 2,
+            }),
+            ..Default::default()
+        };
+        let (server, mut rx) = connect(&state, options).await;
+
+        assert_eq!(
+            rate_limited(&server, &mut rx, 3).await,
+            [false, false, true]
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        assert_eq!(rate_limited(&server, &mut rx, 1).await, [false]);
+    }
+
+    #[tokio::test]
+    async fn connections_are_limited_separately() {
+        let state = Arc::new(State::new());
+        let options = ServerOptions {
+            rate_limit: Some(RateLimit {
+                per_second: 1,
+                burst: 1,
+            }),
+            ..Default::default()
+        };
+        let (first, mut first_rx) = connect(&state, options.clone()).await;
+        let (second, mut second_rx) = connect(&state, options).await;
+
+        assert_eq!(rate_limited(&first, &mut first_rx, 2).await, [false, true]);
+        assert_eq!(rate_limited(&second, &mut second_rx, 1).await, [false]);
+    }
+
+    #[tokio::test]
+    async fn methods_without_prefix_are_explained() {
+        let state = Arc::new(State::new());
+        let (server, mut rx) = connect(&state, ServerOptions::default()).await;
+
+        let res = call(&server, &mut rx, "create", json!({})).await;
+        assert_eq!(res["error"]["code"], -32601);
+        let data = res["error"]["data"].as_str().unwrap();
+        assert!(data.contains("prefix_action"));
+        assert!(data.contains("group, session"));
+
+        let res = call(&server, &mut rx, "foo_create", json!({})).await;
+        assert_eq!(res["error"]["code"], -32601);
+        assert!(res["error"]["data"].is_null());
+    }
+
+    #[tokio::test]
+    async fn relays_to_the_sender_are_rejected() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 2).await;
+        let (sender, rx) = &mut clients[0];
+
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "receiver": 1,
+            "message": "self",
+        });
+        let res = call(sender, rx, "session_message", message).await;
+        assert_eq!(res["error"]["code"], -32602);
+        assert!(received(rx).is_empty());
+    }
+
+    #[tokio::test]
+    async fn loopback_relays_to_the_sender() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 2).await;
+        let (sender, rx) = &mut clients[0];
+
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "receiver": 1,
+            "message": "self",
+            "loopback": true,
+        });
+        notify(sender, "session_message", message).await;
+        let notifications = received(rx);
+        assert_eq!(notifications[0]["params"]["sender"], 1);
+        assert_eq!(notifications[0]["params"]["message"], "self");
+    }
+
+    #[tokio::test]
+    async fn sessions_of_a_group_are_listed() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 2).await;
+        let (client, rx) = &mut clients[0];
+
+        let res = call(client, rx, "session_list", json!({"groupId": group_id})).await;
+        let sessions = res["result"]["sessions"].as_array().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0]["id"], session_id.to_string());
+
+        let params = json!({"groupId": uuid::Uuid::new_v4()});
+        let res = call(client, rx, "session_list", params).await;
+        assert_eq!(res["error"]["code"], -32010);
+    }
+
+    #[tokio::test]
+    async fn methods_beyond_the_distinct_limit_are_rejected() {
+        let state = Arc::new(State::new());
+        let options = ServerOptions {
+            max_distinct_methods: Some(2),
+            ..Default::default()
+        };
+        let (server, mut rx) = connect(&state, options).await;
+        let mut limited = Vec::new();
+        for method in [
+            "group_stats",
+            "session_list",
+            "session_status",
+            "group_stats",
+            "session_status",
+        ] {
+            let res = call(&server, &mut rx, method, json!({})).await;
+            limited.push(res["error"]["code"] == DISTINCT_METHODS_EXCEEDED_CODE);
+        }
+        // Methods already invoked remain available
+        assert_eq!(limited, [false, false, true, false, true]);
+    }
+
+    #[tokio::test]
+    async fn group_stats_are_only_shown_to_members() {
+        let state = Arc::new(State::new());
+        let (group_id, _, mut clients) = session_parties(&state, 2).await;
+        let (outsider, mut outsider_rx) = connect(&state, ServerOptions::default()).await;
+        let params = json!({"groupId": group_id});
+
+        let res = call(&outsider, &mut outsider_rx, "group_stats", params.clone()).await;
+        assert_eq!(res["error"]["code"], -32020);
+        let (client, rx) = &mut clients[0];
+        let res = call(client, rx, "group_stats", params).await;
+        assert!(res["result"]["stats"].is_object());
+    }
+
+    #[tokio::test]
+    async fn group_info_exposes_counts_only() {
+        let state = Arc::new(State::new());
+        let (group_id, _, mut clients) = session_parties(&state, 2).await;
+        let (client, rx) = &mut clients[0];
+
+        let res = call(client, rx, "group_info", json!({"groupId": group_id})).await;
+        assert_eq!(res["result"]["clientCount"], 2);
+        assert_eq!(res["result"]["isFull"], true);
+        assert_eq!(res["result"]["parameters"]["n"], 2);
+        assert!(res["result"].get("clients").is_none());
+    }
+
+    #[tokio::test]
+    async fn goodbye_summarizes_memberships_and_closes() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 2).await;
+        let (client, rx) = &mut clients[1];
+
+        let res = call(client, rx, "system_goodbye", json!({})).await;
+        let groups = res["result"]["groups"].as_array().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0]["groupId"], group_id.to_string());
+        let sessions = groups[0]["sessions"].as_array().unwrap();
+        assert_eq!(sessions[0]["sessionId"], session_id.to_string());
+        assert_eq!(sessions[0]["partyNumber"], 2);
+        assert!(client.goodbye.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn relays_preserve_the_order_of_each_client() {
+        let state = Arc::new(State::new());
+        let (_, _, mut clients) = session_parties(&state, 3).await;
+        let receivers = [clients[1].0.client_id(), clients[2].0.client_id()];
+        let messages = (0..6).map(|i| (receivers[i % 2], json!(i))).collect();
+        let notification = Notification::Relay {
+            method: "session_message".into(),
+            messages,
+        };
+        clients[0]
+            .0
+            .handle_rpc_notification(&notification)
+            .await
+            .unwrap();
+
+        for (offset, (_, rx)) in clients[1..].iter_mut().enumerate() {
+            let params: Vec<_> = received(rx)
+                .into_iter()
+                .map(|msg| msg["params"].clone())
+                .collect();
+            assert_eq!(params, [offset, offset + 2, offset + 4]);
+        }
+    }
+
+    #[tokio::test]
+    async fn large_group_fan_out_is_not_serialized_behind_slow_clients() {
+        const FAST: usize = 1000;
+        const SLOW: usize = 40;
+        const BACKOFF: Duration = Duration::from_millis(50);
+        let state = Arc::new(State::new());
+        let mut group = Group::new(
+            uuid::Uuid::new_v4(),
+            Parameters::new((FAST + SLOW) as u16, 1).unwrap(),
+            None,
+            None,
+            ClientId::new_v4(),
+        );
+        let (mut fast, mut slow) = (Vec::new(), Vec::new());
+        for i in 0..FAST + SLOW {
+            let client_id = state.new_client_id();
+            // Slow clients have a full buffer, every send waits for its retry
+            let (tx, rx) = mpsc::channel(1);
+            if i >= FAST {
+                tx.try_send(b"{}".to_vec()).unwrap();
+            }
+            let disconnect = CancellationToken::new();
+            state
+                .add_client(client_id, tx, Codec::Json, disconnect.clone())
+                .await
+                .unwrap();
+            group.add_client(client_id).unwrap();
+            if i < FAST {
+                fast.push(rx);
+            } else {
+                slow.push((rx, disconnect));
+            }
+        }
+        let group_id = group.id();
+        state.insert_group(group);
+        let options = ServerOptions {
+            send_retry: SendRetry {
+                attempts: 1,
+                backoff: BACKOFF,
+            },
+            ..Default::default()
+        };
+        let (server, _rx) = connect(&state, options).await;
+
+        let notification = Notification::Group {
+            group_id,
+            filter: vec![],
+            method: "group_message".into(),
+            message: json!("hello"),
+        };
+        let start = tokio::time::Instant::now();
+        server.handle_rpc_notification(&notification).await.unwrap();
+        let elapsed = start.elapsed();
+
+        // Sent one at a time, the slow clients alone would take `SLOW * BACKOFF`
+        assert!(
+            elapsed < BACKOFF * SLOW as u32 / 4,
+            "fan-out took {elapsed:?}"
+        );
+        for rx in &mut fast {
+            assert_eq!(received(rx).len(), 1);
+        }
+        // Slow clients are dropped once their retry fails, holding only the
+        // message that filled their buffer
+        for (rx, disconnect) in &mut slow {
+            assert_eq!(received(rx).len(), 1);
+            assert!(disconnect.is_cancelled());
+        }
+    }
+
+    #[tokio::test]
+    async fn session_broadcasts_reach_every_other_party() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 5).await;
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "message": "hello",
+        });
+        notify(&clients[0].0, "session_message", message).await;
+
+        // The sender is left out
+        assert!(received(&mut clients[0].1).is_empty());
+        for (_, rx) in &mut clients[1..] {
+            assert_eq!(received(rx).len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn cbor_messages_are_relayed_untouched() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 2).await;
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "message": "oWFhGCo=",
+            "encoding": "cborBase64",
+        });
+        notify(&clients[0].0, "session_message", message).await;
+
+        let received = received(&mut clients[1].1);
+        assert_eq!(received[0]["params"]["message"], "oWFhGCo=");
+        assert_eq!(received[0]["params"]["encoding"], "cborBase64");
+        let (client, rx) = &mut clients[0];
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "message": "not hex",
+            "encoding": "cborHex",
+        });
+        let res = call(client, rx, "session_message", message).await;
+        assert_eq!(res["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn senders_are_told_of_disconnected_receivers() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 3).await;
+        state
+            .reserve_client(clients[1].0.client_id(), Duration::from_secs(60))
+            .await;
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "receiver": [2, 3],
+            "message": "hello",
+        });
+        notify(&clients[0].0, "session_message", message).await;
+
+        let sent = received(&mut clients[0].1);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0]["method"], "session_message_undeliverable");
+        assert_eq!(sent[0]["params"]["partyNumber"], 2);
+        let relayed = received(&mut clients[2].1);
+        assert_eq!(relayed.len(), 1);
+        assert_eq!(relayed[0]["method"], "session_message");
+    }
+
+    #[tokio::test]
+    async fn only_broadcasts_are_limited_in_size() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 2).await;
+        let handler = ServiceHandler::builder()
+            .with_max_broadcast_size(Some(8))
+            .with_default_services();
+        Arc::get_mut(&mut clients[0].0).unwrap().service_handler = Arc::new(handler.build());
+        let (client, rx) = &mut clients[0];
+        let message = |receiver: Option<Vec<u16>>| {
+            json!({
+                "groupId": group_id,
+                "sessionId": session_id,
+                "receiver": receiver,
+                "message": "longer than eight bytes",
+            })
+        };
+
+        let res = call(client, rx, "session_message", message(None)).await;
+        assert_eq!(
+            res["error"]["code"],
+            crate::service::BROADCAST_SIZE_EXCEEDED_CODE
+        );
+        notify(client, "session_message", message(Some(vec![2]))).await;
+        assert!(received(rx).is_empty());
+        assert_eq!(received(&mut clients[1].1).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn session_messages_are_sequenced() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 3).await;
+        for sender in [0, 1, 0] {
+            let message = json!({
+                "groupId": group_id,
+                "sessionId": session_id,
+                "message": "hello",
+            });
+            notify(&clients[sender].0, "session_message", message).await;
+        }
+
+        let seqs: Vec<_> = received(&mut clients[2].1)
+            .iter()
+            .map(|message| message["params"]["seq"].clone())
+            .collect();
+        assert_eq!(seqs, [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn values_are_only_replaced_before_the_session_is_ready() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 3).await;
+        let params = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "value": "new value",
+        });
+        let (client, rx) = &mut clients[0];
+        let res = call(client, rx, "session_set_value", params.clone()).await;
+        assert_eq!(res["error"]["code"], -32602);
+
+        state
+            .leave_group(group_id, clients[2].0.client_id())
+            .await
+            .unwrap();
+        let (client, rx) = &mut clients[0];
+        let res = call(client, rx, "session_set_value", params).await;
+        assert_eq!(res["result"]["session"]["value"], "new value");
+        let updates = received(&mut clients[1].1);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0]["method"], "session_value_updated");
+        assert_eq!(updates[0]["params"]["session"]["value"], "new value");
+    }
+
+    #[tokio::test]
+    async fn session_notifications_only_skip_filtered_clients() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 3).await;
+        let notification = Notification::Session {
+            group_id,
+            session_id,
+            filter: vec![clients[1].0.client_id()],
+            method: "session_message".into(),
+            message: json!("hello"),
+        };
+        clients[0]
+            .0
+            .handle_rpc_notification(&notification)
+            .await
+            .unwrap();
+
+        let counts: Vec<_> = clients
+            .iter_mut()
+            .map(|(_, rx)| received(rx).len())
+            .collect();
+        assert_eq!(counts, [1, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn sessions_and_messages_are_rejected_while_over_budget() {
+        const BUDGET: usize = 1 << 20;
+        let state = Arc::new(State::new().with_memory_budget(Some(BUDGET)));
+        let (group_id, session_id, mut clients) = session_parties(&state, 2).await;
+        let (client, rx) = &mut clients[0];
+
+        state.memory().reserve(BUDGET);
+        let params = json!({"groupId": group_id, "kind": "keygen"});
+        let res = call(client, rx, "session_create", params).await;
+        assert_eq!(res["error"]["code"], MEMORY_BUDGET_EXCEEDED_CODE);
+        let params = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "receiver": 2,
+            "message": "round 1",
+        });
+        let res = call(client, rx, "session_message", params).await;
+        assert_eq!(res["error"]["code"], MEMORY_BUDGET_EXCEEDED_CODE);
+
+        state.memory().release(BUDGET);
+        let params = json!({"groupId": group_id, "kind": "keygen"});
+        let res = call(client, rx, "session_create", params).await;
+        assert!(res.get("error").is_none());
+    }
+
+    #[tokio::test]
+    async fn queued_messages_are_accounted() {
+        let state = Arc::new(State::new());
+        let (server, mut rx) = connect(&state, ServerOptions::default()).await;
+        let notification = Notification::Relay {
+            method: "session_message".into(),
+            messages: vec![(server.client_id(), json!("hello"))],
+        };
+        server.handle_rpc_notification(&notification).await.unwrap();
+
+        let queued = rx.try_recv().unwrap();
+        assert_eq!(state.memory().used(), queued.len());
+    }
+
+    #[tokio::test]
+    async fn ready_is_replayed_to_the_requester_only() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 2).await;
+
+        let params = json!({"groupId": group_id, "sessionId": session_id});
+        let (client, rx) = &mut clients[1];
+        let res = call(client, rx, "session_ready_replay", params).await;
+        assert_eq!(res["result"]["ready"], true);
+        let ready = received(rx);
+        assert_eq!(ready[0]["method"], "session_ready");
+        assert_eq!(ready[0]["params"]["session"]["id"], session_id.to_string());
+        assert!(received(&mut clients[0].1).is_empty());
+    }
+
+    #[tokio::test]
+    async fn labels_are_sanitized_and_truncated() {
+        use crate::service::system_service::MAX_LABEL_LENGTH;
+
+        let state = Arc::new(State::new());
+        let (server, mut rx) = connect(&state, ServerOptions::default()).await;
+
+        let params = json!({"label": " worker 1/eu\n"});
+        let res = call(&server, &mut rx, "system_set_label", params).await;
+        assert_eq!(res["result"]["label"], "worker_1_eu");
+        let label = state.get_client_label(&server.client_id()).await;
+        assert_eq!(label.as_deref(), Some("worker_1_eu"));
+
+        let params = json!({"label": "a".repeat(MAX_LABEL_LENGTH + 10)});
+        let res = call(&server, &mut rx, "system_set_label", params).await;
+        assert_eq!(res["result"]["label"], "a".repeat(MAX_LABEL_LENGTH));
+
+        let params = json!({"label": "  "});
+        let res = call(&server, &mut rx, "system_set_label", params).await;
+        assert_eq!(res["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn messages_are_relayed_once_to_each_receiver() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 4).await;
+
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "receiver": [2, 3, 3],
+            "message": "round 1",
+        });
+        notify(&clients[0].0, "session_message", message).await;
+        let counts: Vec<_> = clients
+            .iter_mut()
+            .map(|(_, rx)| received(rx).len())
+            .collect();
+        assert_eq!(counts, [0, 1, 1, 0]);
+    }
+
+    #[tokio::test]
+    async fn invalid_receivers_are_rejected() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 3).await;
+
+        for (receiver, code) in [(json!([]), -32602), (json!([2, 5]), -32012)] {
+            let message = json!({
+                "groupId": group_id,
+                "sessionId": session_id,
+                "receiver": receiver,
+                "message": "round 1",
+            });
+            let (sender, rx) = &mut clients[0];
+            let res = call(sender, rx, "session_message", message).await;
+            assert_eq!(res["error"]["code"], code);
+        }
+        // Nothing was relayed to the valid receiver
+        assert!(received(&mut clients[1].1).is_empty());
+    }
+
+    #[tokio::test]
+    async fn acknowledgements_are_forwarded_to_the_sender() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 2).await;
+
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "receiver": 2,
+            "message": "round 1",
+            "ack": true,
+        });
+        let (sender, rx) = &mut clients[0];
+        let res = call(sender, rx, "session_message", message).await;
+        let message_id = res["result"]["messageId"].clone();
+        let (receiver, rx) = &mut clients[1];
+        let relayed = received(rx);
+        assert_eq!(relayed[0]["params"]["messageId"], message_id);
+
+        let ack = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "messageId": message_id,
+        });
+        notify(receiver, "session_message_ack", ack.clone()).await;
+        let acks = received(&mut clients[0].1);
+        assert_eq!(acks[0]["method"], "session_message_ack");
+        assert_eq!(acks[0]["params"]["receiver"], 2);
+
+        // Messages are acknowledged only once
+        let (receiver, rx) = &mut clients[1];
+        let res = call(receiver, rx, "session_message_ack", ack).await;
+        assert_eq!(res["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn group_broadcasts_reach_the_other_members() {
+        let state = Arc::new(State::new());
+        let (group_id, _, mut clients) = session_parties(&state, 3).await;
+        let params = json!({"groupId": group_id, "message": "hello"});
+        notify(&clients[0].0, "group_broadcast", params).await;
+
+        assert!(received(&mut clients[0].1).is_empty());
+        for (_, rx) in &mut clients[1..] {
+            let messages = received(rx);
+            assert_eq!(messages.len(), 1);
+            assert_eq!(messages[0]["method"], "group_message");
+            assert_eq!(messages[0]["params"]["message"], "hello");
+        }
+
+        let (outsider, mut rx) = connect(&state, ServerOptions::default()).await;
+        let params = json!({"groupId": group_id, "message": "hello"});
+        let res = call(&outsider, &mut rx, "group_broadcast", params).await;
+        assert_eq!(res["error"]["code"], -32020);
+        assert!(received(&mut clients[1].1).is_empty());
+    }
+
+    #[tokio::test]
+    async fn session_operations_of_non_members_are_rejected() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 2).await;
+        let (outsider, mut rx) = connect(&state, ServerOptions::default()).await;
+
+        let ids = json!({"groupId": group_id, "sessionId": session_id});
+        for (method, params) in [
+            (
+                "session_create",
+                json!({"groupId": group_id, "kind": "sign"}),
+            ),
+            ("session_signup", ids.clone()),
+            (
+                "session_login",
+                json!({"groupId": group_id, "sessionId": session_id, "partyNumber": 1}),
+            ),
+            (
+                "session_message",
+                json!({"groupId": group_id, "sessionId": session_id, "message": "x"}),
+            ),
+            ("session_list", json!({"groupId": group_id})),
+            ("session_status", ids.clone()),
+            ("session_close", ids.clone()),
+        ] {
+            let res = call(&outsider, &mut rx, method, params).await;
+            assert_eq!(res["error"]["code"], -32020, "{method}");
+        }
+        for (_, rx) in &mut clients {
+            assert!(received(rx).is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn groups_are_joined_by_join_code() {
+        let state = Arc::new(State::new());
+        let (creator, mut creator_rx) = connect(&state, ServerOptions::default()).await;
+        let params = json!({"parameters": {"n": 2, "t": 1}, "joinCode": true});
+        let res = call(&creator, &mut creator_rx, "group_create", params).await;
+        let group = &res["result"]["group"];
+        let join_code = group["joinCode"].as_str().unwrap();
+
+        let (joiner, mut rx) = connect(&state, ServerOptions::default()).await;
+        let res = call(
+            &joiner,
+            &mut rx,
+            "group_join",
+            json!({"joinCode": join_code}),
+        )
+        .await;
+        assert_eq!(res["result"]["group"]["id"], group["id"]);
+        let res = call(&joiner, &mut rx, "group_join", json!({})).await;
+        assert_eq!(res["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn session_ready_lists_the_occupied_party_numbers() {
+        let state = Arc::new(State::new());
+        let mut group = Group::new(
+            uuid::Uuid::new_v4(),
+            Parameters::new(3, 1).unwrap(),
+            None,
+            None,
+            ClientId::new_v4(),
+        );
+        let session_id = group
+            .add_session(SessionKind::Keygen, None, SessionOptions::default())
+            .unwrap()
+            .id;
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            let (server, rx) = connect(&state, ServerOptions::default()).await;
+            group.add_client(server.client_id()).unwrap();
+            clients.push((server, rx));
+        }
+        let session = group.get_session_mut(&session_id).unwrap();
+        session.login(clients[0].0.client_id(), 3).unwrap();
+        session.login(clients[1].0.client_id(), 1).unwrap();
+        let group_id = group.id();
+        state.insert_group(group);
+
+        let (client, rx) = &mut clients[2];
+        let params = json!({"groupId": group_id, "sessionId": session_id});
+        let res = call(client, rx, "session_signup", params).await;
+        assert_eq!(res["result"]["partyNumber"], 2);
+        let ready = received(rx);
+        assert_eq!(ready[0]["method"], "session_ready");
+        assert_eq!(ready[0]["params"]["parties"], json!([1, 2, 3]));
+    }
+
+    #[tokio::test]
+    async fn created_sessions_are_only_announced_to_invitees() {
+        let state = Arc::new(State::new());
+        let (group_id, _, mut clients) = session_parties(&state, 4).await;
+        let invitee = clients[2].0.client_id();
+        let creator = clients[0].0.client_id();
+        let params = json!({
+            "groupId": group_id,
+            "kind": "sign",
+            "invitees": [invitee, invitee, creator],
+        });
+        let (client, rx) = &mut clients[0];
+        let res = call(client, rx, "session_create", params).await;
+        assert!(res.get("error").is_none());
+
+        let counts: Vec<_> = clients
+            .iter_mut()
+            .map(|(_, rx)| received(rx).len())
+            .collect();
+        assert_eq!(counts, [0, 0, 1, 0]);
+
+        let params = json!({
+            "groupId": group_id,
+            "kind": "sign",
+            "invitees": [uuid::Uuid::new_v4()],
+        });
+        let (client, rx) = &mut clients[0];
+        let res = call(client, rx, "session_create", params).await;
+        assert_eq!(res["error"]["code"], -32025);
+    }
+
+    #[tokio::test]
+    async fn state_errors_are_answered_with_their_own_code() {
+        let state = Arc::new(State::new());
+        let (group_id, _, _) = session_parties(&state, 2).await;
+        let (client, mut rx) = connect(&state, ServerOptions::default()).await;
+
+        let params = json!({"groupId": uuid::Uuid::new_v4()});
+        let res = call(&client, &mut rx, "group_join", params).await;
+        assert_eq!(res["error"]["code"], -32010);
+        let res = call(&client, &mut rx, "group_join", json!({"groupId": group_id})).await;
+        assert_eq!(res["error"]["code"], -32030);
+        assert_eq!(
+            res["error"]["message"],
+            format!("group `{group_id}` is full")
+        );
+    }
+
+    #[tokio::test]
+    async fn resume_tokens_are_refreshed_only_after_membership_changes() {
+        let state = Arc::new(State::new());
+        let group = state
+            .add_group(
+                ClientId::new_v4(),
+                Parameters::new(3, 1).unwrap(),
+                None,
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+        let options = ServerOptions {
+            resume: Some(Resume::new(Duration::from_secs(30))),
+            ..Default::default()
+        };
+        let (client, mut rx) = connect(&state, options).await;
+        let is_token = |msg: &Value| msg["method"] == "client_resume_token";
+
+        let params = json!({"groupId": group.id});
+        call(&client, &mut rx, "group_info", params.clone()).await;
+        assert!(!received(&mut rx).iter().any(is_token));
+        let res = call(&client, &mut rx, "group_join", params.clone()).await;
+        assert!(res.get("error").is_none());
+        assert!(received(&mut rx).iter().any(is_token));
+        // Tokens are not reissued while the memberships are unchanged
+        call(&client, &mut rx, "group_info", params.clone()).await;
+        call(&client, &mut rx, "group_leave", params.clone()).await;
+        assert_eq!(
+            received(&mut rx).iter().filter(|msg| is_token(msg)).count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn relays_are_reported_to_the_observer() {
+        use crate::{service::relay_observer::RelayObserver, state::session::SessionPartyNumber};
+
+        type Relay = (SessionPartyNumber, Option<SessionPartyNumber>, usize);
+
+        #[derive(Default)]
+        struct Recorder(StdMutex<Vec<Relay>>);
+
+        impl RelayObserver for Recorder {
+            fn on_relay(
+                &self,
+                _group_id: GroupId,
+                _session_id: SessionId,
+                sender: SessionPartyNumber,
+                receiver: Option<SessionPartyNumber>,
+                byte_len: usize,
+            ) {
+                self.0.lock().unwrap().push((sender, receiver, byte_len));
+            }
+        }
+
+        let state = Arc::new(State::new());
+        let (group_id, session_id, clients) = session_parties(&state, 3).await;
+        let recorder = Arc::new(Recorder::default());
+        let service_handler = ServiceHandler::builder()
+            .with_relay_observer(recorder.clone())
+            .with_default_services()
+            .build();
+        let sender = Arc::new(Server::with_client_id(
+            state.clone(),
+            Arc::new(service_handler),
+            clients[0].0.client_id(),
+        ));
+
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "receiver": [2, 3],
+            "message": "round 1",
+        });
+        notify(&sender, "session_message", message).await;
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "message": "round 2",
+        });
+        notify(&sender, "session_message", message).await;
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [(1, Some(2), 9), (1, Some(3), 9), (1, None, 9)]
+        );
+    }
+
+    #[tokio::test]
+    async fn broadcasts_skip_excluded_parties() {
+        let state = Arc::new(State::new());
+        let (group_id, session_id, mut clients) = session_parties(&state, 4).await;
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "exclude": [3],
+            "message": "round 1",
+        });
+        notify(&clients[0].0, "session_message", message).await;
+        let counts: Vec<_> = clients
+            .iter_mut()
+            .map(|(_, rx)| received(rx).len())
+            .collect();
+        assert_eq!(counts, [0, 1, 0, 1]);
+
+        let message = json!({
+            "groupId": group_id,
+            "sessionId": session_id,
+            "receiver": 2,
+            "exclude": [3],
+            "message": "round 1",
+        });
+        let (sender, rx) = &mut clients[0];
+        let res = call(sender, rx, "session_message", message).await;
+        assert_eq!(res["error"]["code"], -32602);
+    }
+
+    #[tokio::test]
+    async fn only_the_groups_of_the_caller_are_listed() {
+        let state = Arc::new(State::new());
+        let (group_id, _, mut clients) = session_parties(&state, 2).await;
+        session_parties(&state, 2).await;
+        let (client, rx) = &mut clients[0];
+
+        let res = call(client, rx, "group_list_mine", json!({})).await;
+        assert_eq!(res["result"]["groupIds"], json!([group_id]));
+        let (outsider, mut rx) = connect(&state, ServerOptions::default()).await;
+        let res = call(&outsider, &mut rx, "group_list_mine", json!({})).await;
+        assert_eq!(res["result"]["groupIds"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn messages_are_retried_while_the_buffer_drains() {
+        let state = State::new();
+        let client_id = state.new_client_id();
+        let (tx, mut rx) = mpsc::channel(1);
+        let disconnect = CancellationToken::new();
+        state
+            .add_client(client_id, tx.clone(), Codec::Json, disconnect.clone())
+            .await
+            .unwrap();
+        let send_retry = SendRetry {
+            attempts: 3,
+            backoff: Duration::from_millis(5),
+        };
+
+        assert!(
+            enqueue(&state, send_retry, &client_id, &tx, b"first".to_vec())
+                .await
+                .unwrap()
+        );
+        let reader = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(2)).await;
+            let first = rx.recv().await.unwrap();
+            (first, rx.recv().await.unwrap())
+        });
+        assert!(
+            enqueue(&state, send_retry, &client_id, &tx, b"second".to_vec())
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            reader.await.unwrap(),
+            (b"first".to_vec(), b"second".to_vec())
+        );
+        assert!(!disconnect.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn clients_with_a_full_buffer_are_disconnected() {
+        let state = State::new();
+        let client_id = state.new_client_id();
+        let (tx, mut rx) = mpsc::channel(1);
+        let disconnect = CancellationToken::new();
+        state
+            .add_client(client_id, tx.clone(), Codec::Json, disconnect.clone())
+            .await
+            .unwrap();
+
+        assert!(enqueue(
+            &state,
+            SendRetry::default(),
+            &client_id,
+            &tx,
+            b"first".to_vec()
+        )
+        .await
+        .unwrap());
+        assert!(!disconnect.is_cancelled());
+        assert!(!enqueue(
+            &state,
+            SendRetry::default(),
+            &client_id,
+            &tx,
+            b"second".to_vec()
+        )
+        .await
+        .unwrap());
+        assert!(disconnect.is_cancelled());
+        assert_eq!(rx.try_recv().unwrap(), b"first");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn slow_requests_time_out() {
+        /// Service that never answers in time.
+        struct SlowService;
+
+        #[axum::async_trait]
+        impl crate::service::Service for SlowService {
+            async fn handle(
+                &self,
+                _request: &json_rpc2::Request,
+                _ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+                _client_id: ClientId,
+            ) -> Result<Option<json_rpc2::Response>, json_rpc2::Error> {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(None)
+            }
+        }
+
+        let state = Arc::new(State::new());
+        let handler = ServiceHandler::builder()
+            .register_service("slow".into(), Box::new(SlowService))
+            .build();
+        let server = Arc::new(Server::new(state.clone(), Arc::new(handler)).with_options(
+            ServerOptions {
+                request_timeout: Some(Duration::from_millis(10)),
+                ..Default::default()
+            },
+        ));
+        let (tx, mut rx) = mpsc::channel(DEFAULT_SEND_BUFFER_CAPACITY);
+        state
+            .add_client(
+                server.client_id(),
+                tx,
+                Codec::Json,
+                server.disconnect.clone(),
+            )
+            .await
+            .unwrap();
+
+        let res = call(&server, &mut rx, "slow_request", json!({})).await;
+        assert_eq!(res["error"]["code"], REQUEST_TIMEOUT_CODE);
+    }
+
+    #[tokio::test]
+    async fn timed_out_requests_still_send_their_notifications() {
+        /// Service joining a group after the request timed out.
+        struct SlowJoinService(GroupId);
+
+        #[axum::async_trait]
+        impl crate::service::Service for SlowJoinService {
+            async fn handle(
+                &self,
+                request: &json_rpc2::Request,
+                ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+                client_id: ClientId,
+            ) -> Result<Option<json_rpc2::Response>, json_rpc2::Error> {
+                let (state, notifications) = ctx;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                state.join_group(self.0, client_id).await.unwrap();
+                notifications.lock().await.push(Notification::Group {
+                    group_id: self.0,
+                    filter: vec![client_id],
+                    method: "group_client_joined".into(),
+                    message: json!({}),
+                });
+                Ok(Some((request, json!({})).into()))
+            }
+        }
+
+        let state = Arc::new(State::new());
+        let (creator, mut creator_rx) = connect(&state, ServerOptions::default()).await;
+        let params = json!({"parameters": {"n": 3, "t": 1}});
+        let res = call(&creator, &mut creator_rx, "group_create", params).await;
+        let group_id: GroupId =
+            serde_json::from_value(res["result"]["group"]["id"].clone()).unwrap();
+        let handler = ServiceHandler::builder()
+            .register_service("slow".into(), Box::new(SlowJoinService(group_id)))
+            .build();
+        let server = Arc::new(Server::new(state.clone(), Arc::new(handler)).with_options(
+            ServerOptions {
+                request_timeout: Some(Duration::from_millis(10)),
+                ..Default::default()
+            },
+        ));
+        let (tx, mut rx) = mpsc::channel(DEFAULT_SEND_BUFFER_CAPACITY);
+        state
+            .add_client(
+                server.client_id(),
+                tx,
+                Codec::Json,
+                server.disconnect.clone(),
+            )
+            .await
+            .unwrap();
+
+        let res = call(&server, &mut rx, "slow_join", json!({})).await;
+        assert_eq!(res["error"]["code"], REQUEST_TIMEOUT_CODE);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // The join completed and was announced, only its response was dropped
+        assert!(state
+            .is_client_in_group(&group_id, &server.client_id())
+            .await
+            .is_ok());
+        let joined = received(&mut creator_rx);
+        assert_eq!(joined.len(), 1);
+        assert_eq!(joined[0]["method"], "group_client_joined");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn info_describes_the_server() {
+        let state = Arc::new(State::new());
+        let (client, mut rx) = connect(&state, ServerOptions::default()).await;
+
+        let res = call(&client, &mut rx, "system_info", json!({})).await;
+        let info = &res["result"];
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+        let methods = info["methods"].as_array().unwrap();
+        for method in ["group_create", "session_message", "system_info"] {
+            assert!(methods.contains(&json!(method)));
+        }
+        assert!(info["encodings"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("cborHex")));
+        assert_eq!(info["features"]["auth"], false);
+    }
+
+    #[tokio::test]
+    async fn connections_survive_undecodable_messages() {
+        let state = Arc::new(State::new());
+        let (client, mut rx) = connect(&state, ServerOptions::default()).await;
+
+        client
+            .handle_incoming_message(b"{\"jsonrpc\": \"2.0\", \"id\": 1,".to_vec())
+            .await
+            .unwrap();
+        let res: Value = serde_json::from_slice(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(res["error"]["code"], -32700);
+        let res = call(&client, &mut rx, "system_info", json!({})).await;
+        assert!(res.get("result").is_some());
+    }
+}
@@ -0,0 +1,65 @@
+//! Authentication
+//!
+//! This module contains the verifier of the bearer tokens clients present
+//! when connecting.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{fmt, sync::Arc};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Verifies bearer tokens against a shared secret.
+///
+/// Tokens are compared through an HMAC keyed on startup, so the comparison
+/// runs in constant time regardless of the length of the token.
+#[derive(Clone)]
+pub struct Auth {
+    /// Key used to sign the secret and the presented tokens.
+    key: Arc<[u8; 32]>,
+    /// Signature of the shared secret.
+    expected: Arc<[u8]>,
+}
+
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Auth").finish_non_exhaustive()
+    }
+}
+
+impl Auth {
+    /// Creates a new verifier accepting the given shared secret.
+    pub fn new(secret: &str) -> Self {
+        let key: [u8; 32] = rand::random();
+        let expected = mac(&key, secret).finalize().into_bytes();
+        Self {
+            key: Arc::new(key),
+            expected: Arc::from(expected.as_slice()),
+        }
+    }
+
+    /// Returns a boolean indicating if the token matches the shared secret.
+    pub fn verify(&self, token: &str) -> bool {
+        mac(&self.key, token).verify_slice(&self.expected).is_ok()
+    }
+}
+
+fn mac(key: &[u8; 32], token: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(token.as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_shared_secret_is_accepted() {
+        let auth = Auth::new("secret");
+        assert!(auth.verify("secret"));
+        assert!(!auth.verify("secret "));
+        assert!(!auth.verify(""));
+        assert!(!Auth::new("other").verify("secret"));
+    }
+}
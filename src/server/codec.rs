@@ -0,0 +1,161 @@
+//! Codec
+//!
+//! This module contains the wire encodings a client can negotiate through
+//! the websocket subprotocol.
+
+use axum::extract::ws;
+use serde::Serialize;
+use serde_json::Value;
+use strum::{Display, EnumString};
+
+/// Wire encoding of the json-rpc messages of a connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, EnumString)]
+pub enum Codec {
+    /// JSON sent in text frames, used unless another codec is negotiated.
+    #[default]
+    #[strum(serialize = "json")]
+    Json,
+    /// MessagePack sent in binary frames.
+    #[strum(serialize = "msgpack")]
+    MessagePack,
+}
+
+impl Codec {
+    /// Subprotocols that select a codec other than the default.
+    pub const SUBPROTOCOLS: [Codec; 1] = [Codec::MessagePack];
+
+    /// Returns the codec requested in a `Sec-WebSocket-Protocol` header,
+    /// defaulting to JSON if none of the requested subprotocols is supported.
+    pub fn negotiate(protocols: Option<&str>) -> Self {
+        protocols
+            .into_iter()
+            .flat_map(|protocols| protocols.split(','))
+            .filter_map(|protocol| protocol.trim().parse().ok())
+            .find(|codec| Self::SUBPROTOCOLS.contains(codec))
+            .unwrap_or_default()
+    }
+
+    /// Encodes a json-rpc message.
+    pub fn encode<T: Serialize>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Json => Ok(serde_json::to_vec(value)?),
+            Self::MessagePack => Ok(rmp_serde::to_vec_named(value)?),
+        }
+    }
+
+    /// Decodes a json-rpc request.
+    pub fn decode(&self, payload: &[u8]) -> anyhow::Result<json_rpc2::Request> {
+        match self {
+            Self::Json => Ok(json_rpc2::from_slice(payload)?),
+            Self::MessagePack => Ok(rmp_serde::from_slice(payload)?),
+        }
+    }
+
+    /// Builds the error response to a payload that couldn't be decoded as
+    /// a json-rpc request.
+    ///
+    /// Payloads that can't be parsed at all get a parse error with a null
+    /// id. The ones that can but don't form a valid request get an invalid
+    /// request error, echoing their id if it's a string or a number.
+    pub fn decode_error(&self, payload: &[u8], err: &anyhow::Error) -> json_rpc2::Response {
+        let data = match err.downcast_ref::<json_rpc2::Error>() {
+            Some(json_rpc2::Error::Parse { data } | json_rpc2::Error::InvalidRequest { data }) => {
+                data.clone()
+            }
+            _ => err.to_string(),
+        };
+        let value = match self {
+            Self::Json => serde_json::from_slice::<Value>(payload).ok(),
+            Self::MessagePack => rmp_serde::from_slice::<Value>(payload).ok(),
+        };
+        let Some(value) = value else {
+            return json_rpc2::Error::Parse { data }.into();
+        };
+        let id = value
+            .get("id")
+            .filter(|id| id.is_string() || id.is_number())
+            .cloned()
+            .unwrap_or_default();
+        let req = json_rpc2::Request::new(Some(id), String::new(), None);
+        (&req, json_rpc2::Error::InvalidRequest { data }).into()
+    }
+
+    /// Returns the payload of a websocket message, `None` for control frames.
+    ///
+    /// Both text and binary frames are decoded with the codec, so clients
+    /// defaulting to binary frames can still send UTF-8 JSON.
+    pub fn payload(msg: ws::Message) -> Option<Vec<u8>> {
+        match msg {
+            ws::Message::Text(txt) => Some(txt.into_bytes()),
+            ws::Message::Binary(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Wraps an encoded message in a websocket message.
+    pub fn message(&self, payload: Vec<u8>) -> ws::Message {
+        match self {
+            Self::Json => match String::from_utf8(payload) {
+                Ok(txt) => ws::Message::Text(txt),
+                Err(err) => ws::Message::Binary(err.into_bytes()),
+            },
+            Self::MessagePack => ws::Message::Binary(payload),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn msgpack_is_negotiated_when_requested() {
+        assert_eq!(Codec::negotiate(None), Codec::Json);
+        assert_eq!(Codec::negotiate(Some("foo, bar")), Codec::Json);
+        assert_eq!(Codec::negotiate(Some("foo, msgpack")), Codec::MessagePack);
+    }
+
+    #[test]
+    fn requests_roundtrip_through_every_codec() {
+        let request =
+            json!({"jsonrpc": "2.0", "id": 1, "method": "group_create", "params": {"n": 3}});
+        for codec in [Codec::Json, Codec::MessagePack] {
+            let payload = codec.encode(&request).unwrap();
+            let decoded = codec.decode(&payload).unwrap();
+            assert_eq!(decoded.method(), "group_create");
+            assert_eq!(serde_json::to_value(&decoded).unwrap(), request);
+        }
+    }
+
+    #[test]
+    fn data_frames_are_decoded_regardless_of_their_type() {
+        let request = r#"{"jsonrpc":"2.0","method":"group_create"}"#;
+        let binary = ws::Message::Binary(request.as_bytes().to_vec());
+        let payload = Codec::payload(binary).unwrap();
+        assert_eq!(
+            Codec::Json.decode(&payload).unwrap().method(),
+            "group_create"
+        );
+        let text = ws::Message::Text(request.into());
+        assert_eq!(Codec::payload(text), Some(payload));
+        assert_eq!(Codec::payload(ws::Message::Ping(vec![1])), None);
+    }
+
+    #[test]
+    fn undecodable_payloads_get_an_error_response() {
+        let codec = Codec::Json;
+        let unparseable = b"{\"jsonrpc\": \"2.0\", \"id\": 1,";
+        let err = codec.decode(unparseable).unwrap_err();
+        let res = codec.decode_error(unparseable, &err);
+        assert_eq!(res.error().as_ref().unwrap().code, -32700);
+        assert_eq!(serde_json::to_value(&res).unwrap()["id"], Value::Null);
+
+        // Requests without method echo their id
+        let invalid = br#"{"jsonrpc": "2.0", "id": 7, "params": {}}"#;
+        let err = codec.decode(invalid).unwrap_err();
+        let res = codec.decode_error(invalid, &err);
+        assert_eq!(res.error().as_ref().unwrap().code, -32600);
+        assert_eq!(res.id(), &Some(json!(7)));
+    }
+}
@@ -0,0 +1,79 @@
+//! Origin checking
+//!
+//! This module contains the list of origins browsers are allowed to open
+//! connections from.
+
+use std::{collections::HashSet, sync::Arc};
+
+/// Wildcard allowing connections from any origin.
+const ANY_ORIGIN: &str = "*";
+
+/// Origins allowed to open connections, checked against the `Origin`
+/// header browsers send with the websocket upgrade.
+///
+/// Origins are compared case-insensitively, ignoring a trailing slash.
+#[derive(Debug, Clone)]
+pub struct AllowedOrigins {
+    /// Normalized allowed origins, ignored if `any` is set.
+    origins: Arc<HashSet<String>>,
+    /// Whether any origin is allowed.
+    any: bool,
+}
+
+impl AllowedOrigins {
+    /// Creates a list from comma-separated origins, e.g.
+    /// `https://app.example.com,https://admin.example.com`.
+    ///
+    /// A `*` entry allows any origin.
+    pub fn new(origins: &str) -> Self {
+        let origins: HashSet<String> = origins
+            .split(',')
+            .map(normalize)
+            .filter(|origin| !origin.is_empty())
+            .collect();
+        Self {
+            any: origins.contains(ANY_ORIGIN),
+            origins: Arc::new(origins),
+        }
+    }
+
+    /// Returns a boolean indicating if a connection with the given `Origin`
+    /// header is allowed.
+    ///
+    /// Connections without the header don't come from a browser and are
+    /// always allowed, they are covered by authentication instead.
+    pub fn allows(&self, origin: Option<&str>) -> bool {
+        match origin {
+            None => true,
+            Some(_) if self.any => true,
+            Some(origin) => self.origins.contains(&normalize(origin)),
+        }
+    }
+}
+
+fn normalize(origin: &str) -> String {
+    origin.trim().trim_end_matches('/').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_listed_origins_are_allowed() {
+        let origins = AllowedOrigins::new("https://app.example.com, HTTPS://Admin.example.com/");
+
+        assert!(origins.allows(Some("https://app.example.com")));
+        assert!(origins.allows(Some("https://admin.example.com")));
+        assert!(origins.allows(Some("https://APP.example.com/")));
+        assert!(!origins.allows(Some("https://evil.example.com")));
+        // Non-browser clients send no origin
+        assert!(origins.allows(None));
+    }
+
+    #[test]
+    fn wildcards_allow_any_origin() {
+        assert!(AllowedOrigins::new("*").allows(Some("https://evil.example.com")));
+        assert!(!AllowedOrigins::new("").allows(Some("https://app.example.com")));
+    }
+}
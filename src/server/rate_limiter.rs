@@ -0,0 +1,53 @@
+//! Rate limiter
+//!
+//! This module contains a token bucket used to limit the rate of incoming
+//! requests of a connection.
+
+use std::time::Instant;
+
+/// Rate limit settings of a connection.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// Requests per second allowed on average.
+    pub per_second: u32,
+    /// Maximum number of requests allowed in a burst.
+    pub burst: u32,
+}
+
+/// Token bucket refilled continuously at the configured rate.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    /// Settings of the limiter.
+    limit: RateLimit,
+    /// Tokens currently available.
+    tokens: f64,
+    /// Last time tokens were refilled.
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a new limiter with a full bucket.
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes a token from the bucket, returning a boolean indicating
+    /// if the request is allowed.
+    pub(crate) fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.limit.per_second as f64).min(self.limit.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
@@ -0,0 +1,144 @@
+//! Reaper
+//!
+//! This module contains the background task that removes expired groups
+//! and sessions, notifying the members of the groups.
+
+use super::{notify, notify_group_closed, SendRetry};
+use crate::{
+    service::{
+        group_service::GroupClosedReason,
+        session_service::{SessionEvent, SessionExpiredNotification},
+    },
+    state::State,
+};
+use std::{sync::Arc, time::Duration};
+use tokio_util::sync::CancellationToken;
+
+/// Scans the state every `interval` until `shutdown` is cancelled.
+///
+/// Notifications are retried according to `send_retry` while the send
+/// buffer of a member is full.
+pub async fn run_reaper(
+    state: Arc<State>,
+    interval: Duration,
+    send_retry: SendRetry,
+    shutdown: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => reap(&state, send_retry).await,
+            _ = shutdown.cancelled() => break,
+        }
+    }
+}
+
+/// Removes expired groups and sessions and notifies the members of the groups.
+async fn reap(state: &State, send_retry: SendRetry) {
+    let expired = state.remove_expired_groups().await;
+    for (group, _) in &expired {
+        tracing::info!(group_id = group.id.to_string(), "Removing expired group");
+    }
+    notify_group_closed(state, send_retry, expired, GroupClosedReason::Expired).await;
+    for (group, session, client_ids) in state.remove_expired_sessions().await {
+        tracing::info!(
+            group_id = group.id.to_string(),
+            session_id = session.id.to_string(),
+            "Removing expired session"
+        );
+        let notification = SessionExpiredNotification { group, session };
+        if let Err(error) = notify(
+            state,
+            send_retry,
+            SessionEvent::SessionExpired.to_string(),
+            notification,
+            client_ids,
+        )
+        .await
+        {
+            tracing::error!(error = ?error, "Error while sending expiry notification");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        server::Codec,
+        state::{
+            group::Group,
+            parameters::Parameters,
+            session::{SessionKind, SessionOptions},
+            ClientId,
+        },
+    };
+    use serde_json::Value;
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn members_are_notified_of_expired_sessions() {
+        let state = State::new();
+        let mut group = Group::new(
+            Uuid::new_v4(),
+            Parameters::new(2, 1).unwrap(),
+            None,
+            None,
+            ClientId::new_v4(),
+        );
+        let options = SessionOptions {
+            ttl_secs: Some(0),
+            ..Default::default()
+        };
+        let session_id = group
+            .add_session(SessionKind::Keygen, None, options)
+            .unwrap()
+            .id;
+        let (tx, mut rx) = mpsc::channel(16);
+        let client_id = state.new_client_id();
+        state
+            .add_client(client_id, tx, Codec::Json, CancellationToken::new())
+            .await
+            .unwrap();
+        group.add_client(client_id).unwrap();
+        state.insert_group(group);
+
+        reap(&state, SendRetry::default()).await;
+        let notification: Value = serde_json::from_slice(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(notification["method"], "session_expired");
+        assert_eq!(
+            notification["params"]["session"]["id"],
+            session_id.to_string()
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn members_are_notified_of_expired_groups() {
+        let state = State::new();
+        let mut group = Group::new(
+            Uuid::new_v4(),
+            Parameters::new(2, 1).unwrap(),
+            None,
+            Some(0),
+            ClientId::new_v4(),
+        );
+        let group_id = group.id;
+        let (tx, mut rx) = mpsc::channel(16);
+        let client_id = state.new_client_id();
+        state
+            .add_client(client_id, tx, Codec::Json, CancellationToken::new())
+            .await
+            .unwrap();
+        group.add_client(client_id).unwrap();
+        state.insert_group(group);
+
+        reap(&state, SendRetry::default()).await;
+        let notification: Value = serde_json::from_slice(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(notification["method"], "group_closed");
+        assert_eq!(notification["params"]["reason"], "expired");
+        assert_eq!(notification["params"]["group"]["id"], group_id.to_string());
+        assert!(rx.try_recv().is_err());
+    }
+}
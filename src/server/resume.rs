@@ -0,0 +1,244 @@
+//! Resume tokens
+//!
+//! This module contains the signer of the tokens clients present to reclaim
+//! their client id after reconnecting.
+
+use crate::state::{ClientId, Memberships};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Default time a resume token is valid after being issued.
+pub const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Claims signed in a resume token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumeClaims {
+    /// Client the token was issued to.
+    pub client_id: ClientId,
+    /// Digest of the group and session memberships of the client when the
+    /// token was issued.
+    pub memberships: [u8; 32],
+    /// Unix time in seconds the token was issued at.
+    pub issued_at: u64,
+    /// Unix time in seconds the token expires at.
+    pub expires_at: u64,
+}
+
+impl ResumeClaims {
+    /// Returns a boolean indicating if the claims were issued for the
+    /// given memberships.
+    pub fn matches(&self, memberships: &Memberships) -> bool {
+        self.memberships == digest(memberships)
+    }
+}
+
+/// Issues and verifies resume tokens, signed with a key generated on startup.
+///
+/// A token carries the client id, a digest of its group and session
+/// memberships and its issue and expiry times, followed by an HMAC of
+/// them. A token only reclaims a reservation whose memberships match, so
+/// a new token is issued whenever they change. Tokens are invalidated on
+/// restart along with the state.
+#[derive(Clone)]
+pub struct Resume {
+    /// Key used to sign tokens.
+    key: Arc<[u8; 32]>,
+    /// Time a disconnected client's memberships are reserved.
+    window: Duration,
+    /// Time a token is valid after being issued.
+    token_lifetime: Duration,
+}
+
+impl fmt::Debug for Resume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resume")
+            .field("window", &self.window)
+            .field("token_lifetime", &self.token_lifetime)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Resume {
+    /// Creates a new signer with a random key, reserving memberships
+    /// of disconnected clients for `window`.
+    pub fn new(window: Duration) -> Self {
+        let key: [u8; 32] = rand::random();
+        Self {
+            key: Arc::new(key),
+            window,
+            token_lifetime: DEFAULT_TOKEN_LIFETIME,
+        }
+    }
+
+    /// Sets the time a token is valid after being issued, defaults to
+    /// [`DEFAULT_TOKEN_LIFETIME`].
+    pub fn with_token_lifetime(mut self, token_lifetime: Duration) -> Self {
+        self.token_lifetime = token_lifetime;
+        self
+    }
+
+    /// Returns the time a disconnected client's memberships are reserved.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Returns the claims of a token issued now to a client with the
+    /// given memberships.
+    pub fn claims(&self, client_id: ClientId, memberships: &Memberships) -> ResumeClaims {
+        let issued_at = now();
+        ResumeClaims {
+            client_id,
+            memberships: digest(memberships),
+            issued_at,
+            expires_at: issued_at.saturating_add(self.token_lifetime.as_secs()),
+        }
+    }
+
+    /// Returns a boolean indicating if a client holding a token with the
+    /// given claims must be issued a new one, because its memberships
+    /// changed or the token is past half its lifetime.
+    pub fn needs_refresh(&self, claims: &ResumeClaims, memberships: &Memberships) -> bool {
+        let refresh_at = claims
+            .issued_at
+            .saturating_add(self.token_lifetime.as_secs() / 2);
+        !claims.matches(memberships) || now() >= refresh_at
+    }
+
+    /// Issues the resume token carrying the given claims.
+    pub fn issue(&self, claims: &ResumeClaims) -> String {
+        let payload = format!(
+            "{}.{}.{}.{}",
+            claims.client_id.as_uuid().simple(),
+            claims.issued_at,
+            claims.expires_at,
+            encode_hex(&claims.memberships),
+        );
+        let signature = self.mac(&payload).finalize().into_bytes();
+        format!("{payload}.{}", encode_hex(&signature))
+    }
+
+    /// Verifies a resume token, returning its claims if it's not expired.
+    pub fn verify(&self, token: &str) -> Option<ResumeClaims> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        self.mac(payload)
+            .verify_slice(&decode_hex(signature)?)
+            .ok()?;
+        let mut fields = payload.split('.');
+        let client_id = ClientId::from_uuid(Uuid::parse_str(fields.next()?).ok()?);
+        let issued_at = fields.next()?.parse().ok()?;
+        let expires_at = fields.next()?.parse().ok()?;
+        let memberships = decode_hex(fields.next()?)?.try_into().ok()?;
+        if fields.next().is_some() || expires_at <= now() {
+            return None;
+        }
+        Some(ResumeClaims {
+            client_id,
+            memberships,
+            issued_at,
+            expires_at,
+        })
+    }
+
+    fn mac(&self, payload: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(self.key.as_ref()).expect("HMAC accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+/// Returns the current unix time in seconds.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Hashes memberships, every group is followed by its number of sessions
+/// so distinct memberships never produce the same input.
+fn digest(memberships: &Memberships) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for (group_id, session_ids) in memberships {
+        hasher.update(group_id.as_bytes());
+        hasher.update((session_ids.len() as u64).to_be_bytes());
+        for session_id in session_ids {
+            hasher.update(session_id.as_bytes());
+        }
+    }
+    hasher.finalize().into()
+}
+
+/// Encodes bytes as a lowercase hex string.
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Decodes a lowercase or uppercase hex string.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let chunks = hex.as_bytes().chunks_exact(2);
+    if !chunks.remainder().is_empty() {
+        return None;
+    }
+    chunks
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{group::GroupId, session::SessionId};
+    use std::collections::BTreeSet;
+
+    fn memberships() -> Memberships {
+        let session_ids = BTreeSet::from([SessionId::new_v4()]);
+        Memberships::from([(GroupId::new_v4(), session_ids)])
+    }
+
+    #[test]
+    fn tokens_carry_their_claims() {
+        let resume = Resume::new(Duration::from_secs(30));
+        let claims = resume.claims(ClientId::new_v4(), &memberships());
+        assert_eq!(resume.verify(&resume.issue(&claims)), Some(claims));
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_rejected() {
+        let resume = Resume::new(Duration::from_secs(30));
+        let token = resume.issue(&resume.claims(ClientId::new_v4(), &memberships()));
+        let (_, rest) = token.split_once('.').unwrap();
+        let forged = format!("{}.{rest}", Uuid::new_v4().simple());
+        assert_eq!(resume.verify(&forged), None);
+        assert_eq!(resume.verify(&token[..token.len() - 2]), None);
+        assert_eq!(Resume::new(Duration::from_secs(30)).verify(&token), None);
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let resume = Resume::new(Duration::from_secs(30)).with_token_lifetime(Duration::ZERO);
+        let token = resume.issue(&resume.claims(ClientId::new_v4(), &memberships()));
+        assert_eq!(resume.verify(&token), None);
+    }
+
+    #[test]
+    fn tokens_are_refreshed_once_memberships_change() {
+        let resume = Resume::new(Duration::from_secs(30));
+        let memberships = memberships();
+        let claims = resume.claims(ClientId::new_v4(), &memberships);
+        assert!(claims.matches(&memberships));
+        assert!(!resume.needs_refresh(&claims, &memberships));
+        assert!(resume.needs_refresh(&claims, &Memberships::new()));
+        let stale =
+            Resume::new(Duration::from_secs(30)).with_token_lifetime(Duration::from_secs(1));
+        assert!(stale.needs_refresh(&stale.claims(claims.client_id, &memberships), &memberships));
+    }
+}
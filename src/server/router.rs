@@ -0,0 +1,187 @@
+//! Router
+//!
+//! This module contains the HTTP routes of the server: the websocket
+//! endpoint, the health check and the metrics.
+
+use super::{Codec, Server, ServerOptions};
+use crate::{
+    service::{group_service::GroupJoinRequest, ServiceHandler},
+    state::{
+        group::{GroupId, JoinCode},
+        State,
+    },
+};
+use axum::{
+    extract::{Query, State as AxumState, WebSocketUpgrade},
+    http::{
+        header::{AUTHORIZATION, ORIGIN, SEC_WEBSOCKET_PROTOCOL},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Instant};
+use tokio_util::sync::CancellationToken;
+use tower_http::trace::TraceLayer;
+
+/// State shared by the routes.
+pub struct AppState {
+    pub state: Arc<State>,
+    pub service_handler: Arc<ServiceHandler>,
+    pub server_options: ServerOptions,
+    /// Cancelled when the server shuts down, closing every connection.
+    pub shutdown: CancellationToken,
+    pub metrics: PrometheusHandle,
+    pub started_at: Instant,
+}
+
+/// Query parameters of a websocket connection.
+///
+/// Tokens passed in the query string are a leaky fallback: the request
+/// spans of this server never record the query, but proxies and load
+/// balancers usually log full URLs. Clients able to set headers should
+/// send the bearer token in the `Authorization` header instead.
+#[derive(Deserialize)]
+struct ConnectParams {
+    /// Resume token issued on a previous connection.
+    resume: Option<String>,
+    /// Bearer token, for clients that can't set the `Authorization` header,
+    /// e.g. browsers.
+    token: Option<String>,
+    /// Group joined right after connecting, malformed ids are rejected
+    /// before upgrading.
+    group_id: Option<GroupId>,
+    /// Join code of the group joined right after connecting.
+    join_code: Option<JoinCode>,
+}
+
+/// Returns the router serving websocket connections on `/`, the health
+/// check on `/health` and the metrics on `/metrics`.
+pub fn router(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(ws_handler))
+        .route("/health", get(health_handler))
+        .route("/metrics", get(metrics_handler))
+        .with_state(app_state)
+        .layer(TraceLayer::new_for_http().make_span_with(make_span))
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    Query(params): Query<ConnectParams>,
+    headers: HeaderMap,
+    AxumState(app_state): AxumState<Arc<AppState>>,
+) -> Response {
+    if let Some(allowed_origins) = &app_state.server_options.allowed_origins {
+        // An origin that isn't valid text never matches an allowed one
+        let origin = headers
+            .get(ORIGIN)
+            .map(|origin| origin.to_str().unwrap_or_default());
+        if !allowed_origins.allows(origin) {
+            tracing::warn!(origin, "Rejecting connection from disallowed origin");
+            return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+        }
+    }
+    // The slot is held until the connection is closed
+    let Some(permit) = app_state.state.acquire_connection() else {
+        tracing::warn!("Rejecting connection, maximum number of connections reached");
+        return (StatusCode::SERVICE_UNAVAILABLE, "Too many connections").into_response();
+    };
+    if let Some(auth) = &app_state.server_options.auth {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .or(params.token.as_deref());
+        if !token.is_some_and(|token| auth.verify(token)) {
+            tracing::warn!("Rejecting connection with missing or invalid token");
+            return (StatusCode::UNAUTHORIZED, "Missing or invalid token").into_response();
+        }
+    }
+    // JSON is used unless the client requests another codec as subprotocol
+    let codec = Codec::negotiate(
+        headers
+            .get(SEC_WEBSOCKET_PROTOCOL)
+            .and_then(|protocols| protocols.to_str().ok()),
+    );
+    // Clients presenting a valid token reclaim their previous client id once upgraded
+    let resume_claims = match (params.resume, &app_state.server_options.resume) {
+        (None, _) => None,
+        (Some(token), Some(resume)) => match resume.verify(&token) {
+            Some(claims) => Some(claims),
+            None => {
+                return (StatusCode::UNAUTHORIZED, "Invalid or expired resume token")
+                    .into_response()
+            }
+        },
+        (Some(_), None) => {
+            return (StatusCode::BAD_REQUEST, "Resuming is disabled").into_response()
+        }
+    };
+    // Extensions such as permessage-deflate are not supported by the
+    // websocket implementation, `Sec-WebSocket-Extensions` is never echoed
+    // so clients advertising compression fall back to uncompressed frames.
+    // Clients on metered links can negotiate the msgpack codec instead.
+    ws.max_message_size(app_state.server_options.max_message_size)
+        .protocols(Codec::SUBPROTOCOLS.map(|codec| codec.to_string()))
+        .on_upgrade(move |socket| {
+            let state = app_state.state.clone();
+            let service_handler = app_state.service_handler.clone();
+            let mut server = Server::new(state, service_handler)
+                .with_options(app_state.server_options.clone())
+                .with_codec(codec)
+                .with_shutdown(app_state.shutdown.clone());
+            if let Some(claims) = resume_claims {
+                server = server.with_resume_claims(claims);
+            }
+            if params.group_id.is_some() || params.join_code.is_some() {
+                server = server.with_group_join(GroupJoinRequest {
+                    group_id: params.group_id,
+                    join_code: params.join_code,
+                });
+            }
+            async move {
+                server.handle_connection(socket).await;
+                drop(permit);
+            }
+        })
+}
+
+/// Builds the span of an HTTP request, recording its path but never its
+/// query string, which may carry bearer or resume tokens.
+fn make_span<B>(request: &axum::http::Request<B>) -> tracing::Span {
+    tracing::debug_span!(
+        "request",
+        method = %request.method(),
+        path = request.uri().path(),
+        version = ?request.version(),
+    )
+}
+
+/// Health check response.
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    version: &'static str,
+    #[serde(rename = "uptimeSecs")]
+    uptime_secs: u64,
+    clients: usize,
+}
+
+/// Liveness probe, never contends with message traffic.
+async fn health_handler(AxumState(app_state): AxumState<Arc<AppState>>) -> impl IntoResponse {
+    Json(HealthResponse {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_secs: app_state.started_at.elapsed().as_secs(),
+        clients: app_state.state.get_number_of_clients().await,
+    })
+}
+
+/// Renders the metrics in Prometheus text format.
+async fn metrics_handler(AxumState(app_state): AxumState<Arc<AppState>>) -> impl IntoResponse {
+    app_state.metrics.render()
+}
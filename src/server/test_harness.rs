@@ -0,0 +1,305 @@
+//! Test harness
+//!
+//! This module runs a [`Server`] connection in process, over a pair of
+//! in-memory channels instead of a TCP socket, so integration tests don't
+//! need to bind ports or speak websockets.
+//!
+//! ```
+//! use mpc_manager::{
+//!     server::{test_harness::TestClient, Server},
+//!     service::{
+//!         group_service::{GroupCreateRequest, GroupCreateResponse},
+//!         ServiceHandler,
+//!     },
+//!     state::{parameters::Parameters, State},
+//! };
+//! use std::sync::Arc;
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let state = Arc::new(State::new());
+//! let service_handler = Arc::new(ServiceHandler::new());
+//! let mut client = TestClient::connect(Server::new(state.clone(), service_handler)).await?;
+//!
+//! let res: GroupCreateResponse = client
+//!     .request(
+//!         "group_create",
+//!         GroupCreateRequest {
+//!             parameters: Parameters::new(3, 1)?,
+//!             kind: None,
+//!             ttl_secs: None,
+//!             join_code: false,
+//!         },
+//!     )
+//!     .await?;
+//! assert!(state.resolve_client_id(res.group.id, client.client_id()).await.is_ok());
+//! # anyhow::Ok(())
+//! # }).unwrap();
+//! ```
+
+use super::{Server, ServerEvent};
+use crate::state::PublicClientId;
+use anyhow::{anyhow, bail, Context, Result};
+use axum::extract::ws;
+use futures_util::{Sink, Stream};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context as TaskContext, Poll},
+    time::Duration,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Time to wait for a message from the server before failing.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Server end of an in-memory connection.
+struct MemorySocket {
+    /// Messages sent by the client.
+    rx: UnboundedReceiver<ws::Message>,
+    /// Messages sent to the client.
+    tx: UnboundedSender<ws::Message>,
+}
+
+impl Stream for MemorySocket {
+    type Item = Result<ws::Message, axum::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx).map(|msg| msg.map(Ok))
+    }
+}
+
+impl Sink<ws::Message> for MemorySocket {
+    type Error = axum::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, msg: ws::Message) -> Result<(), Self::Error> {
+        self.tx.send(msg).map_err(axum::Error::new)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Notification received from the server.
+#[derive(Debug, Clone)]
+pub struct TestNotification {
+    pub method: String,
+    pub params: Value,
+}
+
+/// Client end of an in-memory connection, speaking JSON.
+///
+/// Notifications received while waiting for a response are buffered
+/// until they are awaited.
+pub struct TestClient {
+    /// Public id assigned to the client by the server.
+    client_id: PublicClientId,
+    /// Messages sent to the server, dropping it closes the connection.
+    tx: UnboundedSender<ws::Message>,
+    /// Messages received from the server.
+    rx: UnboundedReceiver<ws::Message>,
+    /// Id of the next request.
+    next_id: u64,
+    /// Notifications not awaited yet, in arrival order.
+    notifications: VecDeque<TestNotification>,
+    /// Time to wait for a message from the server.
+    timeout: Duration,
+}
+
+impl TestClient {
+    /// Spawns the connection of a server and waits for the client to
+    /// be registered.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the client is not registered in time.
+    pub async fn connect(server: Server) -> Result<Self> {
+        let (client_tx, server_rx) = mpsc::unbounded_channel();
+        let (server_tx, client_rx) = mpsc::unbounded_channel();
+        tokio::spawn(server.handle_connection(MemorySocket {
+            rx: server_rx,
+            tx: server_tx,
+        }));
+        let mut client = Self {
+            client_id: PublicClientId::default(),
+            tx: client_tx,
+            rx: client_rx,
+            next_id: 1,
+            notifications: VecDeque::new(),
+            timeout: DEFAULT_TIMEOUT,
+        };
+        let registered = client
+            .notification(&ServerEvent::ClientRegistered.to_string())
+            .await?;
+        client.client_id = serde_json::from_value(registered["clientId"].clone())?;
+        Ok(client)
+    }
+
+    /// Sets the time to wait for a message from the server.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the public id assigned to the client by the server.
+    pub fn client_id(&self) -> PublicClientId {
+        self.client_id
+    }
+
+    /// Sends a request, waiting for its result.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the server responds with an error.
+    /// * Returns an error if the result can't be deserialized.
+    pub async fn request<P: Serialize, R: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: P,
+    ) -> Result<R> {
+        let res = self.request_raw(method, params).await?;
+        if let Some(error) = res.error() {
+            bail!(
+                "{method} failed with code {}: {}",
+                error.code,
+                error.message
+            );
+        }
+        Ok(serde_json::from_value(
+            res.result().clone().unwrap_or_default(),
+        )?)
+    }
+
+    /// Sends a request, waiting for its response whether it's a result or
+    /// an error.
+    pub async fn request_raw<P: Serialize>(
+        &mut self,
+        method: &str,
+        params: P,
+    ) -> Result<json_rpc2::Response> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(Some(id), method, params)?;
+        loop {
+            let msg = self.receive().await?;
+            if msg.get("method").is_some() {
+                self.notifications.push_back(notification(msg)?);
+            } else if msg["id"] == id {
+                return Ok(serde_json::from_value(msg)?);
+            }
+        }
+    }
+
+    /// Sends a notification, a request that expects no response.
+    pub fn notify<P: Serialize>(&mut self, method: &str, params: P) -> Result<()> {
+        self.send(None, method, params)
+    }
+
+    /// Sends a raw message, e.g. to check how malformed payloads are handled.
+    pub fn send_raw(&mut self, payload: &str) -> Result<()> {
+        self.tx
+            .send(ws::Message::Text(payload.into()))
+            .map_err(|_| anyhow!("connection closed"))
+    }
+
+    /// Waits for the next response, e.g. the one answering a raw message.
+    ///
+    /// Notifications received meanwhile are kept for later.
+    pub async fn response(&mut self) -> Result<json_rpc2::Response> {
+        loop {
+            let msg = self.receive().await?;
+            if msg.get("method").is_some() {
+                self.notifications.push_back(notification(msg)?);
+            } else {
+                return Ok(serde_json::from_value(msg)?);
+            }
+        }
+    }
+
+    /// Waits for a notification with the given method, returning its params.
+    ///
+    /// Other notifications received meanwhile are kept for later.
+    pub async fn notification(&mut self, method: &str) -> Result<Value> {
+        let position = self
+            .notifications
+            .iter()
+            .position(|notification| notification.method == method);
+        if let Some(notification) =
+            position.and_then(|position| self.notifications.remove(position))
+        {
+            return Ok(notification.params);
+        }
+        loop {
+            let msg = self.receive().await?;
+            if msg.get("method").is_none() {
+                continue;
+            }
+            let notification = notification(msg)?;
+            if notification.method == method {
+                return Ok(notification.params);
+            }
+            self.notifications.push_back(notification);
+        }
+    }
+
+    /// Returns the next notification, either buffered or received.
+    pub async fn next_notification(&mut self) -> Result<TestNotification> {
+        if let Some(notification) = self.notifications.pop_front() {
+            return Ok(notification);
+        }
+        loop {
+            let msg = self.receive().await?;
+            if msg.get("method").is_some() {
+                return notification(msg);
+            }
+        }
+    }
+
+    fn send<P: Serialize>(&mut self, id: Option<u64>, method: &str, params: P) -> Result<()> {
+        let req = json_rpc2::Request::new(
+            id.map(Value::from),
+            method.into(),
+            Some(serde_json::to_value(params)?),
+        );
+        self.send_raw(&serde_json::to_string(&req)?)
+    }
+
+    /// Receives the next data message from the server.
+    async fn receive(&mut self) -> Result<Value> {
+        // Control frames don't extend the wait
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            let msg = tokio::time::timeout_at(deadline, self.rx.recv())
+                .await
+                .context("timed out waiting for the server")?
+                .ok_or_else(|| anyhow!("connection closed"))?;
+            match msg {
+                ws::Message::Text(txt) => return Ok(serde_json::from_str(&txt)?),
+                ws::Message::Binary(bytes) => return Ok(serde_json::from_slice(&bytes)?),
+                ws::Message::Ping(payload) => {
+                    // Answered like websocket clients do
+                    let _ = self.tx.send(ws::Message::Pong(payload));
+                }
+                ws::Message::Close(_) => bail!("connection closed"),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn notification(mut msg: Value) -> Result<TestNotification> {
+    Ok(TestNotification {
+        method: serde_json::from_value(msg["method"].take())?,
+        params: msg["params"].take(),
+    })
+}
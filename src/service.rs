@@ -5,10 +5,14 @@
 
 #[cfg(feature = "server")]
 use self::{
-    group_service::GroupService, notification::Notification, session_service::SessionService,
+    group_service::GroupService,
+    notification::Notification,
+    relay_observer::{NoopRelayObserver, RelayObserver},
+    session_service::SessionService,
+    system_service::{ServerFeatures, SystemService},
 };
 #[cfg(feature = "server")]
-use crate::state::{ClientId, State};
+use crate::state::{ClientId, State, StateError};
 #[cfg(feature = "server")]
 use axum::async_trait;
 #[cfg(feature = "server")]
@@ -16,11 +20,26 @@ use std::{collections::HashMap, sync::Arc};
 
 pub mod group_service;
 pub mod notification;
+pub mod ping_service;
+#[cfg(feature = "server")]
+pub mod relay_observer;
 pub mod session_service;
+pub mod system_service;
 
 /// Separator for subroutes.
 pub const SUBROUTE_SEPARATOR: &str = "_";
 
+/// JSON-RPC error code for methods that don't exist.
+#[cfg(feature = "server")]
+const METHOD_NOT_FOUND: isize = -32601;
+
+/// JSON-RPC error code returned while the server memory budget is exhausted.
+pub const MEMORY_BUDGET_EXCEEDED_CODE: isize = -32002;
+
+/// JSON-RPC error code returned when a broadcast message exceeds the
+/// maximum broadcast size.
+pub const BROADCAST_SIZE_EXCEEDED_CODE: isize = -32004;
+
 #[cfg(feature = "server")]
 type ServiceResponse = Result<Option<json_rpc2::Response>, json_rpc2::Error>;
 
@@ -53,14 +72,14 @@ pub struct ServiceHandler {
 
 #[cfg(feature = "server")]
 impl ServiceHandler {
-    /// Create a new ServiceHandler.
+    /// Create a new ServiceHandler with the group, session and system services.
     pub fn new() -> Self {
-        let mut services: HashMap<String, Box<dyn Service>> = HashMap::new();
-        let group_service: Box<dyn Service> = Box::new(GroupService {});
-        let session_service: Box<dyn Service> = Box::new(SessionService {});
-        services.insert(group_service::ROUTE_PREFIX.into(), group_service);
-        services.insert(session_service::ROUTE_PREFIX.into(), session_service);
-        Self { services }
+        Self::builder().with_default_services().build()
+    }
+
+    /// Returns a builder without services, used to register custom ones.
+    pub fn builder() -> ServiceHandlerBuilder {
+        ServiceHandlerBuilder::default()
     }
 
     /// Infallible service handler, errors are automatically converted to responses.
@@ -74,6 +93,17 @@ impl ServiceHandler {
     ) -> Option<json_rpc2::Response> {
         match self.handle(request, ctx, client_id).await {
             Ok(response) => response,
+            Err(json_rpc2::Error::Boxed(e)) if e.is::<StateError>() => {
+                let e = e
+                    .downcast_ref::<StateError>()
+                    .expect("error is a state error");
+                let err = json_rpc2::RpcError {
+                    code: e.code(),
+                    message: e.to_string(),
+                    data: None,
+                };
+                Some((request, err).into())
+            }
             Err(e) => Some((request, e).into()),
         }
     }
@@ -92,7 +122,7 @@ impl ServiceHandler {
         let subroute: Vec<&str> = subroute.collect();
 
         if subroute.len() < 2 {
-            return Ok(Some(self.build_error_not_found_method(req)));
+            return Ok(Some(self.build_error_missing_prefix(req)));
         }
 
         let subroute = subroute[0];
@@ -103,6 +133,22 @@ impl ServiceHandler {
         Ok(Some(self.build_error_not_found_method(req)))
     }
 
+    /// Build a method not found error response explaining that methods
+    /// must be namespaced, listing the available prefixes.
+    fn build_error_missing_prefix(&self, req: &json_rpc2::Request) -> json_rpc2::Response {
+        let mut prefixes: Vec<&str> = self.services.keys().map(String::as_str).collect();
+        prefixes.sort_unstable();
+        let err = json_rpc2::RpcError {
+            code: METHOD_NOT_FOUND,
+            message: format!("Service method not found: {}", req.method()),
+            data: Some(format!(
+                "method must be namespaced as `prefix{SUBROUTE_SEPARATOR}action`, available prefixes: {}",
+                prefixes.join(", ")
+            )),
+        };
+        (req, err).into()
+    }
+
     /// Build a `json_rpc2::Error::MethodNotFound` error response.
     fn build_error_not_found_method(&self, req: &json_rpc2::Request) -> json_rpc2::Response {
         let err = json_rpc2::Error::MethodNotFound {
@@ -113,9 +159,211 @@ impl ServiceHandler {
     }
 }
 
+/// Builder of a [`ServiceHandler`], allowing custom services to be
+/// registered along with or instead of the default ones.
+#[cfg(feature = "server")]
+pub struct ServiceHandlerBuilder {
+    /// Services registered so far, mapped by prefix.
+    services: HashMap<String, Box<dyn Service>>,
+    /// Observer of the messages relayed by the session service.
+    relay_observer: Arc<dyn RelayObserver>,
+    /// Maximum size of a message broadcast by the session service.
+    max_broadcast_size: Option<usize>,
+    /// Optional features reported by the system service.
+    server_features: ServerFeatures,
+}
+
+#[cfg(feature = "server")]
+impl Default for ServiceHandlerBuilder {
+    fn default() -> Self {
+        Self {
+            services: HashMap::new(),
+            relay_observer: Arc::new(NoopRelayObserver),
+            max_broadcast_size: None,
+            server_features: ServerFeatures::default(),
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl ServiceHandlerBuilder {
+    /// Sets the observer notified of every message relayed in a session.
+    ///
+    /// Must be set before registering the default services.
+    pub fn with_relay_observer(mut self, relay_observer: Arc<dyn RelayObserver>) -> Self {
+        self.relay_observer = relay_observer;
+        self
+    }
+
+    /// Sets the maximum size in bytes of a message broadcast to a session,
+    /// unlimited if `None`.
+    ///
+    /// Must be set before registering the default services.
+    pub fn with_max_broadcast_size(mut self, max_broadcast_size: Option<usize>) -> Self {
+        self.max_broadcast_size = max_broadcast_size;
+        self
+    }
+
+    /// Sets the optional features reported to clients asking for the
+    /// server info.
+    ///
+    /// Must be set before registering the default services.
+    pub fn with_server_features(mut self, server_features: ServerFeatures) -> Self {
+        self.server_features = server_features;
+        self
+    }
+
+    /// Registers the group, session and system services.
+    pub fn with_default_services(self) -> Self {
+        let relay_observer = self.relay_observer.clone();
+        let max_broadcast_size = self.max_broadcast_size;
+        let server_features = self.server_features;
+        self.register_service(
+            group_service::ROUTE_PREFIX.into(),
+            Box::new(GroupService {}),
+        )
+        .register_service(
+            session_service::ROUTE_PREFIX.into(),
+            Box::new(
+                SessionService::new(relay_observer).with_max_broadcast_size(max_broadcast_size),
+            ),
+        )
+        .register_service(
+            system_service::ROUTE_PREFIX.into(),
+            Box::new(SystemService::new(server_features)),
+        )
+    }
+
+    /// Registers a service handling the methods namespaced with `prefix`,
+    /// replacing any service previously registered with the same prefix.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the prefix is empty or contains the subroute separator,
+    /// since requests would never be routed to the service.
+    pub fn register_service(mut self, prefix: String, service: Box<dyn Service>) -> Self {
+        assert!(
+            !prefix.is_empty() && !prefix.contains(SUBROUTE_SEPARATOR),
+            "service prefix must be non-empty and not contain `{SUBROUTE_SEPARATOR}`"
+        );
+        self.services.insert(prefix, service);
+        self
+    }
+
+    /// Builds the service handler.
+    pub fn build(self) -> ServiceHandler {
+        ServiceHandler {
+            services: self.services,
+        }
+    }
+}
+
+/// Converts an error returned by the state into a service error.
+///
+/// State errors are answered with their own error code, see
+/// [`StateError::code`], other errors as invalid params.
+#[cfg(feature = "server")]
+pub(crate) fn state_error(req: &json_rpc2::Request, err: anyhow::Error) -> json_rpc2::Error {
+    match err.downcast::<StateError>() {
+        Ok(err) => json_rpc2::Error::Boxed(Box::new(err)),
+        Err(err) => json_rpc2::Error::InvalidParams {
+            id: req.id().clone(),
+            data: err.to_string(),
+        },
+    }
+}
+
+/// Build an error response rejecting a request while the server memory
+/// budget is exhausted.
+#[cfg(feature = "server")]
+pub(crate) fn build_error_memory_budget_exceeded(req: &json_rpc2::Request) -> json_rpc2::Response {
+    let err = json_rpc2::RpcError {
+        code: MEMORY_BUDGET_EXCEEDED_CODE,
+        message: "Memory budget exceeded, try again later".into(),
+        data: None,
+    };
+    (req, err).into()
+}
+
+/// Build an error response rejecting a broadcast message larger than the
+/// maximum broadcast size.
+#[cfg(feature = "server")]
+pub(crate) fn build_error_broadcast_size_exceeded(
+    req: &json_rpc2::Request,
+    size: usize,
+    max_broadcast_size: usize,
+) -> json_rpc2::Response {
+    let err = json_rpc2::RpcError {
+        code: BROADCAST_SIZE_EXCEEDED_CODE,
+        message: format!(
+            "Broadcast message of {size} bytes exceeds the maximum of {max_broadcast_size} bytes, send it to explicit receivers instead"
+        ),
+        data: None,
+    };
+    (req, err).into()
+}
+
 #[cfg(feature = "server")]
 impl Default for ServiceHandler {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// Service replying to every method with its name.
+    struct EchoService;
+
+    #[async_trait]
+    impl Service for EchoService {
+        async fn handle(
+            &self,
+            request: &json_rpc2::Request,
+            _ctx: (Arc<State>, Arc<tokio::sync::Mutex<Vec<Notification>>>),
+            _client_id: ClientId,
+        ) -> Result<Option<json_rpc2::Response>, json_rpc2::Error> {
+            let res = Value::String(request.method().to_string());
+            Ok(Some((request, res).into()))
+        }
+    }
+
+    async fn serve(handler: &ServiceHandler, method: &str) -> Value {
+        let request: json_rpc2::Request =
+            serde_json::from_value(json!({"jsonrpc": "2.0", "id": 1, "method": method})).unwrap();
+        let ctx = (Arc::new(State::new()), Default::default());
+        let response = handler.serve(&request, ctx, ClientId::new_v4()).await;
+        serde_json::to_value(response.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn custom_services_are_routed_by_prefix() {
+        let handler = ServiceHandler::builder()
+            .with_default_services()
+            .register_service("echo".into(), Box::new(EchoService))
+            .build();
+
+        assert_eq!(serve(&handler, "echo_hello").await["result"], "echo_hello");
+        let response = serve(&handler, "system_goodbye").await;
+        assert_eq!(response["result"]["groups"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn builders_start_without_services() {
+        let handler = ServiceHandler::builder()
+            .register_service("echo".into(), Box::new(EchoService))
+            .build();
+
+        let response = serve(&handler, "group_create").await;
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    #[should_panic]
+    fn prefixes_with_the_separator_are_rejected() {
+        ServiceHandler::builder().register_service("my_echo".into(), Box::new(EchoService));
+    }
+}
@@ -4,16 +4,23 @@
 //! for group management.
 
 use crate::state::{
-    group::{Group, GroupId},
+    group::{Group, GroupId, GroupStats, JoinCode},
     parameters::Parameters,
+    session::{SessionId, SessionKind, SessionPartyNumber},
 };
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumString};
+use serde_json::Value;
+use strum::{Display, EnumIter, EnumString};
 
 #[cfg(feature = "server")]
-use super::{notification::Notification, Service, ServiceResponse};
+use super::{
+    notification::Notification,
+    session_service::{SessionEvent, SessionPromotedNotification},
+    state_error, Service, ServiceResponse,
+};
+use crate::state::PublicClientId;
 #[cfg(feature = "server")]
-use crate::state::{ClientId, State};
+use crate::state::{session::Session, ClientId, State};
 #[cfg(feature = "server")]
 use json_rpc2::{Error, Request};
 #[cfg(feature = "server")]
@@ -25,18 +32,62 @@ use tokio::sync::Mutex;
 pub const ROUTE_PREFIX: &str = "group";
 
 /// Available group methods.
-#[derive(Debug, Display, EnumString)]
+#[derive(Debug, Display, EnumIter, EnumString)]
 pub enum GroupMethod {
     #[strum(serialize = "group_create")]
     GroupCreate,
     #[strum(serialize = "group_join")]
     GroupJoin,
+    #[strum(serialize = "group_leave")]
+    GroupLeave,
+    #[strum(serialize = "group_stats")]
+    GroupStats,
+    #[strum(serialize = "group_info")]
+    GroupInfo,
+    #[strum(serialize = "group_broadcast")]
+    GroupBroadcast,
+    #[strum(serialize = "group_list_mine")]
+    GroupListMine,
+    #[strum(serialize = "group_kick")]
+    GroupKick,
+    #[strum(serialize = "group_close")]
+    GroupClose,
+}
+
+/// Available group events.
+#[derive(Debug, Display, EnumString)]
+pub enum GroupEvent {
+    /// A client joined the group.
+    #[strum(serialize = "group_client_joined")]
+    GroupClientJoined,
+    /// A client left the group.
+    #[strum(serialize = "group_client_left")]
+    GroupClientLeft,
+    /// A member broadcasted a message to the group.
+    #[strum(serialize = "group_message")]
+    GroupMessage,
+    /// A group was removed, sent to the connected clients still part of it.
+    #[strum(serialize = "group_closed")]
+    GroupClosed,
+    /// The creator of the group removed the client from it.
+    #[strum(serialize = "group_kicked")]
+    GroupKicked,
 }
 
 /// Group create request.
 #[derive(Deserialize, Serialize)]
 pub struct GroupCreateRequest {
     pub parameters: Parameters,
+    /// Session kind the group is restricted to, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SessionKind>,
+    /// Seconds after creation the group is removed if it never filled up,
+    /// groups never expire if unset.
+    #[serde(rename = "ttlSecs", default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    /// Whether a short join code is generated to share instead of the group id.
+    #[serde(rename = "joinCode", default)]
+    pub join_code: bool,
 }
 
 /// Group create response.
@@ -45,11 +96,14 @@ pub struct GroupCreateResponse {
     pub group: Group,
 }
 
-/// Group join request.
+/// Group join request, identifying the group either by its id or
+/// by its join code.
 #[derive(Deserialize, Serialize)]
 pub struct GroupJoinRequest {
-    #[serde(rename = "groupId")]
-    pub group_id: GroupId,
+    #[serde(rename = "groupId", default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<GroupId>,
+    #[serde(rename = "joinCode", default, skip_serializing_if = "Option::is_none")]
+    pub join_code: Option<JoinCode>,
 }
 
 /// Group join response.
@@ -58,6 +112,163 @@ pub struct GroupJoinResponse {
     pub group: Group,
 }
 
+/// Group client joined notification.
+#[derive(Deserialize, Serialize)]
+pub struct GroupClientJoinedNotification {
+    pub group: Group,
+    #[serde(rename = "clientCount")]
+    pub client_count: usize,
+}
+
+/// Group leave request.
+#[derive(Deserialize, Serialize)]
+pub struct GroupLeaveRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+}
+
+/// Group leave response.
+#[derive(Deserialize, Serialize)]
+pub struct GroupLeaveResponse {
+    pub group: Group,
+}
+
+/// Group client left notification.
+#[derive(Deserialize, Serialize)]
+pub struct GroupClientLeftNotification {
+    pub group: Group,
+    #[serde(rename = "clientCount")]
+    pub client_count: usize,
+}
+
+/// Client to kick from a group, identified either by its client id or
+/// by its party number in one of the group sessions.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum KickTarget {
+    Client {
+        #[serde(rename = "clientId")]
+        client_id: PublicClientId,
+    },
+    Party {
+        #[serde(rename = "sessionId")]
+        session_id: SessionId,
+        #[serde(rename = "partyNumber")]
+        party_number: SessionPartyNumber,
+    },
+}
+
+/// Group kick request, only allowed for the creator of the group.
+#[derive(Deserialize, Serialize)]
+pub struct GroupKickRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(flatten)]
+    pub target: KickTarget,
+}
+
+/// Group kick response.
+#[derive(Deserialize, Serialize)]
+pub struct GroupKickResponse {
+    pub group: Group,
+}
+
+/// Group kicked notification, sent to the kicked client only.
+#[derive(Deserialize, Serialize)]
+pub struct GroupKickedNotification {
+    pub group: Group,
+}
+
+/// Group close request, only allowed for the creator of the group.
+#[derive(Deserialize, Serialize)]
+pub struct GroupCloseRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+}
+
+/// Group close response.
+#[derive(Deserialize, Serialize)]
+pub struct GroupCloseResponse {
+    pub group: Group,
+}
+
+/// Reason a group was removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupClosedReason {
+    /// The group outlived its time to live without filling up.
+    Expired,
+    /// The last member of the group left or disconnected.
+    Empty,
+    /// The creator of the group closed it.
+    Closed,
+}
+
+/// Group closed notification.
+#[derive(Deserialize, Serialize)]
+pub struct GroupClosedNotification {
+    pub group: Group,
+    pub reason: GroupClosedReason,
+}
+
+/// Group stats request.
+#[derive(Deserialize, Serialize)]
+pub struct GroupStatsRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+}
+
+/// Group stats response.
+#[derive(Deserialize, Serialize)]
+pub struct GroupStatsResponse {
+    pub stats: GroupStats,
+}
+
+/// Group info request.
+#[derive(Deserialize, Serialize)]
+pub struct GroupInfoRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+}
+
+/// Group info response.
+///
+/// Only counts are returned, client ids are never exposed.
+#[derive(Deserialize, Serialize)]
+pub struct GroupInfoResponse {
+    pub parameters: Parameters,
+    #[serde(rename = "clientCount")]
+    pub client_count: usize,
+    #[serde(rename = "isFull")]
+    pub is_full: bool,
+}
+
+/// Group broadcast request, relaying a message to the other members
+/// without requiring a session.
+#[derive(Deserialize, Serialize)]
+pub struct GroupBroadcastRequest<T: Serialize = Value> {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    pub message: T,
+}
+
+/// Group message notification.
+///
+/// The sender is not identified, client ids are never exposed.
+#[derive(Deserialize, Serialize)]
+pub struct GroupMessageNotification<T: Serialize = Value> {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    pub message: T,
+}
+
+/// Group list mine response, listing the groups of the calling client only.
+#[derive(Deserialize, Serialize)]
+pub struct GroupListMineResponse {
+    #[serde(rename = "groupIds")]
+    pub group_ids: Vec<GroupId>,
+}
+
 /// Group service that handles incoming requests and maps
 /// them to the corresponding methods.
 #[cfg(feature = "server")]
@@ -83,6 +294,13 @@ impl Service for GroupService {
         let response = match method {
             GroupMethod::GroupCreate => self.group_create(req, ctx, client_id).await?,
             GroupMethod::GroupJoin => self.group_join(req, ctx, client_id).await?,
+            GroupMethod::GroupLeave => self.group_leave(req, ctx, client_id).await?,
+            GroupMethod::GroupStats => self.group_stats(req, ctx, client_id).await?,
+            GroupMethod::GroupInfo => self.group_info(req, ctx, client_id).await?,
+            GroupMethod::GroupBroadcast => self.group_broadcast(req, ctx, client_id).await?,
+            GroupMethod::GroupListMine => self.group_list_mine(req, ctx, client_id).await?,
+            GroupMethod::GroupKick => self.group_kick(req, ctx, client_id).await?,
+            GroupMethod::GroupClose => self.group_close(req, ctx, client_id).await?,
         };
         Ok(response)
     }
@@ -110,11 +328,20 @@ impl GroupService {
                 data: e.to_string(),
             })?;
 
-        let group = state.add_group(params.parameters).await;
+        let group = state
+            .add_group(
+                client_id,
+                params.parameters,
+                params.kind,
+                params.ttl_secs,
+                params.join_code,
+            )
+            .await
+            .map_err(|e| state_error(req, e))?;
         state
             .join_group(group.id, client_id)
             .await
-            .map_err(|e| Error::from(Box::from(e)))?;
+            .map_err(|e| state_error(req, e))?;
         tracing::info!(group_id = group.id().to_string(), "Group created");
         let res = serde_json::to_value(GroupCreateResponse { group })
             .map_err(|e| Error::from(Box::from(e)))?;
@@ -131,20 +358,342 @@ impl GroupService {
         client_id: ClientId,
     ) -> ServiceResponse {
         let params: GroupJoinRequest = req.deserialize()?;
+        let (state, notifications) = ctx;
+        let group_id = match (params.group_id, params.join_code) {
+            (Some(group_id), _) => group_id,
+            (None, Some(join_code)) => state
+                .get_group_id_from_join_code(&join_code)
+                .await
+                .map_err(|e| state_error(req, e))?,
+            (None, None) => {
+                return Err(Error::InvalidParams {
+                    id: req.id().clone(),
+                    data: "either groupId or joinCode is required".into(),
+                })
+            }
+        };
+        tracing::info!(group_id = group_id.to_string(), "Joining client to group");
+        let (group, client_count) = state
+            .join_group(group_id, client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+
+        let notification = serde_json::to_value(GroupClientJoinedNotification {
+            group: group.sanitized(),
+            client_count,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        notifications.lock().await.push(Notification::Group {
+            group_id,
+            filter: vec![client_id],
+            method: GroupEvent::GroupClientJoined.to_string(),
+            message: notification,
+        });
+
+        let res = serde_json::to_value(GroupJoinResponse { group })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+
+    async fn group_leave(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: GroupLeaveRequest = req.deserialize()?;
         tracing::info!(
             group_id = params.group_id.to_string(),
-            "Joining client to group"
+            "Removing client from group"
+        );
+        let (state, notifications) = ctx;
+        let (group, client_count, promotions, closed) = state
+            .leave_group(params.group_id, client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        push_client_left(
+            &notifications,
+            client_id,
+            &group,
+            client_count,
+            promotions,
+            closed,
+        )
+        .await?;
+
+        let res = serde_json::to_value(GroupLeaveResponse { group })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+
+    async fn group_stats(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: GroupStatsRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            "Querying group stats"
         );
         let (state, _) = ctx;
-        let group = state
-            .join_group(params.group_id, client_id)
+        state
+            .is_client_in_group(&params.group_id, &client_id)
             .await
-            .map_err(|e| Error::InvalidParams {
+            .map_err(|e| state_error(req, e))?;
+        let stats = state
+            .get_group_stats(params.group_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let res = serde_json::to_value(GroupStatsResponse { stats })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+
+    async fn group_info(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        _client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: GroupInfoRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            "Querying group info"
+        );
+        let (state, _) = ctx;
+        let (parameters, client_count, is_full) = state
+            .get_group_info(params.group_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let res = serde_json::to_value(GroupInfoResponse {
+            parameters,
+            client_count,
+            is_full,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+
+    async fn group_broadcast(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: GroupBroadcastRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            "Broadcasting message to group"
+        );
+        let (state, notifications) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+
+        let notification = serde_json::to_value(GroupMessageNotification {
+            group_id: params.group_id,
+            message: params.message,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        notifications.lock().await.push(Notification::Group {
+            group_id: params.group_id,
+            filter: vec![client_id],
+            method: GroupEvent::GroupMessage.to_string(),
+            message: notification,
+        });
+        Ok(None)
+    }
+
+    async fn group_list_mine(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        tracing::info!("Listing groups of client");
+        let (state, _) = ctx;
+        let group_ids = state.groups_for_client(client_id).await;
+        let res = serde_json::to_value(GroupListMineResponse { group_ids })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+
+    async fn group_kick(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: GroupKickRequest = req.deserialize()?;
+        let (state, notifications) = ctx;
+        let target = match params.target {
+            KickTarget::Client { client_id } => state
+                .resolve_client_id(params.group_id, client_id)
+                .await
+                .map_err(|e| state_error(req, e))?,
+            KickTarget::Party {
+                session_id,
+                party_number,
+            } => state
+                .get_client_id_from_party_number(params.group_id, session_id, party_number)
+                .await
+                .map_err(|e| state_error(req, e))?,
+        };
+        if target == client_id {
+            return Err(Error::InvalidParams {
                 id: req.id().clone(),
-                data: e.to_string(),
-            })?;
-        let res = serde_json::to_value(GroupJoinResponse { group })
+                data: "the group creator can't kick itself".into(),
+            });
+        }
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            "Kicking client from group"
+        );
+        let (group, client_count, promotions, closed) = state
+            .kick_from_group(params.group_id, client_id, target)
+            .await
+            .map_err(|e| state_error(req, e))?;
+
+        let notification = serde_json::to_value(GroupKickedNotification {
+            group: group.sanitized(),
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        notifications.lock().await.push(Notification::Relay {
+            method: GroupEvent::GroupKicked.to_string(),
+            messages: vec![(target, notification)],
+        });
+        push_client_left(
+            &notifications,
+            target,
+            &group,
+            client_count,
+            promotions,
+            closed,
+        )
+        .await?;
+
+        let res = serde_json::to_value(GroupKickResponse { group })
             .map_err(|e| Error::from(Box::from(e)))?;
         Ok(Some((req, res).into()))
     }
+
+    async fn group_close(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: GroupCloseRequest = req.deserialize()?;
+        let (state, notifications) = ctx;
+        let (group, client_ids) = state
+            .close_group(params.group_id, client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+
+        let notification = serde_json::to_value(GroupClosedNotification {
+            group: group.sanitized(),
+            reason: GroupClosedReason::Closed,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        let messages: Vec<_> = client_ids
+            .into_iter()
+            .filter(|id| *id != client_id)
+            .map(|id| (id, notification.clone()))
+            .collect();
+        if !messages.is_empty() {
+            notifications.lock().await.push(Notification::Relay {
+                method: GroupEvent::GroupClosed.to_string(),
+                messages,
+            });
+        }
+
+        let res = serde_json::to_value(GroupCloseResponse { group })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+}
+
+/// Pushes the notifications of a client leaving a group: `group_client_left`
+/// to the remaining members, `session_promoted` to the promoted standbys and
+/// `group_closed` to the session members left if the group was removed.
+#[cfg(feature = "server")]
+async fn push_client_left(
+    notifications: &Mutex<Vec<Notification>>,
+    client_id: ClientId,
+    group: &Group,
+    client_count: usize,
+    promotions: Vec<(Session, ClientId, SessionPartyNumber)>,
+    closed: Option<Vec<ClientId>>,
+) -> Result<(), Error> {
+    if client_count > 0 {
+        let notification = serde_json::to_value(GroupClientLeftNotification {
+            group: group.sanitized(),
+            client_count,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        notifications.lock().await.push(Notification::Group {
+            group_id: group.id,
+            filter: vec![client_id],
+            method: GroupEvent::GroupClientLeft.to_string(),
+            message: notification,
+        });
+    }
+
+    for (session, promoted, party_number) in promotions {
+        tracing::info!(
+            session_id = session.id.to_string(),
+            party_number,
+            "Promoting standby client to session party"
+        );
+        let notification = serde_json::to_value(SessionPromotedNotification {
+            group: group.sanitized(),
+            session,
+            party_number,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        notifications.lock().await.push(Notification::Relay {
+            method: SessionEvent::SessionPromoted.to_string(),
+            messages: vec![(promoted, notification)],
+        });
+    }
+
+    if let Some(client_ids) = closed.filter(|client_ids| !client_ids.is_empty()) {
+        let notification = serde_json::to_value(GroupClosedNotification {
+            group: group.sanitized(),
+            reason: GroupClosedReason::Empty,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        notifications.lock().await.push(Notification::Relay {
+            method: GroupEvent::GroupClosed.to_string(),
+            messages: client_ids
+                .into_iter()
+                .map(|client_id| (client_id, notification.clone()))
+                .collect(),
+        });
+    }
+    Ok(())
 }
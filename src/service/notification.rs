@@ -2,6 +2,9 @@
 //!
 //! This module contains the notification enum that is used to send
 //! notifications to multiple clients.
+//!
+//! The sender is never excluded implicitly, it must be listed in the
+//! `filter` of a notification to not receive it.
 
 use crate::state::{group::GroupId, session::SessionId, ClientId};
 use serde_json::Value;
@@ -0,0 +1,155 @@
+//! # Ping service
+//!
+//! This module contains an optional service used by clients to confirm the
+//! websocket and JSON-RPC plumbing work before attempting group operations.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use strum::{Display, EnumString};
+
+#[cfg(feature = "server")]
+use super::{notification::Notification, Service, ServiceResponse};
+#[cfg(feature = "server")]
+use crate::state::{ClientId, State};
+#[cfg(feature = "server")]
+use json_rpc2::{Error, Request};
+#[cfg(feature = "server")]
+use std::{
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+#[cfg(feature = "server")]
+use tokio::sync::Mutex;
+
+/// Prefix for ping routes.
+pub const ROUTE_PREFIX: &str = "ping";
+
+/// Available ping methods.
+#[derive(Debug, Display, EnumString)]
+pub enum PingMethod {
+    /// Echoes a nonce back to the client.
+    #[strum(serialize = "ping_ping")]
+    PingPing,
+}
+
+/// Available ping events.
+#[derive(Debug, Display, EnumString)]
+pub enum PingEvent {
+    /// Reply to a ping, relayed like any other notification.
+    #[strum(serialize = "ping_pong")]
+    PingPong,
+}
+
+/// Ping request.
+#[derive(Deserialize, Serialize)]
+pub struct PingRequest {
+    /// Value chosen by the client, echoed back unchanged.
+    #[serde(default)]
+    pub nonce: Value,
+}
+
+/// Ping response, also sent as a `ping_pong` notification.
+#[derive(Deserialize, Serialize)]
+pub struct PingPongResponse {
+    pub nonce: Value,
+    /// Server time in milliseconds since the unix epoch.
+    #[serde(rename = "serverTime")]
+    pub server_time: u64,
+}
+
+/// Ping service that handles incoming requests and maps
+/// them to the corresponding methods.
+///
+/// It's not registered by default, see
+/// [`ServiceHandlerBuilder`](super::ServiceHandlerBuilder).
+#[cfg(feature = "server")]
+pub struct PingService;
+
+#[axum::async_trait]
+#[cfg(feature = "server")]
+impl Service for PingService {
+    async fn handle(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let method =
+            PingMethod::from_str(req.method()).map_err(|_| json_rpc2::Error::MethodNotFound {
+                name: req.method().to_string(),
+                id: req.id().clone(),
+            })?;
+        let response = match method {
+            PingMethod::PingPing => self.ping_ping(req, ctx, client_id).await?,
+        };
+        Ok(response)
+    }
+}
+
+#[cfg(feature = "server")]
+impl PingService {
+    /// Replies with the nonce and the server time both as a response and as
+    /// a `ping_pong` notification, exercising the notification path too.
+    async fn ping_ping(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: PingRequest = req.deserialize()?;
+        let server_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or_default();
+        let res = serde_json::to_value(PingPongResponse {
+            nonce: params.nonce,
+            server_time,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+
+        let (_, notifications) = ctx;
+        notifications.lock().await.push(Notification::Relay {
+            method: PingEvent::PingPong.to_string(),
+            messages: vec![(client_id, res.clone())],
+        });
+        Ok(Some((req, res).into()))
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn pings_echo_the_nonce_to_the_sender() {
+        let req: Request = serde_json::from_value(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "ping_ping",
+            "params": {"nonce": [1, "a"]},
+        }))
+        .unwrap();
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+        let ctx = (Arc::new(State::new()), notifications.clone());
+        let client_id = ClientId::new_v4();
+
+        let response = PingService.handle(&req, ctx, client_id).await.unwrap();
+        let response = serde_json::to_value(response.unwrap()).unwrap();
+        assert_eq!(response["result"]["nonce"], json!([1, "a"]));
+        assert!(response["result"]["serverTime"].as_u64().unwrap() > 0);
+        let notifications = notifications.lock().await;
+        let [Notification::Relay { method, messages }] = notifications.as_slice() else {
+            panic!("expected a single relay, got {notifications:?}");
+        };
+        assert_eq!(method, "ping_pong");
+        assert_eq!(messages, &[(client_id, response["result"].clone())]);
+    }
+}
@@ -0,0 +1,33 @@
+//! Relay observer
+//!
+//! This module contains the hook invoked whenever a session message is
+//! relayed, used to audit the traffic without storing it.
+
+use crate::state::{
+    group::GroupId,
+    session::{SessionId, SessionPartyNumber},
+};
+
+/// Observer notified of every message relayed in a session.
+///
+/// Only metadata is passed, never the payload, preserving the guarantee
+/// that messages are not stored.
+pub trait RelayObserver: Send + Sync {
+    /// Called once a message was queued for delivery, `receiver` is `None`
+    /// for messages broadcasted to the whole session.
+    fn on_relay(
+        &self,
+        _group_id: GroupId,
+        _session_id: SessionId,
+        _sender: SessionPartyNumber,
+        _receiver: Option<SessionPartyNumber>,
+        _byte_len: usize,
+    ) {
+    }
+}
+
+/// Observer that ignores every relay, used by default.
+#[derive(Debug, Default)]
+pub struct NoopRelayObserver;
+
+impl RelayObserver for NoopRelayObserver {}
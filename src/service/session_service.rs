@@ -5,19 +5,29 @@
 
 use crate::state::{
     group::{Group, GroupId},
-    session::{Session, SessionId, SessionKind, SessionPartyNumber},
+    session::{
+        MessageEncoding, MessageId, MessageSeq, Session, SessionHistoryEvent, SessionId,
+        SessionKind, SessionOptions, SessionPartyNumber, SessionProgress,
+    },
+    PublicClientId,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use strum::{Display, EnumString};
+use strum::{Display, EnumIter, EnumString};
 
 #[cfg(feature = "server")]
-use super::{notification::Notification, Service, ServiceResponse};
+use super::{
+    build_error_broadcast_size_exceeded, build_error_memory_budget_exceeded,
+    notification::Notification, relay_observer::RelayObserver, state_error, Service,
+    ServiceResponse,
+};
 #[cfg(feature = "server")]
-use crate::state::{ClientId, State};
+use crate::state::{session::SessionSignup, ClientId, State};
 #[cfg(feature = "server")]
 use json_rpc2::{Error, Request};
 #[cfg(feature = "server")]
+use std::collections::HashSet;
+#[cfg(feature = "server")]
 use std::str::FromStr;
 #[cfg(feature = "server")]
 use std::sync::Arc;
@@ -28,7 +38,7 @@ use tokio::sync::Mutex;
 pub const ROUTE_PREFIX: &str = "session";
 
 /// Available session methods.
-#[derive(Debug, Display, EnumString)]
+#[derive(Debug, Display, EnumIter, EnumString)]
 pub enum SessionMethod {
     /// Create a new session.
     #[strum(serialize = "session_create")]
@@ -42,6 +52,33 @@ pub enum SessionMethod {
     /// Send a message to a session.
     #[strum(serialize = "session_message")]
     SessionMessage,
+    /// Acknowledge the delivery of a message.
+    #[strum(serialize = "session_message_ack")]
+    SessionMessageAck,
+    /// Close a session.
+    #[strum(serialize = "session_close")]
+    SessionClose,
+    /// List the sessions of a group.
+    #[strum(serialize = "session_list")]
+    SessionList,
+    /// Query the status of a session.
+    #[strum(serialize = "session_status")]
+    SessionStatus,
+    /// Re-send the ready notification of a session to the requesting client.
+    #[strum(serialize = "session_ready_replay")]
+    SessionReadyReplay,
+    /// Take over a party number vacated by a client that disconnected.
+    #[strum(serialize = "session_claim_vacant")]
+    SessionClaimVacant,
+    /// Replace the value of a session that isn't ready yet.
+    #[strum(serialize = "session_set_value")]
+    SessionSetValue,
+    /// Tell the other parties to abort the session after a protocol fault.
+    #[strum(serialize = "session_abort")]
+    SessionAbort,
+    /// Fetch the lifecycle events of a session missed while disconnected.
+    #[strum(serialize = "session_events_since")]
+    SessionEventsSince,
 }
 
 /// Available session events.
@@ -56,6 +93,27 @@ pub enum SessionEvent {
     /// A session received a message.
     #[strum(serialize = "session_message")]
     SessionMessage,
+    /// A receiver acknowledged the delivery of a message.
+    #[strum(serialize = "session_message_ack")]
+    SessionMessageAck,
+    /// A message could not be relayed to a receiver that is not connected.
+    #[strum(serialize = "session_message_undeliverable")]
+    SessionMessageUndeliverable,
+    /// A standby client was promoted to a party of the session.
+    #[strum(serialize = "session_promoted")]
+    SessionPromoted,
+    /// The value of a session was replaced.
+    #[strum(serialize = "session_value_updated")]
+    SessionValueUpdated,
+    /// A session was closed.
+    #[strum(serialize = "session_closed")]
+    SessionClosed,
+    /// A party aborted a session.
+    #[strum(serialize = "session_aborted")]
+    SessionAborted,
+    /// A session outlived its time to live without reaching the threshold.
+    #[strum(serialize = "session_expired")]
+    SessionExpired,
 }
 
 /// Session create request.
@@ -66,12 +124,17 @@ pub struct SessionCreateRequest {
     pub kind: SessionKind,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub value: Option<Value>,
+    #[serde(flatten)]
+    pub options: SessionOptions,
+    /// Group members notified of the session instead of the whole group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invitees: Option<Vec<PublicClientId>>,
 }
 
 /// Session create response.
-#[derive(Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct SessionCreateResponse {
-    session: Session,
+    pub session: Session,
 }
 
 /// Session created notification.
@@ -91,11 +154,34 @@ pub struct SessionSignupRequest {
 }
 
 /// Session signup response.
-#[derive(Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct SessionSignupResponse {
-    session: Session,
+    pub session: Session,
     #[serde(rename = "partyNumber")]
-    party_number: SessionPartyNumber,
+    pub party_number: SessionPartyNumber,
+    /// Party numbers occupied after signing up, in ascending order and
+    /// including the client's own.
+    #[serde(default)]
+    pub parties: Vec<SessionPartyNumber>,
+}
+
+/// Session standby response, acknowledging the client was queued
+/// because the session is full.
+#[derive(Deserialize, Serialize)]
+pub struct SessionStandbyResponse {
+    pub session: Session,
+    /// Position in the standby queue, starting at 1.
+    pub standby: usize,
+}
+
+/// Session promoted notification, sent to a standby client
+/// that was assigned a freed party number.
+#[derive(Deserialize, Serialize)]
+pub struct SessionPromotedNotification {
+    pub group: Group,
+    pub session: Session,
+    #[serde(rename = "partyNumber")]
+    pub party_number: SessionPartyNumber,
 }
 
 /// Session login request.
@@ -110,16 +196,62 @@ pub struct SessionLoginRequest {
 }
 
 /// Session login response.
-#[derive(Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct SessionLoginResponse {
-    session: Session,
+    pub session: Session,
+}
+
+/// Session claim vacant request.
+#[derive(Deserialize, Serialize)]
+pub struct SessionClaimVacantRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+    #[serde(rename = "partyNumber")]
+    pub party_number: SessionPartyNumber,
+}
+
+/// Session claim vacant response.
+#[derive(Deserialize, Serialize)]
+pub struct SessionClaimVacantResponse {
+    pub session: Session,
+}
+
+/// Session set value request.
+#[derive(Deserialize, Serialize)]
+pub struct SessionSetValueRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+    pub value: Option<Value>,
+}
+
+/// Session set value response.
+#[derive(Deserialize, Serialize)]
+pub struct SessionSetValueResponse {
+    pub session: Session,
+}
+
+/// Session value updated notification.
+#[derive(Deserialize, Serialize)]
+pub struct SessionValueUpdatedNotification {
+    pub group: Group,
+    pub session: Session,
 }
 
 /// Session ready notification.
 #[derive(Deserialize, Serialize)]
 pub struct SessionReadyNotification {
-    group: Group,
-    session: Session,
+    pub group: Group,
+    pub session: Session,
+    /// Hex encoded random seed shared by all the parties, if requested
+    /// at the moment of creation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<String>,
+    /// Party numbers occupied when the session became ready.
+    pub parties: Vec<SessionPartyNumber>,
 }
 
 /// Session message request.
@@ -129,8 +261,82 @@ pub struct SessionMessageRequest<T: Serialize = Value> {
     pub group_id: GroupId,
     #[serde(rename = "sessionId")]
     pub session_id: SessionId,
-    pub receiver: Option<SessionPartyNumber>,
+    /// Party numbers to relay the message to, broadcasting to the whole
+    /// session if unset. Accepts a single party number or an array.
+    #[serde(default, deserialize_with = "deserialize_receivers")]
+    pub receiver: Option<Vec<SessionPartyNumber>>,
+    /// Party numbers left out of a broadcast, only allowed if `receiver`
+    /// is unset.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<SessionPartyNumber>,
     pub message: T,
+    /// Encoding of the message.
+    #[serde(default, skip_serializing_if = "MessageEncoding::is_json")]
+    pub encoding: MessageEncoding,
+    /// Allows relaying a message to the sender itself.
+    #[serde(default)]
+    pub loopback: bool,
+    /// Requests delivery acknowledgements from the receivers. Only the latest
+    /// [`MAX_PENDING_ACKS_PER_SENDER`] messages of a sender are tracked.
+    ///
+    /// [`MAX_PENDING_ACKS_PER_SENDER`]: crate::state::session::MAX_PENDING_ACKS_PER_SENDER
+    #[serde(default)]
+    pub ack: bool,
+}
+
+/// Deserializes the receivers of a message, accepting a bare party
+/// number as a one-element list for backward compatibility.
+fn deserialize_receivers<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<SessionPartyNumber>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(SessionPartyNumber),
+        Many(Vec<SessionPartyNumber>),
+    }
+
+    Ok(
+        Option::<OneOrMany>::deserialize(deserializer)?.map(|receiver| match receiver {
+            OneOrMany::One(party_number) => vec![party_number],
+            OneOrMany::Many(party_numbers) => party_numbers,
+        }),
+    )
+}
+
+/// Validates that an opaque payload matches its declared encoding.
+///
+/// CBOR payloads must be strings in the declared text encoding, the blob is
+/// passed through untouched and never decoded nor parsed as CBOR.
+pub fn validate_encoding(payload: &Value, encoding: MessageEncoding) -> Result<(), String> {
+    let text = match (encoding, payload) {
+        (MessageEncoding::Json, _) => return Ok(()),
+        (_, Value::String(text)) if !text.is_empty() => text.as_bytes(),
+        _ => return Err(format!("{encoding:?} payload must be a non-empty string")),
+    };
+    let valid = match encoding {
+        MessageEncoding::Json => true,
+        MessageEncoding::CborBase64 => {
+            let data = text
+                .strip_suffix(b"==")
+                .or_else(|| text.strip_suffix(b"="))
+                .unwrap_or(text);
+            text.len() % 4 == 0
+                && data
+                    .iter()
+                    .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'+' || *byte == b'/')
+        }
+        MessageEncoding::CborHex => {
+            text.len() % 2 == 0 && text.iter().all(|byte| byte.is_ascii_hexdigit())
+        }
+    };
+    if !valid {
+        return Err(format!("payload is not valid {encoding:?}"));
+    }
+    Ok(())
 }
 
 /// Session message notification.
@@ -142,13 +348,211 @@ pub struct SessionMessageNotification<T: Serialize = Value> {
     pub session_id: SessionId,
     pub sender: SessionPartyNumber,
     pub message: T,
+    /// Sequence number assigned by the server, increasing by one with
+    /// every message relayed in the session, so receivers can detect
+    /// reordered or replayed messages. It's only reset when the session
+    /// is created again.
+    #[serde(default)]
+    pub seq: MessageSeq,
+    /// Encoding of the message.
+    #[serde(default, skip_serializing_if = "MessageEncoding::is_json")]
+    pub encoding: MessageEncoding,
+    /// Id to acknowledge the message with, present only if the sender
+    /// requested delivery acknowledgements.
+    #[serde(rename = "messageId", default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<MessageId>,
+}
+
+/// Session message response, only sent when acknowledgements are requested.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SessionMessageResponse {
+    #[serde(rename = "messageId")]
+    pub message_id: MessageId,
+}
+
+/// Session message ack request, sent by a receiver to confirm delivery.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SessionMessageAckRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+    #[serde(rename = "messageId")]
+    pub message_id: MessageId,
+}
+
+/// Session message ack notification, forwarded to the sender of a message.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SessionMessageAckNotification {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+    #[serde(rename = "messageId")]
+    pub message_id: MessageId,
+    /// Party number of the receiver that acknowledged the message.
+    pub receiver: SessionPartyNumber,
+}
+
+/// Session message undeliverable notification, sent back to the sender of
+/// a message when a receiver is not connected.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SessionMessageUndeliverableNotification {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+    /// Party number of the receiver the message was intended for.
+    #[serde(rename = "partyNumber")]
+    pub party_number: SessionPartyNumber,
+}
+
+/// Session close request.
+#[derive(Deserialize, Serialize)]
+pub struct SessionCloseRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+}
+
+/// Session close response.
+#[derive(Serialize)]
+pub struct SessionCloseResponse {
+    session: Session,
+}
+
+/// Session closed notification.
+#[derive(Deserialize, Serialize)]
+pub struct SessionClosedNotification {
+    group: Group,
+    session: Session,
+}
+
+/// Session abort request, sent by a party that detected a protocol fault.
+#[derive(Deserialize, Serialize)]
+pub struct SessionAbortRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+    /// Reason of the abort, relayed as is to the other parties.
+    pub reason: String,
+    /// Whether the session is closed as well, otherwise it's kept so
+    /// parties can retry.
+    #[serde(default)]
+    pub close: bool,
+}
+
+/// Session abort response.
+#[derive(Deserialize, Serialize)]
+pub struct SessionAbortResponse {
+    pub session: Session,
+}
+
+/// Session aborted notification.
+#[derive(Deserialize, Serialize)]
+pub struct SessionAbortedNotification {
+    pub group: Group,
+    pub session: Session,
+    /// Party number of the client that aborted the session.
+    #[serde(rename = "partyNumber")]
+    pub party_number: SessionPartyNumber,
+    pub reason: String,
+    /// Whether the session was closed along with the abort.
+    pub closed: bool,
+}
+
+/// Session expired notification.
+#[derive(Deserialize, Serialize)]
+pub struct SessionExpiredNotification {
+    pub group: Group,
+    pub session: Session,
+}
+
+/// Session list request.
+#[derive(Deserialize, Serialize)]
+pub struct SessionListRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+}
+
+/// Session list response.
+#[derive(Serialize)]
+pub struct SessionListResponse {
+    sessions: Vec<Session>,
+}
+
+/// Session status request.
+#[derive(Deserialize, Serialize)]
+pub struct SessionStatusRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+}
+
+/// Session status response.
+#[derive(Deserialize, Serialize)]
+pub struct SessionStatusResponse {
+    pub session: Session,
+    /// Party numbers that haven't signed up or logged in yet.
+    pub missing: Vec<SessionPartyNumber>,
+    /// Party count, required threshold and readiness of the session.
+    #[serde(flatten)]
+    pub progress: SessionProgress,
+}
+
+/// Session ready replay request.
+#[derive(Deserialize, Serialize)]
+pub struct SessionReadyReplayRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+}
+
+/// Session ready replay response.
+///
+/// If the session is ready, a `session_ready` notification is
+/// sent to the requesting client only.
+#[derive(Deserialize, Serialize)]
+pub struct SessionReadyReplayResponse {
+    pub ready: bool,
+}
+
+/// Session events since request.
+#[derive(Deserialize, Serialize)]
+pub struct SessionEventsSinceRequest {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+    /// Sequence number of the last event seen, every recorded event is
+    /// returned if unset.
+    #[serde(default)]
+    pub since: u64,
+}
+
+/// Session events since response.
+#[derive(Deserialize, Serialize)]
+pub struct SessionEventsSinceResponse {
+    pub session: Session,
+    /// Lifecycle events after `since`, in the order they happened.
+    pub events: Vec<SessionHistoryEvent>,
+    /// Whether older events after `since` were already discarded.
+    pub truncated: bool,
 }
 
 /// Session service that handles incoming requests and maps
 /// them to the corresponding methods.
-#[derive(Debug)]
 #[cfg(feature = "server")]
-pub struct SessionService;
+pub struct SessionService {
+    /// Observer notified of every relayed message.
+    relay_observer: Arc<dyn RelayObserver>,
+    /// Maximum size in bytes of a broadcast message, unlimited if `None`.
+    max_broadcast_size: Option<usize>,
+}
 
 #[axum::async_trait]
 #[cfg(feature = "server")]
@@ -170,6 +574,23 @@ impl Service for SessionService {
             SessionMethod::SessionSignup => self.session_signup(req, ctx, client_id).await?,
             SessionMethod::SessionLogin => self.session_login(req, ctx, client_id).await?,
             SessionMethod::SessionMessage => self.session_message(req, ctx, client_id).await?,
+            SessionMethod::SessionMessageAck => {
+                self.session_message_ack(req, ctx, client_id).await?
+            }
+            SessionMethod::SessionClose => self.session_close(req, ctx, client_id).await?,
+            SessionMethod::SessionAbort => self.session_abort(req, ctx, client_id).await?,
+            SessionMethod::SessionEventsSince => {
+                self.session_events_since(req, ctx, client_id).await?
+            }
+            SessionMethod::SessionList => self.session_list(req, ctx, client_id).await?,
+            SessionMethod::SessionStatus => self.session_status(req, ctx, client_id).await?,
+            SessionMethod::SessionReadyReplay => {
+                self.session_ready_replay(req, ctx, client_id).await?
+            }
+            SessionMethod::SessionClaimVacant => {
+                self.session_claim_vacant(req, ctx, client_id).await?
+            }
+            SessionMethod::SessionSetValue => self.session_set_value(req, ctx, client_id).await?,
         };
         Ok(response)
     }
@@ -177,6 +598,23 @@ impl Service for SessionService {
 
 #[cfg(feature = "server")]
 impl SessionService {
+    /// Creates a new session service notifying relayed messages to the observer.
+    pub fn new(relay_observer: Arc<dyn RelayObserver>) -> Self {
+        Self {
+            relay_observer,
+            max_broadcast_size: None,
+        }
+    }
+
+    /// Sets the maximum size in bytes of a message broadcast to a session.
+    ///
+    /// Broadcasts are sent to every party, so they are usually limited
+    /// more strictly than messages relayed to explicit receivers.
+    pub fn with_max_broadcast_size(mut self, max_broadcast_size: Option<usize>) -> Self {
+        self.max_broadcast_size = max_broadcast_size;
+        self
+    }
+
     async fn session_create(
         &self,
         req: &Request,
@@ -189,27 +627,67 @@ impl SessionService {
             "Creating a new session"
         );
         let (state, notifications) = ctx;
-        let (group, session) = state
-            .add_session(params.group_id, params.kind, params.value)
+        state
+            .is_client_in_group(&params.group_id, &client_id)
             .await
-            .map_err(|e| Error::InvalidParams {
-                id: req.id().clone(),
-                data: e.to_string(),
+            .map_err(|e| state_error(req, e))?;
+        if state.memory().is_exhausted() {
+            tracing::warn!("Memory budget exceeded, rejecting session creation");
+            return Ok(Some(build_error_memory_budget_exceeded(req)));
+        }
+        if let Some(value) = &params.value {
+            validate_encoding(value, params.options.value_encoding).map_err(|data| {
+                Error::InvalidParams {
+                    id: req.id().clone(),
+                    data,
+                }
             })?;
+        }
+        let invitees = match params.invitees {
+            Some(public_ids) => {
+                let mut invitees = Vec::with_capacity(public_ids.len());
+                for public_id in public_ids {
+                    let invitee = state
+                        .resolve_client_id(params.group_id, public_id)
+                        .await
+                        .map_err(|e| state_error(req, e))?;
+                    invitees.push(invitee);
+                }
+                Some(invitees)
+            }
+            None => None,
+        };
+        let (group, session) = state
+            .add_session(params.group_id, params.kind, params.value, params.options)
+            .await
+            .map_err(|e| state_error(req, e))?;
 
         let res = serde_json::to_value(SessionCreateResponse {
-            session: session.clone(),
+            session: session.sanitized(),
         })
         .map_err(|e| Error::from(Box::from(e)))?;
         let notification = serde_json::to_value(SessionCreatedNotification { group, session })
             .map_err(|e| Error::from(Box::from(e)))?;
 
-        notifications.lock().await.push(Notification::Group {
-            group_id: params.group_id,
-            filter: vec![client_id],
-            method: SessionEvent::SessionCreated.to_string(),
-            message: notification.clone(),
-        });
+        let notification = match invitees {
+            Some(invitees) => Notification::Relay {
+                method: SessionEvent::SessionCreated.to_string(),
+                messages: invitees
+                    .into_iter()
+                    .filter(|invitee| *invitee != client_id)
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .map(|invitee| (invitee, notification.clone()))
+                    .collect(),
+            },
+            None => Notification::Group {
+                group_id: params.group_id,
+                filter: vec![client_id],
+                method: SessionEvent::SessionCreated.to_string(),
+                message: notification,
+            },
+        };
+        notifications.lock().await.push(notification);
         Ok(Some((req, res).into()))
     }
 
@@ -226,24 +704,44 @@ impl SessionService {
             "Signing up client to a session"
         );
         let (state, notifications) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
 
-        let (group, session, party_number, threshold) = state
+        let (group, session, signup, threshold) = state
             .signup_session(client_id, params.group_id, params.session_id)
             .await
-            .map_err(|e| Error::InvalidParams {
-                id: req.id().clone(),
-                data: e.to_string(),
-            })?;
+            .map_err(|e| state_error(req, e))?;
 
+        let party_number = match signup {
+            SessionSignup::Party(party_number) => party_number,
+            SessionSignup::Standby(standby) => {
+                tracing::info!(standby, "Session is full, client queued as standby");
+                let res = serde_json::to_value(SessionStandbyResponse { session, standby })
+                    .map_err(|e| Error::from(Box::from(e)))?;
+                return Ok(Some((req, res).into()));
+            }
+        };
+        let mut parties = session.occupied_party_numbers.clone();
+        parties.sort_unstable();
         let res = serde_json::to_value(SessionSignupResponse {
-            session: session.clone(),
+            session: session.sanitized(),
             party_number,
+            parties,
         })
         .map_err(|e| Error::from(Box::from(e)))?;
 
         if threshold {
-            let notification = serde_json::to_value(SessionReadyNotification { group, session })
-                .map_err(|e| Error::from(Box::from(e)))?;
+            let seed = session.seed.clone();
+            let parties = session.occupied_party_numbers.clone();
+            let notification = serde_json::to_value(SessionReadyNotification {
+                group,
+                session,
+                seed,
+                parties,
+            })
+            .map_err(|e| Error::from(Box::from(e)))?;
             notifications.lock().await.push(Notification::Group {
                 group_id: params.group_id,
                 filter: vec![],
@@ -266,6 +764,10 @@ impl SessionService {
             "Loggin in client to a session"
         );
         let (state, notifications) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
         let (group, session, threshold) = state
             .login_session(
                 client_id,
@@ -274,17 +776,21 @@ impl SessionService {
                 params.party_number,
             )
             .await
-            .map_err(|e| Error::InvalidParams {
-                id: req.id().clone(),
-                data: e.to_string(),
-            })?;
+            .map_err(|e| state_error(req, e))?;
         let res = serde_json::to_value(SessionLoginResponse {
-            session: session.clone(),
+            session: session.sanitized(),
         })
         .map_err(|e| Error::from(Box::from(e)))?;
         if threshold {
-            let notification = serde_json::to_value(SessionReadyNotification { group, session })
-                .map_err(|e| Error::from(Box::from(e)))?;
+            let seed = session.seed.clone();
+            let parties = session.occupied_party_numbers.clone();
+            let notification = serde_json::to_value(SessionReadyNotification {
+                group,
+                session,
+                seed,
+                parties,
+            })
+            .map_err(|e| Error::from(Box::from(e)))?;
             notifications.lock().await.push(Notification::Group {
                 group_id: params.group_id,
                 filter: vec![],
@@ -294,6 +800,108 @@ impl SessionService {
         }
         Ok(Some((req, res).into()))
     }
+    async fn session_claim_vacant(
+        &self,
+        req: &Request,
+        ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: SessionClaimVacantRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            session_id = params.session_id.to_string(),
+            party_number = params.party_number,
+            "Claiming vacant party number of a session"
+        );
+        let (state, notifications) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let (group, session, threshold) = state
+            .claim_vacant_party(
+                client_id,
+                params.group_id,
+                params.session_id,
+                params.party_number,
+            )
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let res = serde_json::to_value(SessionClaimVacantResponse {
+            session: session.sanitized(),
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        if threshold {
+            let seed = session.seed.clone();
+            let parties = session.occupied_party_numbers.clone();
+            let notification = serde_json::to_value(SessionReadyNotification {
+                group,
+                session,
+                seed,
+                parties,
+            })
+            .map_err(|e| Error::from(Box::from(e)))?;
+            notifications.lock().await.push(Notification::Group {
+                group_id: params.group_id,
+                filter: vec![],
+                method: SessionEvent::SessionReady.to_string(),
+                message: notification,
+            });
+        }
+        Ok(Some((req, res).into()))
+    }
+
+    async fn session_set_value(
+        &self,
+        req: &Request,
+        ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: SessionSetValueRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            session_id = params.session_id.to_string(),
+            "Setting session value"
+        );
+        let (state, notifications) = ctx;
+        if state.memory().is_exhausted() {
+            tracing::warn!("Memory budget exceeded, rejecting session value");
+            return Ok(Some(build_error_memory_budget_exceeded(req)));
+        }
+        // The encoding is fixed at creation, so it can be checked up front
+        let (_, session, _) = state
+            .get_session_readiness(client_id, params.group_id, params.session_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        if let Some(value) = &params.value {
+            validate_encoding(value, session.value_encoding).map_err(|data| {
+                Error::InvalidParams {
+                    id: req.id().clone(),
+                    data,
+                }
+            })?;
+        }
+        let (group, session, client_ids) = state
+            .set_session_value(client_id, params.group_id, params.session_id, params.value)
+            .await
+            .map_err(|e| state_error(req, e))?;
+
+        let res = serde_json::to_value(SessionSetValueResponse {
+            session: session.clone(),
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        let notification = serde_json::to_value(SessionValueUpdatedNotification { group, session })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        notifications.lock().await.push(Notification::Relay {
+            method: SessionEvent::SessionValueUpdated.to_string(),
+            messages: client_ids
+                .into_iter()
+                .map(|client_id| (client_id, notification.clone()))
+                .collect(),
+        });
+        Ok(Some((req, res).into()))
+    }
+
     async fn session_message(
         &self,
         req: &Request,
@@ -307,58 +915,492 @@ impl SessionService {
             "Sending message to session"
         );
         let (state, notifications) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        if state.memory().is_exhausted() {
+            tracing::warn!("Memory budget exceeded, rejecting session message");
+            return Ok(Some(build_error_memory_budget_exceeded(req)));
+        }
 
         let self_party_number = state
             .get_party_number_from_client_id(params.group_id, params.session_id, client_id)
             .await
-            .map_err(|e| Error::InvalidParams {
-                id: req.id().clone(),
-                data: e.to_string(),
-            })?;
+            .map_err(|e| state_error(req, e))?;
         state
             .validate_group_and_session(params.group_id, params.session_id)
             .await
-            .map_err(|e| Error::InvalidParams {
+            .map_err(|e| state_error(req, e))?;
+
+        validate_encoding(&params.message, params.encoding).map_err(|data| {
+            Error::InvalidParams {
                 id: req.id().clone(),
-                data: e.to_string(),
-            })?;
+                data,
+            }
+        })?;
+
+        let byte_len = params.message.to_string().len();
+        if let Some(max_broadcast_size) = self.max_broadcast_size {
+            if params.receiver.is_none() && byte_len > max_broadcast_size {
+                tracing::warn!(
+                    size = byte_len,
+                    max_broadcast_size,
+                    "Broadcast message too large, rejecting session message"
+                );
+                return Ok(Some(build_error_broadcast_size_exceeded(
+                    req,
+                    byte_len,
+                    max_broadcast_size,
+                )));
+            }
+        }
+
+        // Resolve excluded parties of a broadcast, the sender is always excluded
+        let mut filter = vec![client_id];
+        if !params.exclude.is_empty() {
+            if params.receiver.is_some() {
+                return Err(Error::InvalidParams {
+                    id: req.id().clone(),
+                    data: "exclude is only allowed when broadcasting without receiver".into(),
+                });
+            }
+            for &party_number in &params.exclude {
+                let excluded_client_id = state
+                    .get_client_id_from_party_number(
+                        params.group_id,
+                        params.session_id,
+                        party_number,
+                    )
+                    .await
+                    .map_err(|e| state_error(req, e))?;
+                filter.push(excluded_client_id);
+            }
+        }
+
+        // Resolve receivers, `None` means broadcast to the session
+        let mut undeliverable = Vec::new();
+        let receivers = match params.receiver {
+            Some(mut party_numbers) => {
+                if party_numbers.is_empty() {
+                    return Err(Error::InvalidParams {
+                        id: req.id().clone(),
+                        data: "receiver must contain at least one party number".into(),
+                    });
+                }
+                // Relay once per party even if it was listed more than once
+                let mut seen = HashSet::new();
+                party_numbers.retain(|party_number| seen.insert(*party_number));
+
+                let mut client_ids = Vec::with_capacity(party_numbers.len());
+                for &party_number in &party_numbers {
+                    if party_number == self_party_number && !params.loopback {
+                        return Err(Error::InvalidParams {
+                            id: req.id().clone(),
+                            data: format!(
+                                "receiver `{party_number}` is the sender, set `loopback` to relay a message to self"
+                            ),
+                        });
+                    }
+                    let receiver_client_id = state
+                        .get_client_id_from_party_number(
+                            params.group_id,
+                            params.session_id,
+                            party_number,
+                        )
+                        .await
+                        .map_err(|e| state_error(req, e))?;
+                    client_ids.push(receiver_client_id);
+                }
+
+                // Receivers without a connection are reported back to the
+                // sender instead of being relayed to
+                let mut connected = (Vec::new(), Vec::new());
+                for (party_number, receiver_client_id) in party_numbers.into_iter().zip(client_ids)
+                {
+                    if state.get_client(&receiver_client_id).await.is_some() {
+                        connected.0.push(party_number);
+                        connected.1.push(receiver_client_id);
+                    } else {
+                        undeliverable.push(party_number);
+                    }
+                }
+                Some(connected)
+            }
+            None => None,
+        };
 
+        let message_id = if params.ack {
+            let ack_receivers = match &receivers {
+                Some((_, client_ids)) => client_ids.clone(),
+                None => state
+                    .get_client_ids_from_session(&params.group_id, &params.session_id)
+                    .await
+                    .map_err(|e| state_error(req, e))?
+                    .into_iter()
+                    .filter(|id| !filter.contains(id))
+                    .collect(),
+            };
+            let message_id = state
+                .add_pending_ack(params.group_id, params.session_id, client_id, ack_receivers)
+                .await
+                .map_err(|e| state_error(req, e))?;
+            Some(message_id)
+        } else {
+            None
+        };
+
+        let seq = state
+            .next_message_seq(params.group_id, params.session_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
         let res = serde_json::to_value(SessionMessageNotification {
             group_id: params.group_id,
             session_id: params.session_id,
             message: params.message,
+            seq,
+            encoding: params.encoding,
             sender: self_party_number,
+            message_id,
         })
         .map_err(|e| Error::from(Box::from(e)))?;
 
         let mut notifications = notifications.lock().await;
-        match params.receiver {
-            Some(party_number) => {
-                let receiver_client_id = state
-                    .get_client_id_from_party_number(
+        if !undeliverable.is_empty() {
+            let mut messages = Vec::with_capacity(undeliverable.len());
+            for party_number in undeliverable {
+                let res = serde_json::to_value(SessionMessageUndeliverableNotification {
+                    group_id: params.group_id,
+                    session_id: params.session_id,
+                    party_number,
+                })
+                .map_err(|e| Error::from(Box::from(e)))?;
+                messages.push((client_id, res));
+            }
+            notifications.push(Notification::Relay {
+                method: SessionEvent::SessionMessageUndeliverable.to_string(),
+                messages,
+            });
+        }
+        match receivers {
+            Some((party_numbers, client_ids)) if !client_ids.is_empty() => {
+                notifications.push(Notification::Relay {
+                    method: SessionEvent::SessionMessage.to_string(),
+                    messages: client_ids
+                        .into_iter()
+                        .map(|client_id| (client_id, res.clone()))
+                        .collect(),
+                });
+                for party_number in party_numbers {
+                    self.relay_observer.on_relay(
                         params.group_id,
                         params.session_id,
-                        party_number,
-                    )
-                    .await
-                    .map_err(|e| Error::InvalidParams {
-                        id: req.id().clone(),
-                        data: e.to_string(),
-                    })?;
-                notifications.push(Notification::Relay {
+                        self_party_number,
+                        Some(party_number),
+                        byte_len,
+                    );
+                }
+            }
+            Some(_) => {}
+            None => {
+                notifications.push(Notification::Session {
                     method: SessionEvent::SessionMessage.to_string(),
-                    messages: vec![(receiver_client_id, res)],
-                })
+                    group_id: params.group_id,
+                    session_id: params.session_id,
+                    filter,
+                    message: res,
+                });
+                self.relay_observer.on_relay(
+                    params.group_id,
+                    params.session_id,
+                    self_party_number,
+                    None,
+                    byte_len,
+                );
             }
-            None => notifications.push(Notification::Session {
-                method: SessionEvent::SessionMessage.to_string(),
-                group_id: params.group_id,
-                session_id: params.session_id,
-                filter: vec![],
-                message: res,
-            }),
         };
 
+        match message_id {
+            Some(message_id) => {
+                let res = serde_json::to_value(SessionMessageResponse { message_id })
+                    .map_err(|e| Error::from(Box::from(e)))?;
+                Ok(Some((req, res).into()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn session_message_ack(
+        &self,
+        req: &Request,
+        ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: SessionMessageAckRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            session_id = params.session_id.to_string(),
+            message_id = params.message_id,
+            "Acknowledging session message"
+        );
+        let (state, notifications) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let self_party_number = state
+            .get_party_number_from_client_id(params.group_id, params.session_id, client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let sender_client_id = state
+            .acknowledge_message(
+                params.group_id,
+                params.session_id,
+                params.message_id,
+                client_id,
+            )
+            .await
+            .map_err(|e| state_error(req, e))?;
+
+        let notification = serde_json::to_value(SessionMessageAckNotification {
+            group_id: params.group_id,
+            session_id: params.session_id,
+            message_id: params.message_id,
+            receiver: self_party_number,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        notifications.lock().await.push(Notification::Relay {
+            method: SessionEvent::SessionMessageAck.to_string(),
+            messages: vec![(sender_client_id, notification)],
+        });
         Ok(None)
     }
+
+    async fn session_close(
+        &self,
+        req: &Request,
+        ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: SessionCloseRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            session_id = params.session_id.to_string(),
+            "Closing session"
+        );
+        let (state, notifications) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let (group, session, client_ids) = state
+            .close_session(client_id, params.group_id, params.session_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+
+        let res = serde_json::to_value(SessionCloseResponse {
+            session: session.sanitized(),
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        let notification = serde_json::to_value(SessionClosedNotification { group, session })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        notifications.lock().await.push(Notification::Relay {
+            method: SessionEvent::SessionClosed.to_string(),
+            messages: client_ids
+                .into_iter()
+                .map(|client_id| (client_id, notification.clone()))
+                .collect(),
+        });
+        Ok(Some((req, res).into()))
+    }
+
+    async fn session_abort(
+        &self,
+        req: &Request,
+        ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: SessionAbortRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            session_id = params.session_id.to_string(),
+            close = params.close,
+            "Aborting session"
+        );
+        let (state, notifications) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let (group, session, party_number, client_ids) = state
+            .abort_session(client_id, params.group_id, params.session_id, params.close)
+            .await
+            .map_err(|e| state_error(req, e))?;
+
+        let res = serde_json::to_value(SessionAbortResponse {
+            session: session.sanitized(),
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        let notification = serde_json::to_value(SessionAbortedNotification {
+            group,
+            session,
+            party_number,
+            reason: params.reason,
+            closed: params.close,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        notifications.lock().await.push(Notification::Relay {
+            method: SessionEvent::SessionAborted.to_string(),
+            messages: client_ids
+                .into_iter()
+                .map(|client_id| (client_id, notification.clone()))
+                .collect(),
+        });
+        Ok(Some((req, res).into()))
+    }
+
+    async fn session_list(
+        &self,
+        req: &Request,
+        ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: SessionListRequest = req.deserialize()?;
+        tracing::info!(group_id = params.group_id.to_string(), "Listing sessions");
+        let (state, _) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let sessions = state
+            .list_sessions(params.group_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let res = serde_json::to_value(SessionListResponse { sessions })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+
+    async fn session_status(
+        &self,
+        req: &Request,
+        ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: SessionStatusRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            session_id = params.session_id.to_string(),
+            "Querying session status"
+        );
+        let (state, _) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let (session, missing, progress) = state
+            .get_session_status(params.group_id, params.session_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let res = serde_json::to_value(SessionStatusResponse {
+            session,
+            missing,
+            progress,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+
+    async fn session_ready_replay(
+        &self,
+        req: &Request,
+        ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: SessionReadyReplayRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            session_id = params.session_id.to_string(),
+            "Replaying session ready notification"
+        );
+        let (state, notifications) = ctx;
+        state
+            .is_client_in_group(&params.group_id, &client_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let (group, session, ready) = state
+            .get_session_readiness(client_id, params.group_id, params.session_id)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        if ready {
+            let seed = session.seed.clone();
+            let parties = session.occupied_party_numbers.clone();
+            let notification = serde_json::to_value(SessionReadyNotification {
+                group,
+                session,
+                seed,
+                parties,
+            })
+            .map_err(|e| Error::from(Box::from(e)))?;
+            notifications.lock().await.push(Notification::Relay {
+                method: SessionEvent::SessionReady.to_string(),
+                messages: vec![(client_id, notification)],
+            });
+        }
+        let res = serde_json::to_value(SessionReadyReplayResponse { ready })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+
+    async fn session_events_since(
+        &self,
+        req: &Request,
+        ctx: (Arc<State>, Arc<Mutex<Vec<Notification>>>),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: SessionEventsSinceRequest = req.deserialize()?;
+        tracing::info!(
+            group_id = params.group_id.to_string(),
+            session_id = params.session_id.to_string(),
+            since = params.since,
+            "Fetching session events"
+        );
+        let (state, _) = ctx;
+        let (session, events, truncated) = state
+            .get_session_events_since(client_id, params.group_id, params.session_id, params.since)
+            .await
+            .map_err(|e| state_error(req, e))?;
+        let res = serde_json::to_value(SessionEventsSinceResponse {
+            session,
+            events,
+            truncated,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn payloads_must_match_their_encoding() {
+        let base64 = MessageEncoding::CborBase64;
+        assert!(validate_encoding(&json!("oWFhGCo="), base64).is_ok());
+        assert!(validate_encoding(&json!("oWFh"), base64).is_ok());
+        assert!(validate_encoding(&json!("oWFhGCo"), base64).is_err());
+        assert!(validate_encoding(&json!("oW-hGCo="), base64).is_err());
+
+        let hex = MessageEncoding::CborHex;
+        assert!(validate_encoding(&json!("a16161182a"), hex).is_ok());
+        assert!(validate_encoding(&json!("a161611"), hex).is_err());
+        assert!(validate_encoding(&json!("zz"), hex).is_err());
+
+        // CBOR payloads must be non-empty strings, JSON is never checked
+        assert!(validate_encoding(&json!(""), hex).is_err());
+        assert!(validate_encoding(&json!({"a": 1}), base64).is_err());
+        assert!(validate_encoding(&json!({"a": 1}), MessageEncoding::Json).is_ok());
+    }
 }
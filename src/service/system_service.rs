@@ -0,0 +1,253 @@
+//! # System service
+//!
+//! This module contains the system service that handles incoming requests
+//! related to the connection itself rather than groups or sessions.
+
+use crate::state::{
+    group::GroupId,
+    session::{MessageEncoding, SessionId, SessionKind, SessionPartyNumber},
+};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, EnumString};
+
+#[cfg(feature = "server")]
+use super::{group_service, notification::Notification, session_service, Service, ServiceResponse};
+#[cfg(feature = "server")]
+use crate::state::{ClientId, State};
+#[cfg(feature = "server")]
+use json_rpc2::{Error, Request};
+#[cfg(feature = "server")]
+use std::str::FromStr;
+#[cfg(feature = "server")]
+use strum::IntoEnumIterator;
+#[cfg(feature = "server")]
+use tokio::sync::Mutex;
+
+/// Prefix for system routes.
+pub const ROUTE_PREFIX: &str = "system";
+
+/// Available system methods.
+#[derive(Debug, Display, EnumIter, EnumString)]
+pub enum SystemMethod {
+    /// Requests a summary of the client memberships, after which the
+    /// server closes the connection.
+    #[strum(serialize = "system_goodbye")]
+    SystemGoodbye,
+    /// Sets a label attached to the connection logs.
+    #[strum(serialize = "system_set_label")]
+    SystemSetLabel,
+    /// Describes the server version and the capabilities it supports.
+    #[strum(serialize = "system_info")]
+    SystemInfo,
+}
+
+/// Version of the server, taken from the crate version.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Maximum number of characters of a connection label.
+pub const MAX_LABEL_LENGTH: usize = 64;
+
+/// Session membership of a client.
+#[derive(Deserialize, Serialize)]
+pub struct SessionMembership {
+    #[serde(rename = "sessionId")]
+    pub session_id: SessionId,
+    #[serde(rename = "partyNumber")]
+    pub party_number: SessionPartyNumber,
+}
+
+/// Group membership of a client.
+#[derive(Deserialize, Serialize)]
+pub struct GroupMembership {
+    #[serde(rename = "groupId")]
+    pub group_id: GroupId,
+    pub sessions: Vec<SessionMembership>,
+}
+
+/// System goodbye response.
+///
+/// Lists the memberships the client held right before being dropped.
+#[derive(Deserialize, Serialize)]
+pub struct SystemGoodbyeResponse {
+    pub groups: Vec<GroupMembership>,
+}
+
+/// System set label request.
+#[derive(Deserialize, Serialize)]
+pub struct SystemSetLabelRequest {
+    pub label: String,
+}
+
+/// System set label response, containing the label after sanitization.
+#[derive(Deserialize, Serialize)]
+pub struct SystemSetLabelResponse {
+    pub label: String,
+}
+
+/// Optional features enabled in the server.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct ServerFeatures {
+    /// Whether clients must present a bearer token when connecting.
+    pub auth: bool,
+    /// Whether connections are served over TLS.
+    pub tls: bool,
+    /// Whether disconnected clients can resume with a token.
+    pub resume: bool,
+}
+
+/// System info response.
+#[derive(Deserialize, Serialize)]
+pub struct SystemInfoResponse {
+    /// Version of the server.
+    pub version: String,
+    /// Methods handled by the default services.
+    pub methods: Vec<String>,
+    /// Session kinds that can be created.
+    #[serde(rename = "sessionKinds")]
+    pub session_kinds: Vec<SessionKind>,
+    /// Encodings accepted for session values and messages.
+    pub encodings: Vec<MessageEncoding>,
+    /// Optional features enabled in the server.
+    pub features: ServerFeatures,
+}
+
+/// System service that handles incoming requests and maps
+/// them to the corresponding methods.
+#[cfg(feature = "server")]
+#[derive(Default)]
+pub struct SystemService {
+    /// Optional features reported by `system_info`.
+    features: ServerFeatures,
+}
+
+#[axum::async_trait]
+#[cfg(feature = "server")]
+impl Service for SystemService {
+    async fn handle(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let method =
+            SystemMethod::from_str(req.method()).map_err(|_| json_rpc2::Error::MethodNotFound {
+                name: req.method().to_string(),
+                id: req.id().clone(),
+            })?;
+        let response = match method {
+            SystemMethod::SystemGoodbye => self.system_goodbye(req, ctx, client_id).await?,
+            SystemMethod::SystemSetLabel => self.system_set_label(req, ctx, client_id).await?,
+            SystemMethod::SystemInfo => self.system_info(req).await?,
+        };
+        Ok(response)
+    }
+}
+
+#[cfg(feature = "server")]
+impl SystemService {
+    /// Creates a new system service reporting the given features.
+    pub fn new(features: ServerFeatures) -> Self {
+        Self { features }
+    }
+
+    async fn system_goodbye(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        tracing::info!("Building goodbye summary");
+        let (state, _) = ctx;
+        let groups = state
+            .get_client_memberships(client_id)
+            .await
+            .into_iter()
+            .map(|(group_id, sessions)| GroupMembership {
+                group_id,
+                sessions: sessions
+                    .into_iter()
+                    .map(|(session_id, party_number)| SessionMembership {
+                        session_id,
+                        party_number,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let res = serde_json::to_value(SystemGoodbyeResponse { groups })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+
+    async fn system_info(&self, req: &Request) -> ServiceResponse {
+        let methods = group_service::GroupMethod::iter()
+            .map(|method| method.to_string())
+            .chain(session_service::SessionMethod::iter().map(|method| method.to_string()))
+            .chain(SystemMethod::iter().map(|method| method.to_string()))
+            .collect();
+        let res = serde_json::to_value(SystemInfoResponse {
+            version: VERSION.into(),
+            methods,
+            session_kinds: SessionKind::ALL.to_vec(),
+            encodings: MessageEncoding::ALL.to_vec(),
+            features: self.features,
+        })
+        .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+
+    async fn system_set_label(
+        &self,
+        req: &Request,
+        ctx: (
+            std::sync::Arc<State>,
+            std::sync::Arc<Mutex<Vec<Notification>>>,
+        ),
+        client_id: ClientId,
+    ) -> ServiceResponse {
+        let params: SystemSetLabelRequest = req.deserialize()?;
+        let label = sanitize_label(&params.label);
+        if label.is_empty() {
+            return Err(Error::InvalidParams {
+                id: req.id().clone(),
+                data: "label must not be empty".into(),
+            });
+        }
+        tracing::info!(label, "Setting connection label");
+        let (state, _) = ctx;
+        state
+            .set_client_label(client_id, label.clone())
+            .await
+            .map_err(|e| Error::InvalidParams {
+                id: req.id().clone(),
+                data: e.to_string(),
+            })?;
+        let res = serde_json::to_value(SystemSetLabelResponse { label })
+            .map_err(|e| Error::from(Box::from(e)))?;
+        Ok(Some((req, res).into()))
+    }
+}
+
+/// Sanitizes a connection label, replacing characters other than ASCII
+/// alphanumerics, `-`, `_`, `.` and `:` with `_` and truncating it to
+/// [`MAX_LABEL_LENGTH`] characters.
+#[cfg(feature = "server")]
+fn sanitize_label(label: &str) -> String {
+    label
+        .trim()
+        .chars()
+        .take(MAX_LABEL_LENGTH)
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
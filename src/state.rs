@@ -3,29 +3,149 @@
 //! This module contains the state of the server and the different types used
 //! to represent it.
 
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 #[cfg(feature = "server")]
 use self::{
-    group::{Group, GroupId},
+    group::{Group, GroupId, GroupStats, JoinCode},
+    memory::MemoryTracker,
     parameters::Parameters,
-    session::{Session, SessionId, SessionKind, SessionPartyNumber, SessionValue},
+    session::{
+        MessageId, MessageSeq, Session, SessionError, SessionFullPolicy, SessionHistoryEvent,
+        SessionId, SessionKind, SessionLifecycle, SessionOptions, SessionPartyNumber,
+        SessionProgress, SessionReadiness, SessionSignup, SessionValue,
+    },
+    store::{GroupRecord, MemoryStateStore, StateStore},
 };
 #[cfg(feature = "server")]
+use crate::server::Codec;
+#[cfg(feature = "server")]
+use crate::telemetry::{ACTIVE_GROUPS, ACTIVE_SESSIONS, CONNECTED_CLIENTS};
+#[cfg(feature = "server")]
 use anyhow::Result;
 #[cfg(feature = "server")]
-use std::collections::HashMap;
+use dashmap::{mapref::entry::Entry, DashMap};
+#[cfg(feature = "server")]
+#[cfg(feature = "server")]
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+#[cfg(feature = "server")]
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+#[cfg(feature = "server")]
+use std::time::{Duration, Instant};
 #[cfg(feature = "server")]
 use thiserror::Error;
 #[cfg(feature = "server")]
-use tokio::sync::{mpsc::UnboundedSender, RwLock};
+use tokio::sync::mpsc::Sender;
+#[cfg(feature = "server")]
+use tokio_util::sync::CancellationToken;
 
 pub mod group;
+#[cfg(feature = "server")]
+pub mod memory;
 pub mod parameters;
 pub mod session;
+#[cfg(feature = "server")]
+pub mod store;
+
+/// Unique ID of a client, used to route messages inside the server.
+///
+/// It never leaves the server: it serializes as its [`PublicClientId`] and
+/// can't be deserialized, clients are identified in requests by their
+/// public id instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ClientId(Uuid);
+
+/// ID of a client as exposed to clients, derived from its [`ClientId`]
+/// without revealing it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct PublicClientId(Uuid);
 
-/// Unique ID of a client.
-pub type ClientId = Uuid;
+impl ClientId {
+    /// Generates a new random client id.
+    #[cfg(feature = "server")]
+    pub(crate) fn new_v4() -> Self {
+        Self(Uuid::new_v4())
+    }
+
+    /// Client id that doesn't belong to any client.
+    pub fn nil() -> Self {
+        Self(Uuid::nil())
+    }
+
+    /// Returns a boolean indicating if this is the nil client id.
+    pub fn is_nil(&self) -> bool {
+        self.0.is_nil()
+    }
+
+    /// Wraps an internal id, e.g. one recovered from a resume token.
+    #[cfg(feature = "server")]
+    pub(crate) fn from_uuid(id: Uuid) -> Self {
+        Self(id)
+    }
+
+    /// Returns the internal id, only for tokens handed to the client itself.
+    #[cfg(feature = "server")]
+    pub(crate) fn as_uuid(&self) -> &Uuid {
+        &self.0
+    }
+
+    /// Returns the id other clients know this client by, a hash of the
+    /// internal id.
+    pub fn public_id(&self) -> PublicClientId {
+        use sha2::{Digest, Sha256};
+        let digest = Sha256::new()
+            .chain_update(b"mpc-manager public client id")
+            .chain_update(self.0.as_bytes())
+            .finalize();
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        PublicClientId(uuid::Builder::from_random_bytes(bytes).into_uuid())
+    }
+}
+
+impl std::fmt::Display for ClientId {
+    /// Formats the public id, so client ids can't leak through error
+    /// messages or logs.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.public_id().fmt(f)
+    }
+}
+
+impl Serialize for ClientId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.public_id().serialize(serializer)
+    }
+}
+
+impl std::fmt::Display for PublicClientId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Groups a client is a member of, along with the sessions of each group
+/// where the client holds a party number.
+#[cfg(feature = "server")]
+pub type Memberships = BTreeMap<GroupId, BTreeSet<SessionId>>;
+
+/// Standby promoted to the party number of a dropped client: clones of the
+/// group and session without sensitive information, the promoted client,
+/// its party number and a boolean indicating if the session reached its
+/// threshold with it.
+#[cfg(feature = "server")]
+pub type Promotion = (Group, Session, ClientId, SessionPartyNumber, bool);
+
+/// Characters of a join code, the base32 alphabet.
+#[cfg(feature = "server")]
+const JOIN_CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+/// Number of characters of a join code.
+#[cfg(feature = "server")]
+const JOIN_CODE_LENGTH: usize = 8;
 
 /// Error type for state operations.
 #[derive(Debug, Error)]
@@ -46,16 +166,202 @@ pub enum StateError {
     /// Error generated when a client was not found.
     #[error("client id `{0}` not found")]
     ClientNotFound(ClientId),
+    /// Error generated when a client is not a member of a group.
+    #[error("client id `{0}` is not a member of group `{1}`")]
+    ClientNotInGroup(ClientId, GroupId),
+    /// Error generated when a client already joined a group.
+    #[error("client id `{0}` is already a member of group `{1}`")]
+    AlreadyMember(ClientId, GroupId),
+    /// Error generated when a client id is already in use.
+    #[error("client id `{0}` is already in use")]
+    ClientAlreadyExists(ClientId),
+    /// Error generated when a client is not a party of a session.
+    #[error("client id `{0}` is not a party of session `{1}`")]
+    ClientNotInSession(ClientId, SessionId),
+    /// Error generated when a session is already full.
+    #[error("session `{0}` is full")]
+    SessionFull(SessionId),
+    /// Error generated when the maximum number of groups is reached.
+    #[error("maximum number of groups `{0}` reached")]
+    TooManyGroups(usize),
+    /// Error generated when a group reached its maximum number of sessions.
+    #[error("group `{0}` reached the maximum number of sessions `{1}`")]
+    TooManySessions(GroupId, usize),
+    /// Error generated when a join code doesn't belong to any group.
+    #[error("join code `{0}` not found")]
+    JoinCodeNotFound(JoinCode),
+    /// Error generated when a client other than the creator of a group
+    /// attempts an action reserved to the creator.
+    #[error("client id `{0}` is not the creator of group `{1}`")]
+    NotGroupCreator(ClientId, GroupId),
+    /// Error generated when a public client id doesn't belong to any
+    /// member of a group.
+    #[error("client id `{0}` is not a member of group `{1}`")]
+    MemberNotFound(PublicClientId, GroupId),
+}
+
+#[cfg(feature = "server")]
+impl StateError {
+    /// Returns the JSON-RPC error code clients receive for this error.
+    ///
+    /// Codes are stable and grouped by the kind of failure:
+    ///
+    /// | Code     | Error                 | Kind            |
+    /// |----------|-----------------------|-----------------|
+    /// | `-32010` | `GroupNotFound`       | Not found       |
+    /// | `-32011` | `SessionNotFound`     | Not found       |
+    /// | `-32012` | `PartyNotFound`       | Not found       |
+    /// | `-32013` | `ClientNotFound`      | Not found       |
+    /// | `-32014` | `JoinCodeNotFound`    | Not found       |
+    /// | `-32020` | `ClientNotInGroup`    | Invalid         |
+    /// | `-32021` | `ClientNotInSession`  | Invalid         |
+    /// | `-32022` | `AlreadyMember`       | Invalid         |
+    /// | `-32023` | `ClientAlreadyExists` | Invalid         |
+    /// | `-32024` | `NotGroupCreator`     | Invalid         |
+    /// | `-32025` | `MemberNotFound`      | Invalid         |
+    /// | `-32030` | `GroupIsFull`         | Full, retryable |
+    /// | `-32031` | `SessionFull`         | Full, retryable |
+    /// | `-32032` | `TooManyGroups`       | Full, retryable |
+    /// | `-32033` | `TooManySessions`     | Full, retryable |
+    pub fn code(&self) -> isize {
+        match self {
+            Self::GroupNotFound(_) => -32010,
+            Self::SessionNotFound(_, _) => -32011,
+            Self::PartyNotFound(_) => -32012,
+            Self::ClientNotFound(_) => -32013,
+            Self::JoinCodeNotFound(_) => -32014,
+            Self::ClientNotInGroup(_, _) => -32020,
+            Self::ClientNotInSession(_, _) => -32021,
+            Self::AlreadyMember(_, _) => -32022,
+            Self::ClientAlreadyExists(_) => -32023,
+            Self::NotGroupCreator(_, _) => -32024,
+            Self::MemberNotFound(_, _) => -32025,
+            Self::GroupIsFull(_) => -32030,
+            Self::SessionFull(_) => -32031,
+            Self::TooManyGroups(_) => -32032,
+            Self::TooManySessions(_, _) => -32033,
+        }
+    }
+}
+
+/// Slot of a connection counted towards the maximum number of connections,
+/// released on drop.
+#[derive(Debug)]
+#[cfg(feature = "server")]
+pub struct ConnectionPermit {
+    connections: Arc<AtomicUsize>,
+}
+
+#[cfg(feature = "server")]
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.connections.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Point-in-time counts of the state of the server.
+#[derive(Debug, Clone, Serialize)]
+#[cfg(feature = "server")]
+pub struct StateSnapshot {
+    /// Number of connected clients.
+    pub clients: usize,
+    /// Snapshots of every group.
+    pub groups: Vec<GroupSnapshot>,
+}
+
+/// Point-in-time counts of a group.
+#[derive(Debug, Clone, Serialize)]
+#[cfg(feature = "server")]
+pub struct GroupSnapshot {
+    pub id: GroupId,
+    /// Number of members of the group.
+    pub members: usize,
+    /// Snapshots of every session of the group.
+    pub sessions: Vec<SessionSnapshot>,
+}
+
+/// Point-in-time counts of a session.
+#[derive(Debug, Clone, Serialize)]
+#[cfg(feature = "server")]
+pub struct SessionSnapshot {
+    pub id: SessionId,
+    pub kind: SessionKind,
+    /// Number of parties signed up to the session.
+    pub parties: usize,
+    /// Whether the session reached the threshold of its kind.
+    pub ready: bool,
+}
+
+/// Connection metadata of a client.
+#[derive(Debug)]
+#[cfg(feature = "server")]
+struct Client {
+    /// Channel used to send encoded messages to the client.
+    tx: Sender<Vec<u8>>,
+    /// Codec used to encode messages sent to the client.
+    codec: Codec,
+    /// Token cancelled to close the connection of the client.
+    disconnect: CancellationToken,
+    /// Label chosen by the client, used for logging only.
+    label: Option<String>,
 }
 
 /// Shared state of clients and db managed by the server.
-#[derive(Debug, Default)]
+///
+/// Maps are sharded so operations on different groups don't contend,
+/// references to their entries must never be held across an `.await`.
+#[derive(Debug)]
 #[cfg(feature = "server")]
 pub struct State {
     /// Connected clients.
-    clients: RwLock<HashMap<ClientId, UnboundedSender<String>>>,
+    clients: DashMap<ClientId, Client>,
     /// Collection of groups mapped by UUID.
-    groups: RwLock<HashMap<GroupId, Group>>,
+    groups: DashMap<GroupId, Group>,
+    /// Maximum number of concurrent groups, unlimited if `None`.
+    max_groups: Option<usize>,
+    /// Number of groups, including the ones being created. Slots are
+    /// reserved before inserting a group so `max_groups` is never exceeded.
+    group_count: AtomicUsize,
+    /// Maximum number of sessions of each group, unlimited if `None`.
+    max_sessions_per_group: Option<usize>,
+    /// Maximum number of concurrent connections, unlimited if `None`.
+    max_connections: Option<usize>,
+    /// Number of open connections, including the ones being upgraded.
+    connections: Arc<AtomicUsize>,
+    /// Group ids mapped by their join code.
+    join_codes: DashMap<JoinCode, GroupId>,
+    /// Approximate memory held on behalf of clients.
+    memory: MemoryTracker,
+    /// Number of connected clients, readable without locking `clients`.
+    client_count: AtomicUsize,
+    /// Disconnected clients whose memberships are reserved until they
+    /// resume or the reservation expires.
+    reservations: DashMap<ClientId, Instant>,
+    /// Store of the non-sensitive metadata of groups.
+    store: Arc<dyn StateStore>,
+    /// Whether changes are no longer forwarded to the store.
+    store_frozen: AtomicBool,
+}
+
+#[cfg(feature = "server")]
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            clients: DashMap::default(),
+            groups: DashMap::default(),
+            max_groups: None,
+            group_count: AtomicUsize::default(),
+            max_sessions_per_group: None,
+            max_connections: None,
+            connections: Arc::default(),
+            join_codes: DashMap::default(),
+            memory: MemoryTracker::default(),
+            client_count: AtomicUsize::default(),
+            reservations: DashMap::default(),
+            store: Arc::new(MemoryStateStore::default()),
+            store_frozen: AtomicBool::new(false),
+        }
+    }
 }
 
 #[cfg(feature = "server")]
@@ -65,122 +371,679 @@ impl State {
         Self::default()
     }
 
+    /// Sets the memory budget, shedding load once it's exhausted.
+    pub fn with_memory_budget(mut self, budget: Option<usize>) -> Self {
+        self.memory = MemoryTracker::new(budget);
+        self
+    }
+
+    /// Sets the maximum number of concurrent groups, rejecting new
+    /// groups once it's reached.
+    pub fn with_max_groups(mut self, max_groups: Option<usize>) -> Self {
+        self.max_groups = max_groups;
+        self
+    }
+
+    /// Sets the maximum number of sessions of each group, rejecting new
+    /// sessions of a group once it's reached.
+    pub fn with_max_sessions_per_group(mut self, max_sessions_per_group: Option<usize>) -> Self {
+        self.max_sessions_per_group = max_sessions_per_group;
+        self
+    }
+
+    /// Sets the maximum number of concurrent connections, new connections
+    /// are rejected once it's reached.
+    pub fn with_max_connections(mut self, max_connections: Option<usize>) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// Acquires a connection slot, returning `None` if the maximum number of
+    /// connections is reached. The slot is released when the permit is dropped.
+    pub fn acquire_connection(&self) -> Option<ConnectionPermit> {
+        self.connections
+            .fetch_update(
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                |connections| match self.max_connections {
+                    Some(max_connections) if connections >= max_connections => None,
+                    _ => Some(connections + 1),
+                },
+            )
+            .ok()?;
+        Some(ConnectionPermit {
+            connections: self.connections.clone(),
+        })
+    }
+
+    /// Sets the store notified of every structural change of the groups.
+    pub fn with_store(mut self, store: Arc<dyn StateStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Restores the groups saved in the store, returning the number of
+    /// groups restored.
+    ///
+    /// Groups that had no members when saved are discarded. Restored groups
+    /// have no members nor parties, clients must join them again within
+    /// `reclaim_window` or the group expires.
+    pub async fn restore(&self, reclaim_window: Duration) -> Result<usize> {
+        let mut restored = 0;
+        for record in self.store.load_groups()? {
+            if record.clients == 0 || self.groups.contains_key(&record.id) {
+                self.store.remove_group(&record.id)?;
+                continue;
+            }
+            let mut group = record.into_group();
+            group.reclaim_deadline = Some(Instant::now() + reclaim_window);
+            let mut sessions = 0;
+            group.sessions().for_each(|session| {
+                self.memory.reserve(session.approximate_size());
+                sessions += 1;
+            });
+            tracing::info!(group_id = group.id.to_string(), sessions, "Restoring group");
+            metrics::increment_gauge!(ACTIVE_SESSIONS, sessions as f64);
+            metrics::increment_gauge!(ACTIVE_GROUPS, 1.0);
+            self.group_count.fetch_add(1, Ordering::AcqRel);
+            self.groups.insert(group.id, group);
+            restored += 1;
+        }
+        Ok(restored)
+    }
+
+    /// Stops forwarding changes to the store, so the metadata saved before
+    /// a graceful shutdown survives the connections being dropped.
+    pub fn freeze_store(&self) {
+        self.store_frozen.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the memory tracker.
+    pub fn memory(&self) -> &MemoryTracker {
+        &self.memory
+    }
+
     /// Returns a new client id.
     pub fn new_client_id(&self) -> ClientId {
-        Uuid::new_v4()
+        ClientId::new_v4()
     }
 
-    /// Adds a new client.
-    pub async fn add_client(&self, id: ClientId, tx: UnboundedSender<String>) {
-        self.clients.write().await.insert(id, tx);
+    /// Adds a new client, whose connection is closed once `disconnect`
+    /// is cancelled.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if a client with the same id is already connected,
+    ///   which can only happen when ids are provisioned externally.
+    pub async fn add_client(
+        &self,
+        id: ClientId,
+        tx: Sender<Vec<u8>>,
+        codec: Codec,
+        disconnect: CancellationToken,
+    ) -> Result<()> {
+        match self.clients.entry(id) {
+            Entry::Occupied(_) => Err(StateError::ClientAlreadyExists(id).into()),
+            Entry::Vacant(entry) => {
+                entry.insert(Client {
+                    tx,
+                    codec,
+                    disconnect,
+                    label: None,
+                });
+                self.client_count.fetch_add(1, Ordering::Relaxed);
+                metrics::increment_gauge!(CONNECTED_CLIENTS, 1.0);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the number of connected clients.
+    pub async fn get_number_of_clients(&self) -> usize {
+        self.client_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the counts of clients, group members and session parties.
+    ///
+    /// Groups are visited once, each one locked only while it's read, so
+    /// the snapshot is cheap but not atomic across groups.
+    pub async fn snapshot(&self) -> StateSnapshot {
+        let groups = self
+            .groups
+            .iter()
+            .map(|group| GroupSnapshot {
+                id: group.id,
+                members: group.clients().len(),
+                sessions: group
+                    .sessions()
+                    .map(|session| SessionSnapshot {
+                        id: session.id,
+                        kind: session.kind.clone(),
+                        parties: session.get_number_of_clients(),
+                        ready: session
+                            .threshold_reached(&group.params, self.count_ready_parties(session)),
+                    })
+                    .collect(),
+            })
+            .collect();
+        StateSnapshot {
+            clients: self.client_count.load(Ordering::Relaxed),
+            groups,
+        }
     }
 
-    /// Returns client data.
-    pub async fn get_client(&self, id: &ClientId) -> Option<UnboundedSender<String>> {
-        self.clients.read().await.get(id).cloned()
+    /// Returns the channel of a client and the codec its messages must
+    /// be encoded with.
+    pub async fn get_client(&self, id: &ClientId) -> Option<(Sender<Vec<u8>>, Codec)> {
+        self.clients
+            .get(id)
+            .map(|client| (client.tx.clone(), client.codec))
+    }
+
+    /// Closes the connection of a client, the client is dropped once its
+    /// connection handler finishes.
+    pub async fn disconnect_client(&self, id: &ClientId) {
+        if let Some(client) = self.clients.get(id) {
+            client.disconnect.cancel();
+        }
+    }
+
+    /// Sets the label of a connected client, used for logging only.
+    pub async fn set_client_label(&self, id: ClientId, label: String) -> Result<()> {
+        let mut client = self
+            .clients
+            .get_mut(&id)
+            .ok_or(StateError::ClientNotFound(id))?;
+        client.label = Some(label);
+        Ok(())
+    }
+
+    /// Returns the label of a connected client, if set.
+    pub async fn get_client_label(&self, id: &ClientId) -> Option<String> {
+        self.clients.get(id)?.label.clone()
     }
 
     /// Drops a client, performing all necessary cleanup to preserve
     /// security.
-    pub async fn drop_client(&self, id: ClientId) {
+    ///
+    /// Returns clones without sensitive information of the groups removed
+    /// because they became empty, along with the connected clients that
+    /// were still part of them, and the standbys promoted to the party
+    /// numbers of the client.
+    pub async fn drop_client(&self, id: ClientId) -> (Vec<(Group, Vec<ClientId>)>, Vec<Promotion>) {
         // Remove client from groups and remove group if empty
-        let mut groups = self.groups.write().await;
-        let mut empty_groups: Vec<Uuid> = Vec::new();
-        groups.iter_mut().for_each(|(group_id, group)| {
-            group.drop_client(id);
+        let mut empty_groups: Vec<GroupId> = Vec::new();
+        let mut promotions = Vec::new();
+        self.groups.iter_mut().for_each(|mut group| {
+            if !group.has_client(&id) {
+                return;
+            }
+            let params = group.params.clone();
+            self.memory.release(group.sessions_size());
+            let dropped = group.drop_client(id);
+            self.memory.reserve(group.sessions_size());
+            for (session_id, promoted, party_number) in dropped {
+                let Some(session) = group.get_session_mut(&session_id) else {
+                    continue;
+                };
+                // The promoted standby may complete the parties counted
+                let parties = self.count_ready_parties(session);
+                let threshold = session.threshold_reached(&params, parties);
+                if threshold {
+                    session.mark_ready();
+                }
+                let session_c = session.sanitized();
+                promotions.push((
+                    group.sanitized(),
+                    session_c,
+                    promoted,
+                    party_number,
+                    threshold,
+                ));
+            }
+            self.persist(&group);
             if group.is_empty() {
-                empty_groups.push(*group_id);
+                empty_groups.push(*group.key());
             }
         });
-        empty_groups.iter().for_each(|group_id| {
-            tracing::info!(group_id = group_id.to_string(), "Removing empty group");
-            groups.remove(group_id);
-        });
+        let closed = empty_groups
+            .iter()
+            .filter_map(|group_id| {
+                let (_, group) = self
+                    .groups
+                    .remove_if(group_id, |_, group| group.is_empty())?;
+                tracing::info!(group_id = group_id.to_string(), "Removing empty group");
+                let client_ids = self
+                    .release_group(&group)
+                    .into_iter()
+                    .filter(|client_id| *client_id != id)
+                    .collect();
+                Some((group.sanitized(), client_ids))
+            })
+            .collect();
+
+        self.reservations.remove(&id);
+        self.remove_client(id);
+        (closed, promotions)
+    }
 
-        // TODO: remove from sessions?
+    /// Disconnects a client keeping its group and session memberships
+    /// reserved for `window`, so it can resume with the same client id.
+    ///
+    /// The client must be dropped with [`State::expire_reservation`] once
+    /// the window elapses.
+    pub async fn reserve_client(&self, id: ClientId, window: Duration) {
+        self.reservations.insert(id, Instant::now() + window);
+        self.remove_client(id);
+    }
 
-        // Remove client
-        self.clients.write().await.remove(&id);
+    /// Reclaims the reservation of a disconnected client, returning a boolean
+    /// indicating if it was reserved, not expired and its memberships were
+    /// accepted by `accept`.
+    pub async fn reclaim_client(
+        &self,
+        id: ClientId,
+        accept: impl FnOnce(&Memberships) -> bool,
+    ) -> bool {
+        self.reservations
+            .remove_if(&id, |_, expires_at| {
+                *expires_at > Instant::now() && accept(&self.memberships(id))
+            })
+            .is_some()
+    }
+
+    /// Drops a disconnected client if its reservation expired, a reservation
+    /// renewed after reconnecting and disconnecting again is kept.
+    ///
+    /// Returns the groups removed along with the client and the promoted
+    /// standbys, as in [`State::drop_client`].
+    pub async fn expire_reservation(
+        &self,
+        id: ClientId,
+    ) -> (Vec<(Group, Vec<ClientId>)>, Vec<Promotion>) {
+        if self
+            .reservations
+            .remove_if(&id, |_, expires_at| *expires_at <= Instant::now())
+            .is_some()
+        {
+            tracing::info!(client_id = id.to_string(), "Resume reservation expired");
+            return self.drop_client(id).await;
+        }
+        (Vec::new(), Vec::new())
+    }
+
+    /// Removes the connection of a client.
+    fn remove_client(&self, id: ClientId) {
+        if self.clients.remove(&id).is_some() {
+            self.client_count.fetch_sub(1, Ordering::Relaxed);
+            metrics::decrement_gauge!(CONNECTED_CLIENTS, 1.0);
+        }
     }
 
     /// Adds a new group to the state, returning a clone without
     /// sensitive information for logging purposes.
-    pub async fn add_group(&self, params: Parameters) -> Group {
+    ///
+    /// If `kind` is set, only sessions of that kind can be created
+    /// in the group. If `ttl_secs` is set, the group is removed once it
+    /// elapses unless the group filled up before. If `join_code` is set, a
+    /// unique join code is generated along with the group id. The
+    /// `creator` is the only client allowed to kick members later on.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the maximum number of groups is reached.
+    pub async fn add_group(
+        &self,
+        creator: ClientId,
+        params: Parameters,
+        kind: Option<SessionKind>,
+        ttl_secs: Option<u64>,
+        join_code: bool,
+    ) -> Result<Group> {
+        self.reserve_group()?;
         let uuid = Uuid::new_v4();
-        let group = Group::new(uuid, params);
-        let group_c = group.clone();
-        self.groups.write().await.insert(uuid, group);
-        group_c
+        let mut group = Group::new(uuid, params, kind, ttl_secs, creator);
+        if join_code {
+            group.join_code = Some(self.new_join_code(uuid));
+        }
+        let group_c = group.sanitized();
+        self.persist(&group);
+        self.groups.insert(uuid, group);
+        metrics::increment_gauge!(ACTIVE_GROUPS, 1.0);
+        Ok(group_c)
+    }
+
+    /// Reserves a group slot, released once the group is removed.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the maximum number of groups is reached.
+    fn reserve_group(&self) -> Result<()> {
+        self.group_count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |groups| {
+                match self.max_groups {
+                    Some(max_groups) if groups >= max_groups => None,
+                    _ => Some(groups + 1),
+                }
+            })
+            .map_err(|_| StateError::TooManyGroups(self.max_groups.unwrap_or_default()))?;
+        Ok(())
+    }
+
+    /// Generates a join code not used by any other group, reserving it
+    /// for the given group.
+    fn new_join_code(&self, group_id: GroupId) -> JoinCode {
+        loop {
+            let code: JoinCode = (0..JOIN_CODE_LENGTH)
+                .map(|_| {
+                    JOIN_CODE_ALPHABET[rand::random::<usize>() % JOIN_CODE_ALPHABET.len()] as char
+                })
+                .collect();
+            if let Entry::Vacant(entry) = self.join_codes.entry(code.clone()) {
+                entry.insert(group_id);
+                return code;
+            }
+        }
+    }
+
+    /// Returns the id of the group a join code belongs to, codes are
+    /// case insensitive.
+    pub async fn get_group_id_from_join_code(&self, join_code: &str) -> Result<GroupId> {
+        let join_code = join_code.trim().to_uppercase();
+        let group_id = self
+            .join_codes
+            .get(&join_code)
+            .ok_or(StateError::JoinCodeNotFound(join_code.clone()))?;
+        Ok(*group_id)
     }
 
     /// Joins a client to a group, returning a clone without
-    /// sensitive information for logging purposes.
-    pub async fn join_group(&self, group_id: GroupId, client_id: ClientId) -> Result<Group> {
-        // Validate group exists and is not full
-        let groups = self.groups.read().await;
-        let group = groups
-            .get(&group_id)
+    /// sensitive information for logging purposes and the number of clients
+    /// in the group after joining.
+    pub async fn join_group(
+        &self,
+        group_id: GroupId,
+        client_id: ClientId,
+    ) -> Result<(Group, usize)> {
+        // Validate group exists, client is not a member and group is not full
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
             .ok_or(StateError::GroupNotFound(group_id))?;
+        if group.has_client(&client_id) {
+            return Err(StateError::AlreadyMember(client_id, group_id).into());
+        }
         if group.is_full() {
             return Err(StateError::GroupIsFull(group_id).into());
         }
 
         // Join group
-        let mut groups = self.groups.write().await;
-        let group = groups.get_mut(&group_id).unwrap(); // validation was done previously
         group.add_client(client_id)?;
-        Ok(group.clone())
+        self.persist(&group);
+        Ok((group.sanitized(), group.clients().len()))
+    }
+
+    /// Removes a client from a group and all of its sessions, removing
+    /// the group if it becomes empty.
+    ///
+    /// Returns a clone without sensitive information for logging purposes,
+    /// the number of clients remaining in the group and the standbys that
+    /// were promoted to the freed party numbers.
+    ///
+    /// If the group became empty it is removed, and the connected clients
+    /// that were still part of its sessions are returned as well.
+    pub async fn leave_group(
+        &self,
+        group_id: GroupId,
+        client_id: ClientId,
+    ) -> Result<(
+        Group,
+        usize,
+        Vec<(Session, ClientId, SessionPartyNumber)>,
+        Option<Vec<ClientId>>,
+    )> {
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        if !group.has_client(&client_id) {
+            return Err(StateError::ClientNotInGroup(client_id, group_id).into());
+        }
+
+        self.memory.release(group.sessions_size());
+        let removed = group.remove_client(client_id);
+        self.memory.reserve(group.sessions_size());
+        let promotions = removed
+            .into_iter()
+            .filter_map(|(session_id, promoted, party_number)| {
+                let session = group.get_session(&session_id)?.sanitized();
+                Some((session, promoted, party_number))
+            })
+            .collect();
+        let group_c = group.sanitized();
+        let remaining = group.clients().len();
+        self.persist(&group);
+        drop(group);
+        let closed = self
+            .groups
+            .remove_if(&group_id, |_, group| group.is_empty())
+            .map(|(_, group)| {
+                tracing::info!(group_id = group_id.to_string(), "Removing empty group");
+                self.release_group(&group)
+            });
+        Ok((group_c, remaining, promotions, closed))
+    }
+
+    /// Removes a member from a group on behalf of the group creator, see
+    /// [`State::leave_group`] for the returned values.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the client is not the creator of the group.
+    /// * Returns an error if the target is not a member of the group.
+    pub async fn kick_from_group(
+        &self,
+        group_id: GroupId,
+        client_id: ClientId,
+        target: ClientId,
+    ) -> Result<(
+        Group,
+        usize,
+        Vec<(Session, ClientId, SessionPartyNumber)>,
+        Option<Vec<ClientId>>,
+    )> {
+        {
+            let group = self
+                .groups
+                .get(&group_id)
+                .ok_or(StateError::GroupNotFound(group_id))?;
+            if !group.is_creator(&client_id) {
+                return Err(StateError::NotGroupCreator(client_id, group_id).into());
+            }
+        }
+        self.leave_group(group_id, target).await
+    }
+
+    /// Removes a group on behalf of its creator, along with all of its
+    /// sessions.
+    ///
+    /// Returns a clone without sensitive information and the connected
+    /// clients that were still part of it, the creator included.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the group doesn't exist.
+    /// * Returns an error if the client is not the creator of the group.
+    pub async fn close_group(
+        &self,
+        group_id: GroupId,
+        client_id: ClientId,
+    ) -> Result<(Group, Vec<ClientId>)> {
+        let (_, group) = self
+            .groups
+            .remove_if(&group_id, |_, group| group.is_creator(&client_id))
+            .ok_or_else(|| {
+                if self.groups.contains_key(&group_id) {
+                    StateError::NotGroupCreator(client_id, group_id)
+                } else {
+                    StateError::GroupNotFound(group_id)
+                }
+            })?;
+        tracing::info!(group_id = group_id.to_string(), "Closing group");
+        let client_ids = self.release_group(&group);
+        Ok((group.sanitized(), client_ids))
+    }
+
+    /// Returns the parameters of a group, its number of clients and a
+    /// boolean indicating if it's full.
+    pub async fn get_group_info(&self, group_id: GroupId) -> Result<(Parameters, usize, bool)> {
+        let group = self
+            .groups
+            .get(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        Ok((group.params.clone(), group.clients().len(), group.is_full()))
+    }
+
+    /// Returns the historical participation counters of a group.
+    pub async fn get_group_stats(&self, group_id: GroupId) -> Result<GroupStats> {
+        let group = self
+            .groups
+            .get(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        Ok(group.stats().clone())
+    }
+
+    /// Returns the ids of the groups a client is a member of.
+    pub async fn groups_for_client(&self, client_id: ClientId) -> Vec<GroupId> {
+        self.groups
+            .iter()
+            .filter(|group| group.has_client(&client_id))
+            .map(|group| group.id)
+            .collect()
+    }
+
+    /// Returns the groups a client is a member of, along with the sessions
+    /// of each group where the client holds a party number.
+    pub async fn get_client_memberships(
+        &self,
+        client_id: ClientId,
+    ) -> Vec<(GroupId, Vec<(SessionId, SessionPartyNumber)>)> {
+        self.groups
+            .iter()
+            .filter(|group| group.has_client(&client_id))
+            .map(|group| {
+                let sessions = group
+                    .sessions()
+                    .filter_map(|session| {
+                        session
+                            .get_party_number(&client_id)
+                            .map(|party_number| (session.id, party_number))
+                    })
+                    .collect();
+                (group.id, sessions)
+            })
+            .collect()
+    }
+
+    /// Returns the memberships of a client, as bound to its resume tokens.
+    pub fn memberships(&self, client_id: ClientId) -> Memberships {
+        self.groups
+            .iter()
+            .filter(|group| group.has_client(&client_id))
+            .map(|group| {
+                let sessions = group
+                    .sessions()
+                    .filter(|session| session.get_party_number(&client_id).is_some())
+                    .map(|session| session.id)
+                    .collect();
+                (group.id, sessions)
+            })
+            .collect()
     }
 
     /// Adds a new session, returning a clone without sensitive information
     /// for logging purposes.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the group reached the maximum number of sessions.
     pub async fn add_session(
         &self,
         group_id: GroupId,
         kind: SessionKind,
         value: SessionValue,
+        options: SessionOptions,
     ) -> Result<(Group, Session)> {
-        // Validate group exists
-        let groups = self.groups.read().await;
-        groups
-            .get(&group_id)
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
             .ok_or(StateError::GroupNotFound(group_id))?;
-
-        // Add session
-        let mut groups = self.groups.write().await;
-        let group = groups.get_mut(&group_id).unwrap();
-        let session = group.add_session(kind, value);
-        Ok((group.clone(), session))
+        if let Some(max_sessions) = self.max_sessions_per_group {
+            if group.sessions().count() >= max_sessions {
+                return Err(StateError::TooManySessions(group_id, max_sessions).into());
+            }
+        }
+        let session = group.add_session(kind, value, options)?;
+        self.persist(&group);
+        self.memory.reserve(session.approximate_size());
+        metrics::increment_gauge!(ACTIVE_SESSIONS, 1.0);
+        Ok((group.sanitized(), session))
     }
 
     /// Registers a client to a given session and returns
-    /// a session clone, session party number and a boolean
+    /// a session clone, the signup result and a boolean
     /// indicating if the threshold has been reached.
+    ///
+    /// If the session is already full the client is either rejected or
+    /// queued as a standby, according to the session's full policy.
+    ///
+    /// # Errors
+    ///
+    /// * Returns `StateError::SessionFull` if the session already holds
+    ///   `n` parties, or `t + 1` for a fixed signing set, or every party
+    ///   number is reserved, and its full policy is to reject, so party
+    ///   numbers beyond `n` are never handed out.
     pub async fn signup_session(
         &self,
         client_id: ClientId,
         group_id: GroupId,
         session_id: SessionId,
-    ) -> Result<(Group, Session, SessionPartyNumber, bool)> {
-        // Validate group and session exist
-        let groups = self.groups.read().await;
-        let group = groups
-            .get(&group_id)
+    ) -> Result<(Group, Session, SessionSignup, bool)> {
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
             .ok_or(StateError::GroupNotFound(group_id))?;
-        group
-            .get_session(&session_id)
+        let params = group.params.clone();
+        let session = group
+            .get_session_mut(&session_id)
             .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        if session.is_full(&params) {
+            let position = match session.options.on_full {
+                SessionFullPolicy::Reject => return Err(StateError::SessionFull(session_id).into()),
+                SessionFullPolicy::Standby => session.add_standby(client_id)?,
+            };
+            let session_c = session.sanitized();
+            return Ok((
+                group.sanitized(),
+                session_c,
+                SessionSignup::Standby(position),
+                false,
+            ));
+        }
+        let party_index = session.signup(client_id, params.n())?;
 
-        // Signup session
-        let mut groups = self.groups.write().await;
-        let group = groups.get_mut(&group_id).unwrap();
-        let session = group.get_session_mut(&session_id).unwrap();
-        let party_index = session.signup(client_id)?;
-
-        let parties = session.get_number_of_clients();
-        let session_c = session.clone();
-        let threshold = group.params.threshold_reached(session_c.kind, parties);
-        Ok((group.clone(), session_c, party_index, threshold))
+        let parties = self.count_ready_parties(session);
+        let threshold = session.threshold_reached(&params, parties);
+        if threshold {
+            session.mark_ready();
+        }
+        let session_c = session.sanitized();
+        Ok((
+            group.sanitized(),
+            session_c,
+            SessionSignup::Party(party_index),
+            threshold,
+        ))
     }
 
     /// Logins a client witha given party number to a session and returns
@@ -192,30 +1055,456 @@ impl State {
         session_id: SessionId,
         party_number: SessionPartyNumber,
     ) -> Result<(Group, Session, bool)> {
-        // Validate group and session exist
-        let groups = self.groups.read().await;
-        let group = groups
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        let params = group.params.clone();
+        let session = group
+            .get_session_mut(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        if party_number == 0 || party_number > params.n() {
+            return Err(SessionError::PartyNumberOutOfRange(party_number, params.n()).into());
+        }
+        if !session.is_client_in_session(&client_id)
+            && session.get_number_of_clients() >= session.capacity(&params)
+        {
+            return Err(StateError::SessionFull(session_id).into());
+        }
+        session.login(client_id, party_number)?;
+        let parties = self.count_ready_parties(session);
+        let threshold = session.threshold_reached(&params, parties);
+        if threshold {
+            session.mark_ready();
+        }
+        let session_c = session.sanitized();
+        Ok((group.sanitized(), session_c, threshold))
+    }
+
+    /// Assigns a vacant party number of a session to a client and returns
+    /// the session and a boolean indicating if the threshold has been reached.
+    ///
+    /// A party number is vacant once the client holding it is no longer
+    /// a member of the group, i.e. it disconnected without resuming. Before
+    /// the threshold, the first standby takes over a dropped party number
+    /// instead, so only sessions without standbys leave it vacant.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the party number is free or still held by a
+    ///   member of the group.
+    /// * Returns an error if the client already holds a party number.
+    pub async fn claim_vacant_party(
+        &self,
+        client_id: ClientId,
+        group_id: GroupId,
+        session_id: SessionId,
+        party_number: SessionPartyNumber,
+    ) -> Result<(Group, Session, bool)> {
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        let params = group.params.clone();
+        let holder = group
+            .get_session(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?
+            .get_client_id(party_number);
+        if holder.is_none_or(|holder| group.has_client(&holder)) {
+            return Err(SessionError::PartyNumberNotVacant(party_number).into());
+        }
+        let session = group
+            .get_session_mut(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        self.memory.release(session.approximate_size());
+        let claimed = session.claim(client_id, party_number);
+        self.memory.reserve(session.approximate_size());
+        claimed?;
+        let parties = self.count_ready_parties(session);
+        let threshold = session.threshold_reached(&params, parties);
+        if threshold {
+            session.mark_ready();
+        }
+        let session_c = session.sanitized();
+        Ok((group.sanitized(), session_c, threshold))
+    }
+
+    /// Closes a session, removing it from its group. Only a party of the
+    /// session is allowed to close it.
+    ///
+    /// Returns group and session clones without sensitive information and
+    /// the client ids of the other parties of the session.
+    pub async fn close_session(
+        &self,
+        client_id: ClientId,
+        group_id: GroupId,
+        session_id: SessionId,
+    ) -> Result<(Group, Session, Vec<ClientId>)> {
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        let session = group
+            .get_session(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        if !session.is_client_in_session(&client_id) {
+            return Err(StateError::ClientNotInSession(client_id, session_id).into());
+        }
+
+        let session = group
+            .remove_session(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        self.persist(&group);
+        self.memory.release(session.approximate_size());
+        metrics::decrement_gauge!(ACTIVE_SESSIONS, 1.0);
+        let client_ids = session
+            .get_all_client_ids()
+            .into_iter()
+            .filter(|id| *id != client_id)
+            .collect();
+        Ok((group.sanitized(), session.sanitized(), client_ids))
+    }
+
+    /// Aborts a session on behalf of one of its parties, removing it from
+    /// its group if `close` is set.
+    ///
+    /// Returns group and session clones without sensitive information, the
+    /// party number of the aborting client and the client ids of the other
+    /// parties of the session.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the client is not a party of the session.
+    pub async fn abort_session(
+        &self,
+        client_id: ClientId,
+        group_id: GroupId,
+        session_id: SessionId,
+        close: bool,
+    ) -> Result<(Group, Session, SessionPartyNumber, Vec<ClientId>)> {
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        let session = group
+            .get_session(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        let party_number = session
+            .get_party_number(&client_id)
+            .ok_or(StateError::ClientNotInSession(client_id, session_id))?;
+        let client_ids = session
+            .get_all_client_ids()
+            .into_iter()
+            .filter(|id| *id != client_id)
+            .collect();
+        let session_c = session.sanitized();
+
+        if close {
+            let session = group
+                .abort_session(&session_id)
+                .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+            self.persist(&group);
+            self.memory.release(session.approximate_size());
+            metrics::decrement_gauge!(ACTIVE_SESSIONS, 1.0);
+        }
+        Ok((group.sanitized(), session_c, party_number, client_ids))
+    }
+
+    /// Replaces the value of a session, returning group and session clones
+    /// without sensitive information and the other parties of the session.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the client is not a party of the session.
+    /// * Returns an error if the session already reached the threshold, so
+    ///   the value parties are working on never changes under them.
+    pub async fn set_session_value(
+        &self,
+        client_id: ClientId,
+        group_id: GroupId,
+        session_id: SessionId,
+        value: SessionValue,
+    ) -> Result<(Group, Session, Vec<ClientId>)> {
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        let params = group.params.clone();
+        let session = group
+            .get_session_mut(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        if !session.is_client_in_session(&client_id) {
+            return Err(StateError::ClientNotInSession(client_id, session_id).into());
+        }
+        if session.threshold_reached(&params, self.count_ready_parties(session)) {
+            return Err(SessionError::ValueLocked.into());
+        }
+
+        self.memory.release(session.approximate_size());
+        session.value = value;
+        self.memory.reserve(session.approximate_size());
+        session.record_event(SessionLifecycle::ValueUpdated);
+        let session_c = session.sanitized();
+        let client_ids = session
+            .get_all_client_ids()
+            .into_iter()
+            .filter(|id| *id != client_id)
+            .collect();
+        Ok((group.sanitized(), session_c, client_ids))
+    }
+
+    /// Removes the groups that outlived their time to live without ever
+    /// filling up.
+    ///
+    /// Returns group clones without sensitive information and the connected
+    /// clients that were still part of them.
+    pub async fn remove_expired_groups(&self) -> Vec<(Group, Vec<ClientId>)> {
+        let group_ids: Vec<GroupId> = self
+            .groups
+            .iter()
+            .filter(|group| group.is_expired())
+            .map(|group| *group.key())
+            .collect();
+        group_ids
+            .iter()
+            .filter_map(|group_id| {
+                self.groups
+                    .remove_if(group_id, |_, group| group.is_expired())
+            })
+            .map(|(_, group)| {
+                let client_ids = self.release_group(&group);
+                (group.sanitized(), client_ids)
+            })
+            .collect()
+    }
+
+    /// Removes the sessions that outlived their time to live without
+    /// reaching the threshold.
+    ///
+    /// Returns group and session clones without sensitive information and
+    /// the client ids of the group members of every removed session.
+    pub async fn remove_expired_sessions(&self) -> Vec<(Group, Session, Vec<ClientId>)> {
+        let mut expired = Vec::new();
+        self.groups.iter_mut().for_each(|mut group| {
+            let session_ids: Vec<SessionId> = group
+                .sessions()
+                .filter(|session| {
+                    session.is_expired()
+                        && !session
+                            .threshold_reached(&group.params, self.count_ready_parties(session))
+                })
+                .map(|session| session.id)
+                .collect();
+            for session_id in session_ids {
+                let Some(session) = group.remove_session(&session_id) else {
+                    continue;
+                };
+                self.memory.release(session.approximate_size());
+                metrics::decrement_gauge!(ACTIVE_SESSIONS, 1.0);
+                let client_ids = group.clients().iter().copied().collect();
+                expired.push((group.sanitized(), session.sanitized(), client_ids));
+            }
+            if expired
+                .last()
+                .is_some_and(|(expired, _, _)| expired.id == group.id)
+            {
+                self.persist(&group);
+            }
+        });
+        expired
+    }
+
+    /// Returns group and session clones without sensitive information and
+    /// a boolean indicating if the session has reached the threshold.
+    ///
+    /// Only a member of the group is allowed to query it.
+    pub async fn get_session_readiness(
+        &self,
+        client_id: ClientId,
+        group_id: GroupId,
+        session_id: SessionId,
+    ) -> Result<(Group, Session, bool)> {
+        let group = self
+            .groups
             .get(&group_id)
             .ok_or(StateError::GroupNotFound(group_id))?;
-        group
+        if !group.has_client(&client_id) {
+            return Err(StateError::ClientNotInGroup(client_id, group_id).into());
+        }
+        let session = group
             .get_session(&session_id)
             .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        let parties = self.count_ready_parties(session);
+        let threshold = session.threshold_reached(&group.params, parties);
+        Ok((group.sanitized(), session.sanitized(), threshold))
+    }
 
-        // Login session
-        let mut groups = self.groups.write().await;
-        let group = groups.get_mut(&group_id).unwrap();
-        let session = group.get_session_mut(&session_id).unwrap();
-        session.login(client_id, party_number)?;
-        let session_c = session.clone();
-        let parties = session.party_signups.len();
-        let threshold = group.params.threshold_reached(session_c.kind, parties);
-        Ok((group.clone(), session_c, threshold))
+    /// Returns a session clone without sensitive information and its
+    /// lifecycle events after `since`, along with a boolean indicating if
+    /// some of them were already discarded.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the client is not a member of the group.
+    /// * Returns an error if event history is not enabled for the session.
+    pub async fn get_session_events_since(
+        &self,
+        client_id: ClientId,
+        group_id: GroupId,
+        session_id: SessionId,
+        since: u64,
+    ) -> Result<(Session, Vec<SessionHistoryEvent>, bool)> {
+        let group = self
+            .groups
+            .get(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        if !group.has_client(&client_id) {
+            return Err(StateError::ClientNotInGroup(client_id, group_id).into());
+        }
+        let session = group
+            .get_session(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        let (events, truncated) = session.events_since(since)?;
+        Ok((session.sanitized(), events, truncated))
+    }
+
+    /// Returns the sequence number of a new message relayed in a session.
+    pub async fn next_message_seq(
+        &self,
+        group_id: GroupId,
+        session_id: SessionId,
+    ) -> Result<MessageSeq> {
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        let session = group
+            .get_session_mut(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        Ok(session.next_seq())
+    }
+
+    /// Registers a message sent by a party that waits for delivery
+    /// acknowledgements of the given receivers, returning its id.
+    pub async fn add_pending_ack(
+        &self,
+        group_id: GroupId,
+        session_id: SessionId,
+        sender: ClientId,
+        receivers: Vec<ClientId>,
+    ) -> Result<MessageId> {
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        let session = group
+            .get_session_mut(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        self.memory.release(session.approximate_size());
+        let message_id = session.add_pending_ack(sender, receivers);
+        self.memory.reserve(session.approximate_size());
+        Ok(message_id)
+    }
+
+    /// Acknowledges the delivery of a message, returning the client id
+    /// of its sender.
+    pub async fn acknowledge_message(
+        &self,
+        group_id: GroupId,
+        session_id: SessionId,
+        message_id: MessageId,
+        client_id: ClientId,
+    ) -> Result<ClientId> {
+        let mut group = self
+            .groups
+            .get_mut(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        let session = group
+            .get_session_mut(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        self.memory.release(session.approximate_size());
+        let sender = session.acknowledge(message_id, &client_id);
+        self.memory.reserve(session.approximate_size());
+        sender
+    }
+
+    /// Returns clones without sensitive information of the sessions
+    /// belonging to a group.
+    pub async fn list_sessions(&self, group_id: GroupId) -> Result<Vec<Session>> {
+        let group = self
+            .groups
+            .get(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        Ok(group.sessions().map(Session::sanitized).collect())
+    }
+
+    /// Returns a clone without sensitive information of a session along with
+    /// the party numbers within `1..=n` that haven't been taken yet and its
+    /// progress towards the threshold.
+    pub async fn get_session_status(
+        &self,
+        group_id: GroupId,
+        session_id: SessionId,
+    ) -> Result<(Session, Vec<SessionPartyNumber>, SessionProgress)> {
+        let group = self
+            .groups
+            .get(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        let session = group
+            .get_session(&session_id)
+            .ok_or(StateError::SessionNotFound(session_id, group_id))?;
+        let missing = session.get_missing_party_numbers(group.params.n());
+        let parties = self.count_ready_parties(session);
+        let progress = SessionProgress {
+            parties,
+            required: session.required_parties(&group.params),
+            ready: session.threshold_reached(&group.params, parties),
+        };
+        Ok((session.sanitized(), missing, progress))
+    }
+
+    /// Checks that a client is a member of a group.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the group doesn't exist or the client never
+    ///   joined it.
+    pub async fn is_client_in_group(&self, group_id: &GroupId, client_id: &ClientId) -> Result<()> {
+        let group = self
+            .groups
+            .get(group_id)
+            .ok_or(StateError::GroupNotFound(*group_id))?;
+        if !group.has_client(client_id) {
+            return Err(StateError::ClientNotInGroup(*client_id, *group_id).into());
+        }
+        Ok(())
+    }
+
+    /// Returns the client id of the group member a public id belongs to.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the group doesn't exist or none of its members
+    ///   has the public id.
+    pub async fn resolve_client_id(
+        &self,
+        group_id: GroupId,
+        public_id: PublicClientId,
+    ) -> Result<ClientId> {
+        let group = self
+            .groups
+            .get(&group_id)
+            .ok_or(StateError::GroupNotFound(group_id))?;
+        let client_id = group
+            .find_client(&public_id)
+            .ok_or(StateError::MemberNotFound(public_id, group_id))?;
+        Ok(client_id)
     }
 
     /// Returns client ids associated with a given group, if it exists.
     pub async fn get_client_ids_from_group(&self, group_id: &GroupId) -> Result<Vec<ClientId>> {
-        let groups = self.groups.read().await;
-        let group = groups
+        let group = self
+            .groups
             .get(group_id)
             .ok_or(StateError::GroupNotFound(*group_id))?;
         let client_ids: Vec<ClientId> = group.clients().iter().copied().collect();
@@ -228,8 +1517,8 @@ impl State {
         group_id: &GroupId,
         session_id: &SessionId,
     ) -> Result<Vec<ClientId>> {
-        let groups = self.groups.read().await;
-        let group = groups
+        let group = self
+            .groups
             .get(group_id)
             .ok_or(StateError::GroupNotFound(*group_id))?;
         let session = group
@@ -247,8 +1536,8 @@ impl State {
         party_number: SessionPartyNumber,
     ) -> Result<ClientId> {
         // Validate group, session and party number exist.
-        let groups = self.groups.read().await;
-        let group = groups
+        let group = self
+            .groups
             .get(&group_id)
             .ok_or(StateError::GroupNotFound(group_id))?;
         let session = group
@@ -269,8 +1558,8 @@ impl State {
         session_id: SessionId,
         client_id: ClientId,
     ) -> Result<SessionPartyNumber> {
-        let groups = self.groups.read().await;
-        let group = groups
+        let group = self
+            .groups
             .get(&group_id)
             .ok_or(StateError::GroupNotFound(group_id))?;
         let session = group
@@ -282,14 +1571,81 @@ impl State {
         Ok(party_number)
     }
 
+    /// Releases the slot, memory and metrics accounted for a removed group.
+    ///
+    /// Returns the connected clients still part of the group, either as
+    /// members or as parties of any of its sessions.
+    fn release_group(&self, group: &Group) -> Vec<ClientId> {
+        if let Some(join_code) = &group.join_code {
+            self.join_codes.remove(join_code);
+        }
+        self.unpersist(&group.id);
+        let mut client_ids: HashSet<ClientId> = group.clients().clone();
+        let mut sessions = 0;
+        group.sessions().for_each(|session| {
+            self.memory.release(session.approximate_size());
+            client_ids.extend(session.get_all_client_ids());
+            sessions += 1;
+        });
+        metrics::decrement_gauge!(ACTIVE_SESSIONS, sessions as f64);
+        metrics::decrement_gauge!(ACTIVE_GROUPS, 1.0);
+        self.group_count.fetch_sub(1, Ordering::AcqRel);
+        client_ids
+            .into_iter()
+            .filter(|client_id| self.clients.contains_key(client_id))
+            .collect()
+    }
+
+    /// Saves the metadata of a group to the store, errors are logged
+    /// since the store is best effort.
+    fn persist(&self, group: &Group) {
+        if self.store_frozen.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Err(error) = self.store.save_group(&GroupRecord::from(group)) {
+            tracing::warn!(error = ?error, group_id = group.id.to_string(), "Error while saving group to store");
+        }
+    }
+
+    /// Removes the metadata of a group from the store, errors are logged
+    /// since the store is best effort.
+    fn unpersist(&self, group_id: &GroupId) {
+        if self.store_frozen.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Err(error) = self.store.remove_group(group_id) {
+            tracing::warn!(error = ?error, group_id = group_id.to_string(), "Error while removing group from store");
+        }
+    }
+
+    /// Returns the number of parties counted towards the session threshold,
+    /// according to the session readiness mode.
+    fn count_ready_parties(&self, session: &Session) -> usize {
+        match session.options.readiness {
+            SessionReadiness::SignedUp => session.get_number_of_clients(),
+            SessionReadiness::Connected => session
+                .get_all_client_ids()
+                .iter()
+                .filter(|client_id| self.clients.contains_key(client_id))
+                .count(),
+        }
+    }
+
+    /// Inserts an already populated group, bypassing any validation.
+    #[cfg(test)]
+    pub(crate) fn insert_group(&self, group: Group) {
+        self.group_count.fetch_add(1, Ordering::AcqRel);
+        self.groups.insert(group.id, group);
+    }
+
     /// Helper function that validates if group and session are valid.
     pub async fn validate_group_and_session(
         &self,
         group_id: GroupId,
         session_id: SessionId,
     ) -> Result<()> {
-        let groups = self.groups.read().await;
-        let group = groups
+        let group = self
+            .groups
             .get(&group_id)
             .ok_or(StateError::GroupNotFound(group_id))?;
         group
@@ -298,3 +1654,923 @@ impl State {
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+
+    /// Adds a group of `n` parties joined by `members` new clients, with a
+    /// keygen session every member signed up to.
+    async fn group(state: &State, n: u16, members: usize) -> (GroupId, SessionId, Vec<ClientId>) {
+        let mut group = Group::new(
+            Uuid::new_v4(),
+            Parameters::new(n, 1).unwrap(),
+            None,
+            None,
+            ClientId::new_v4(),
+        );
+        let session_id = group
+            .add_session(SessionKind::Keygen, None, SessionOptions::default())
+            .unwrap()
+            .id;
+        let client_ids: Vec<_> = (0..members).map(|_| state.new_client_id()).collect();
+        for client_id in &client_ids {
+            group.add_client(*client_id).unwrap();
+            let session = group.get_session_mut(&session_id).unwrap();
+            session.signup(*client_id, n).unwrap();
+        }
+        let group_id = group.id();
+        state.insert_group(group);
+        (group_id, session_id, client_ids)
+    }
+
+    #[test]
+    fn client_ids_only_leave_the_server_as_their_public_id() {
+        let client_id = ClientId::new_v4();
+        let public_id = client_id.public_id();
+        assert_ne!(public_id.to_string(), client_id.as_uuid().to_string());
+        assert_eq!(public_id, client_id.public_id());
+        assert_eq!(client_id.to_string(), public_id.to_string());
+        assert_eq!(
+            serde_json::to_value(client_id).unwrap(),
+            serde_json::to_value(public_id).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn public_ids_are_resolved_among_the_members() {
+        let state = State::new();
+        let (group_id, _, client_ids) = group(&state, 3, 2).await;
+
+        let resolved = state
+            .resolve_client_id(group_id, client_ids[1].public_id())
+            .await
+            .unwrap();
+        assert_eq!(resolved, client_ids[1]);
+        let outsider = state.new_client_id().public_id();
+        let err = state
+            .resolve_client_id(group_id, outsider)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateError>(),
+            Some(StateError::MemberNotFound(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn leaving_a_group_frees_the_party_numbers() {
+        let state = State::new();
+        let (group_id, session_id, client_ids) = group(&state, 3, 2).await;
+
+        let (_, remaining, _, closed) = state.leave_group(group_id, client_ids[0]).await.unwrap();
+        assert_eq!(remaining, 1);
+        assert!(closed.is_none());
+        let group = state.groups.get(&group_id).unwrap();
+        assert!(!group.has_client(&client_ids[0]));
+        let session = group.get_session(&session_id).unwrap();
+        assert_eq!(session.get_party_number(&client_ids[0]), None);
+        assert_eq!(session.occupied_party_numbers, [2]);
+    }
+
+    #[tokio::test]
+    async fn groups_are_removed_once_the_last_member_leaves() {
+        let state = State::new();
+        let (group_id, _, client_ids) = group(&state, 3, 1).await;
+
+        let (_, remaining, _, closed) = state.leave_group(group_id, client_ids[0]).await.unwrap();
+        assert_eq!(remaining, 0);
+        assert!(closed.is_some());
+        assert!(state.get_client_ids_from_group(&group_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn only_vacant_party_numbers_can_be_claimed() {
+        let state = State::new();
+        let (group_id, session_id, client_ids) = group(&state, 3, 2).await;
+        let claimer = state.new_client_id();
+        state
+            .groups
+            .get_mut(&group_id)
+            .unwrap()
+            .add_client(claimer)
+            .unwrap();
+
+        // The holder of party number 1 is still a member
+        let err = state
+            .claim_vacant_party(claimer, group_id, session_id, 1)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(SessionError::PartyNumberNotVacant(1))
+        ));
+        state
+            .groups
+            .get_mut(&group_id)
+            .unwrap()
+            .drop_client(client_ids[0]);
+        let (_, session, threshold) = state
+            .claim_vacant_party(claimer, group_id, session_id, 1)
+            .await
+            .unwrap();
+        assert!(!threshold);
+        assert_eq!(session.occupied_party_numbers, [1, 2]);
+        let group = state.groups.get(&group_id).unwrap();
+        let session = group.get_session(&session_id).unwrap();
+        assert_eq!(session.get_party_number(&claimer), Some(1));
+        assert_eq!(session.get_party_number(&client_ids[0]), None);
+    }
+
+    #[tokio::test]
+    async fn non_members_cant_leave_a_group() {
+        let state = State::new();
+        let (group_id, _, _) = group(&state, 3, 1).await;
+
+        let err = state
+            .leave_group(group_id, state.new_client_id())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::ClientNotInGroup(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn duplicate_joins_are_rejected() {
+        let state = State::new();
+        let (group_id, _, client_ids) = group(&state, 3, 1).await;
+
+        let err = state.join_group(group_id, client_ids[0]).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::AlreadyMember(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn joins_beyond_n_are_rejected() {
+        let state = State::new();
+        let (group_id, _, _) = group(&state, 2, 2).await;
+
+        let err = state
+            .join_group(group_id, state.new_client_id())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::GroupIsFull(_))
+        ));
+        let err = state
+            .join_group(Uuid::new_v4(), state.new_client_id())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::GroupNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn client_ids_in_use_are_rejected() {
+        let state = State::new();
+        let client_id = state.new_client_id();
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        state
+            .add_client(client_id, tx.clone(), Codec::Json, CancellationToken::new())
+            .await
+            .unwrap();
+
+        let err = state
+            .add_client(client_id, tx, Codec::Json, CancellationToken::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::ClientAlreadyExists(_))
+        ));
+        assert!(state.get_client(&client_id).await.is_some());
+    }
+
+    /// Returns the ready parties of a session signed up by three clients,
+    /// of which only the first two are connected.
+    async fn ready_parties_with_a_disconnection(readiness: SessionReadiness) -> usize {
+        let state = State::new();
+        let options = SessionOptions {
+            readiness,
+            ..Default::default()
+        };
+        let mut session = Session::new(Uuid::new_v4(), SessionKind::Keygen, None, options);
+        for i in 0..3 {
+            let client_id = state.new_client_id();
+            session.signup(client_id, 3).unwrap();
+            if i < 2 {
+                let (tx, _) = tokio::sync::mpsc::channel(16);
+                state
+                    .add_client(client_id, tx, Codec::Json, CancellationToken::new())
+                    .await
+                    .unwrap();
+            }
+        }
+        state.count_ready_parties(&session)
+    }
+
+    #[tokio::test]
+    async fn signed_up_readiness_counts_disconnected_parties() {
+        assert_eq!(
+            ready_parties_with_a_disconnection(SessionReadiness::SignedUp).await,
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn connected_readiness_ignores_disconnected_parties() {
+        assert_eq!(
+            ready_parties_with_a_disconnection(SessionReadiness::Connected).await,
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn parties_can_close_sessions() {
+        let state = State::new();
+        let (group_id, session_id, client_ids) = group(&state, 3, 3).await;
+        state
+            .groups
+            .get_mut(&group_id)
+            .unwrap()
+            .get_session_mut(&session_id)
+            .unwrap()
+            .mark_ready();
+
+        let (_, _, others) = state
+            .close_session(client_ids[0], group_id, session_id)
+            .await
+            .unwrap();
+        assert_eq!(others.len(), 2);
+        assert!(!others.contains(&client_ids[0]));
+        assert!(state
+            .get_client_ids_from_session(&group_id, &session_id)
+            .await
+            .is_err());
+        let stats = state.get_group_stats(group_id).await.unwrap();
+        assert_eq!(stats.sessions_completed, 1);
+    }
+
+    #[tokio::test]
+    async fn non_parties_cant_close_sessions() {
+        let state = State::new();
+        let (group_id, session_id, _) = group(&state, 3, 1).await;
+
+        let err = state
+            .close_session(state.new_client_id(), group_id, session_id)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::ClientNotInSession(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn session_status_reports_missing_party_numbers() {
+        let state = State::new();
+        let (group_id, session_id, client_ids) = group(&state, 4, 3).await;
+        state.leave_group(group_id, client_ids[1]).await.unwrap();
+
+        let (_, missing, progress) = state
+            .get_session_status(group_id, session_id)
+            .await
+            .unwrap();
+        assert_eq!(missing, [2, 4]);
+        assert_eq!(progress.parties, 2);
+        assert_eq!(progress.required, 4);
+        assert!(!progress.ready);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_joins_never_exceed_n() {
+        let state = std::sync::Arc::new(State::new());
+        let (group_id, _, _) = group(&state, 5, 1).await;
+
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let client_id = state.new_client_id();
+                    state.join_group(group_id, client_id).await.is_ok()
+                })
+            })
+            .collect();
+        let mut joined = 0;
+        for task in tasks {
+            joined += usize::from(task.await.unwrap());
+        }
+
+        assert_eq!(joined, 4);
+        let (_, client_count, is_full) = state.get_group_info(group_id).await.unwrap();
+        assert_eq!(client_count, 5);
+        assert!(is_full);
+    }
+
+    /// Adds a group of `n` parties joined by `members` new clients, with a
+    /// keygen session nobody signed up to yet.
+    async fn joined_group(
+        state: &State,
+        n: u16,
+        members: usize,
+    ) -> (GroupId, SessionId, Vec<ClientId>) {
+        let group = state
+            .add_group(
+                ClientId::new_v4(),
+                Parameters::new(n, 1).unwrap(),
+                None,
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+        let mut client_ids = Vec::new();
+        for _ in 0..members {
+            let client_id = state.new_client_id();
+            state.join_group(group.id, client_id).await.unwrap();
+            client_ids.push(client_id);
+        }
+        let (_, session) = state
+            .add_session(
+                group.id,
+                SessionKind::Keygen,
+                None,
+                SessionOptions::default(),
+            )
+            .await
+            .unwrap();
+        (group.id, session.id, client_ids)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_signups_get_distinct_party_numbers() {
+        let state = std::sync::Arc::new(State::new());
+        let (group_id, session_id, client_ids) = joined_group(&state, 8, 7).await;
+
+        let tasks: Vec<_> = client_ids
+            .into_iter()
+            .map(|client_id| {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let (_, _, signup, _) = state
+                        .signup_session(client_id, group_id, session_id)
+                        .await
+                        .unwrap();
+                    match signup {
+                        SessionSignup::Party(party_number) => party_number,
+                        SessionSignup::Standby(_) => panic!("unexpected standby"),
+                    }
+                })
+            })
+            .collect();
+        let mut party_numbers = std::collections::BTreeSet::new();
+        for task in tasks {
+            party_numbers.insert(task.await.unwrap());
+        }
+        assert_eq!(party_numbers, (1..=7).collect());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn signups_racing_the_group_removal_fail_without_panicking() {
+        let state = std::sync::Arc::new(State::new());
+        let (group_id, session_id, client_ids) = joined_group(&state, 8, 7).await;
+
+        let tasks: Vec<_> = client_ids
+            .iter()
+            .map(|client_id| {
+                let (state, client_id) = (state.clone(), *client_id);
+                tokio::spawn(async move {
+                    let _ = state.signup_session(client_id, group_id, session_id).await;
+                    state.leave_group(group_id, client_id).await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        let err = state
+            .signup_session(client_ids[0], group_id, session_id)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::GroupNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn session_readiness_is_only_reported_to_members() {
+        let state = State::new();
+        let (group_id, session_id, client_ids) = group(&state, 3, 2).await;
+
+        let (_, _, ready) = state
+            .get_session_readiness(client_ids[0], group_id, session_id)
+            .await
+            .unwrap();
+        assert!(!ready);
+        let err = state
+            .get_session_readiness(state.new_client_id(), group_id, session_id)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::ClientNotInGroup(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn exactly_n_parties_sign_up() {
+        let state = State::new();
+        let (group_id, session_id, mut client_ids) = joined_group(&state, 3, 3).await;
+        // A fourth client signs up once the session holds n parties
+        client_ids.push(state.new_client_id());
+
+        let mut signups = Vec::new();
+        for client_id in client_ids {
+            let signup = state.signup_session(client_id, group_id, session_id).await;
+            signups.push(match signup {
+                Ok((_, _, SessionSignup::Party(party_number), _)) => Some(party_number),
+                Ok((_, _, SessionSignup::Standby(_), _)) => panic!("unexpected standby"),
+                Err(err) => {
+                    assert!(matches!(
+                        err.downcast_ref(),
+                        Some(StateError::SessionFull(_))
+                    ));
+                    None
+                }
+            });
+        }
+        assert_eq!(signups, [Some(1), Some(2), Some(3), None]);
+    }
+
+    #[tokio::test]
+    async fn logins_outside_1_to_n_are_rejected() {
+        let state = State::new();
+        let (group_id, session_id, client_ids) = joined_group(&state, 3, 1).await;
+
+        for party_number in [0, 4] {
+            let err = state
+                .login_session(client_ids[0], group_id, session_id, party_number)
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                err.downcast_ref(),
+                Some(SessionError::PartyNumberOutOfRange(_, 3))
+            ));
+        }
+        state
+            .login_session(client_ids[0], group_id, session_id, 3)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn fixed_signing_sets_hold_t_plus_one_parties() {
+        let state = State::new();
+        let (group_id, _, client_ids) = joined_group(&state, 3, 3).await;
+        let options = SessionOptions {
+            fixed_signers: true,
+            ..Default::default()
+        };
+        let (_, session) = state
+            .add_session(group_id, SessionKind::Sign, None, options)
+            .await
+            .unwrap();
+
+        for client_id in &client_ids[..2] {
+            state
+                .signup_session(*client_id, group_id, session.id)
+                .await
+                .unwrap();
+        }
+        let err = state
+            .signup_session(client_ids[2], group_id, session.id)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::SessionFull(_))
+        ));
+        let err = state
+            .login_session(client_ids[2], group_id, session.id, 3)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::SessionFull(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reservations_are_reclaimed_with_matching_memberships() {
+        let state = State::new();
+        let (group_id, session_id, client_ids) = group(&state, 3, 1).await;
+        let client_id = client_ids[0];
+        let memberships = state.memberships(client_id);
+        assert_eq!(memberships[&group_id], BTreeSet::from([session_id]));
+
+        state
+            .reserve_client(client_id, Duration::from_secs(30))
+            .await;
+        assert!(!state.reclaim_client(client_id, |_| false).await);
+        assert!(
+            state
+                .reclaim_client(client_id, |reserved| *reserved == memberships)
+                .await
+        );
+        assert!(!state.reclaim_client(client_id, |_| true).await);
+    }
+
+    #[tokio::test]
+    async fn expired_reservations_drop_the_client() {
+        let state = State::new();
+        let (group_id, _, client_ids) = group(&state, 3, 2).await;
+
+        state.reserve_client(client_ids[0], Duration::ZERO).await;
+        assert!(!state.reclaim_client(client_ids[0], |_| true).await);
+        state.expire_reservation(client_ids[0]).await;
+        let group = state.groups.get(&group_id).unwrap();
+        assert!(!group.has_client(&client_ids[0]));
+        assert!(group.has_client(&client_ids[1]));
+    }
+
+    /// Adds a group of 2 parties with a keygen session expiring right away,
+    /// signed up by `signups` of its 2 members.
+    fn expiring_session(state: &State, signups: usize) -> (GroupId, SessionId) {
+        let mut group = Group::new(
+            Uuid::new_v4(),
+            Parameters::new(2, 1).unwrap(),
+            None,
+            None,
+            ClientId::new_v4(),
+        );
+        let options = SessionOptions {
+            ttl_secs: Some(0),
+            ..Default::default()
+        };
+        let session_id = group
+            .add_session(SessionKind::Keygen, None, options)
+            .unwrap()
+            .id;
+        for i in 0..2 {
+            let client_id = state.new_client_id();
+            group.add_client(client_id).unwrap();
+            if i < signups {
+                let session = group.get_session_mut(&session_id).unwrap();
+                session.signup(client_id, 2).unwrap();
+            }
+        }
+        let group_id = group.id();
+        state.insert_group(group);
+        (group_id, session_id)
+    }
+
+    #[tokio::test]
+    async fn sessions_below_the_threshold_expire() {
+        let state = State::new();
+        let (group_id, session_id) = expiring_session(&state, 1);
+
+        let expired = state.remove_expired_sessions().await;
+        assert_eq!(expired.len(), 1);
+        let (group, session, client_ids) = &expired[0];
+        assert_eq!(group.id, group_id);
+        assert_eq!(session.id, session_id);
+        assert_eq!(client_ids.len(), 2);
+        assert!(state
+            .groups
+            .get(&group_id)
+            .unwrap()
+            .get_session(&session_id)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn ready_sessions_never_expire() {
+        let state = State::new();
+        let (group_id, session_id) = expiring_session(&state, 2);
+
+        assert!(state.remove_expired_sessions().await.is_empty());
+        assert!(state
+            .groups
+            .get(&group_id)
+            .unwrap()
+            .get_session(&session_id)
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn expired_groups_are_removed_with_their_members() {
+        let state = State::new();
+        let params = Parameters::new(3, 1).unwrap();
+        let expiring = state
+            .add_group(ClientId::new_v4(), params.clone(), None, Some(0), false)
+            .await
+            .unwrap();
+        let lasting = state
+            .add_group(ClientId::new_v4(), params, None, None, false)
+            .await
+            .unwrap();
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let client_id = state.new_client_id();
+        state
+            .add_client(client_id, tx, Codec::Json, CancellationToken::new())
+            .await
+            .unwrap();
+        let mut group = state.groups.get_mut(&expiring.id).unwrap();
+        group.add_client(client_id).unwrap();
+        // Disconnected members are not returned
+        group.add_client(state.new_client_id()).unwrap();
+        drop(group);
+
+        let expired = state.remove_expired_groups().await;
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].0.id, expiring.id);
+        assert_eq!(expired[0].1, [client_id]);
+        assert!(!state.groups.contains_key(&expiring.id));
+        assert!(state.groups.contains_key(&lasting.id));
+    }
+
+    #[tokio::test]
+    async fn join_codes_are_resolved_until_the_group_is_removed() {
+        let state = State::new();
+        let params = Parameters::new(2, 1).unwrap();
+        let group = state
+            .add_group(ClientId::new_v4(), params.clone(), None, None, true)
+            .await
+            .unwrap();
+        let join_code = group.join_code.unwrap();
+        assert_eq!(join_code.len(), JOIN_CODE_LENGTH);
+        assert!(state
+            .add_group(ClientId::new_v4(), params, None, None, false)
+            .await
+            .unwrap()
+            .join_code
+            .is_none());
+
+        let lowercase = format!(" {} ", join_code.to_lowercase());
+        let group_id = state.get_group_id_from_join_code(&lowercase).await.unwrap();
+        assert_eq!(group_id, group.id);
+
+        let client_id = state.new_client_id();
+        state.join_group(group.id, client_id).await.unwrap();
+        state.leave_group(group.id, client_id).await.unwrap();
+        let err = state
+            .get_group_id_from_join_code(&join_code)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::JoinCodeNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn groups_beyond_the_maximum_are_rejected() {
+        let state = State::new().with_max_groups(Some(1));
+        let params = Parameters::new(2, 1).unwrap();
+        let group = state
+            .add_group(ClientId::new_v4(), params.clone(), None, None, false)
+            .await
+            .unwrap();
+
+        let err = state
+            .add_group(ClientId::new_v4(), params.clone(), None, None, false)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::TooManyGroups(1))
+        ));
+        let client_id = state.new_client_id();
+        state.join_group(group.id, client_id).await.unwrap();
+        state.leave_group(group.id, client_id).await.unwrap();
+        assert!(state
+            .add_group(ClientId::new_v4(), params, None, None, false)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_creations_never_exceed_the_maximum_groups() {
+        let state = std::sync::Arc::new(State::new().with_max_groups(Some(5)));
+
+        let tasks: Vec<_> = (0..64)
+            .map(|_| {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let params = Parameters::new(2, 1).unwrap();
+                    state
+                        .add_group(ClientId::new_v4(), params, None, None, false)
+                        .await
+                })
+            })
+            .collect();
+        let mut created = Vec::new();
+        for task in tasks {
+            if let Ok(group) = task.await.unwrap() {
+                created.push(group.id);
+            }
+        }
+        assert_eq!(created.len(), 5);
+        assert_eq!(state.groups.len(), 5);
+
+        // Removing a group frees its slot
+        let client_id = state.new_client_id();
+        state.join_group(created[0], client_id).await.unwrap();
+        state.leave_group(created[0], client_id).await.unwrap();
+        let params = Parameters::new(2, 1).unwrap();
+        assert!(state
+            .add_group(ClientId::new_v4(), params.clone(), None, None, false)
+            .await
+            .is_ok());
+        assert!(state
+            .add_group(ClientId::new_v4(), params, None, None, false)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn sessions_beyond_the_maximum_per_group_are_rejected() {
+        let state = State::new().with_max_sessions_per_group(Some(2));
+        let (group_id, session_id, _) = group(&state, 2, 2).await;
+        let options = SessionOptions::default();
+        state
+            .add_session(group_id, SessionKind::Sign, None, options.clone())
+            .await
+            .unwrap();
+
+        let err = state
+            .add_session(group_id, SessionKind::Sign, None, options.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::TooManySessions(id, 2)) if *id == group_id
+        ));
+        state
+            .groups
+            .get_mut(&group_id)
+            .unwrap()
+            .remove_session(&session_id);
+        assert!(state
+            .add_session(group_id, SessionKind::Sign, None, options)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn racing_closes_report_the_missing_session() {
+        let state = std::sync::Arc::new(State::new());
+        let (group_id, session_id, client_ids) = group(&state, 2, 2).await;
+
+        let tasks: Vec<_> = client_ids
+            .into_iter()
+            .map(|client_id| {
+                let state = state.clone();
+                tokio::spawn(
+                    async move { state.close_session(client_id, group_id, session_id).await },
+                )
+            })
+            .collect();
+        let mut results = Vec::new();
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        let err = results.into_iter().find_map(Result::err).unwrap();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(StateError::SessionNotFound(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn restored_groups_expire_unless_reclaimed() {
+        let store = Arc::new(MemoryStateStore::default());
+        let records: Vec<GroupRecord> = [2, 2, 0]
+            .into_iter()
+            .map(|clients| GroupRecord {
+                clients,
+                ..GroupRecord::from(&Group::new(
+                    Uuid::new_v4(),
+                    Parameters::new(3, 1).unwrap(),
+                    None,
+                    None,
+                    ClientId::new_v4(),
+                ))
+            })
+            .collect();
+        for record in &records {
+            store.save_group(record).unwrap();
+        }
+        let (abandoned, reclaimed) = (records[0].id, records[1].id);
+
+        let state = State::new().with_store(store.clone());
+        // Groups saved without members are discarded
+        assert_eq!(state.restore(Duration::ZERO).await.unwrap(), 2);
+        state
+            .join_group(reclaimed, state.new_client_id())
+            .await
+            .unwrap();
+
+        let expired = state.remove_expired_groups().await;
+        let expired: Vec<_> = expired.iter().map(|(group, _)| group.id).collect();
+        assert_eq!(expired, [abandoned]);
+        assert!(state.groups.contains_key(&reclaimed));
+        let saved: Vec<_> = store
+            .load_groups()
+            .unwrap()
+            .iter()
+            .map(|record| record.id)
+            .collect();
+        assert_eq!(saved, [reclaimed]);
+    }
+
+    #[test]
+    fn connection_slots_are_released_on_drop() {
+        let state = State::new().with_max_connections(Some(2));
+        let first = state.acquire_connection().unwrap();
+        let _second = state.acquire_connection().unwrap();
+
+        assert!(state.acquire_connection().is_none());
+        drop(first);
+        assert!(state.acquire_connection().is_some());
+    }
+
+    #[tokio::test]
+    async fn snapshots_count_members_and_parties() {
+        let state = State::new();
+        let (group_id, session_id, _) = group(&state, 2, 2).await;
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        state
+            .add_client(
+                state.new_client_id(),
+                tx,
+                Codec::Json,
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+        let snapshot = state.snapshot().await;
+        assert_eq!(snapshot.clients, 1);
+        assert_eq!(snapshot.groups.len(), 1);
+        assert_eq!(snapshot.groups[0].id, group_id);
+        assert_eq!(snapshot.groups[0].members, 2);
+        let session = &snapshot.groups[0].sessions[0];
+        assert_eq!(session.id, session_id);
+        assert_eq!(session.parties, 2);
+        assert!(session.ready);
+    }
+
+    #[tokio::test]
+    async fn pending_acks_are_charged_to_the_memory_budget() {
+        let state = State::new();
+        let (group_id, session_id, client_ids) = joined_group(&state, 3, 3).await;
+        let used = state.memory().used();
+
+        let message_id = state
+            .add_pending_ack(
+                group_id,
+                session_id,
+                client_ids[0],
+                client_ids[1..].to_vec(),
+            )
+            .await
+            .unwrap();
+        assert!(state.memory().used() > used);
+        for client_id in &client_ids[1..] {
+            state
+                .acknowledge_message(group_id, session_id, message_id, *client_id)
+                .await
+                .unwrap();
+        }
+        assert_eq!(state.memory().used(), used);
+
+        // Acks are released along with their sender
+        state
+            .add_pending_ack(
+                group_id,
+                session_id,
+                client_ids[0],
+                client_ids[1..].to_vec(),
+            )
+            .await
+            .unwrap();
+        state.drop_client(client_ids[0]).await;
+        assert_eq!(state.memory().used(), used);
+        state
+            .add_pending_ack(group_id, session_id, client_ids[1], vec![client_ids[2]])
+            .await
+            .unwrap();
+        state.leave_group(group_id, client_ids[1]).await.unwrap();
+        assert_eq!(state.memory().used(), used);
+    }
+}
@@ -4,80 +4,289 @@
 
 use super::{
     parameters::Parameters,
-    session::{Session, SessionId},
+    session::{Session, SessionId, SessionKind, SessionPartyNumber},
     ClientId,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 use thiserror::Error;
 use uuid::Uuid;
 
 #[cfg(feature = "server")]
-use super::session::{SessionKind, SessionValue};
+#[cfg(feature = "server")]
+use std::time::Duration;
+
+#[cfg(feature = "server")]
+use super::{
+    session::{SessionLifecycle, SessionOptions, SessionValue},
+    PublicClientId,
+};
 
 /// Unique ID of a group.
 pub type GroupId = Uuid;
 
+/// Short code that can be shared instead of the group id to join a group.
+pub type JoinCode = String;
+
 /// Error type for group operations.
 #[derive(Debug, Error)]
 pub enum GroupError {
     /// Error generated when the group is full.
     #[error("group is full")]
     GroupFull,
+    /// Error generated when the client already joined the group.
+    #[error("client `{0}` is already a member of the group")]
+    AlreadyMember(ClientId),
+    /// Error generated when a session kind doesn't match the single kind
+    /// declared by the group.
+    #[error("group only accepts `{0}` sessions, got `{1}`")]
+    SessionKindMismatch(SessionKind, SessionKind),
+    /// Error generated when a fixed signing set is requested for a session
+    /// that is not a sign session.
+    #[error("fixed signers are only supported by sign sessions, got `{0}`")]
+    FixedSignersNotSupported(SessionKind),
+    /// Error generated when a reserved party number is out of range.
+    #[error("reserved party number `{0}` is out of range, expected a value between 1 and `{1}`")]
+    ReservedPartyOutOfRange(SessionPartyNumber, u16),
+    /// Error generated when a required number of parties is set for a
+    /// session that is not a custom one.
+    #[error("required parties are only supported by custom sessions, got `{0}`")]
+    RequiredPartiesNotSupported(SessionKind),
+    /// Error generated when the required number of parties is out of range.
+    #[error("required parties `{0}` is out of range, expected a value between 1 and `{1}`")]
+    RequiredPartiesOutOfRange(usize, u16),
+}
+
+/// Cumulative, non-sensitive counters tracked across the life of a group.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct GroupStats {
+    /// Total number of clients that ever joined the group.
+    pub joined: u64,
+    /// Total number of sessions created in the group.
+    #[serde(rename = "sessionsCreated")]
+    pub sessions_created: u64,
+    /// Total number of sessions closed after completing.
+    #[serde(rename = "sessionsCompleted")]
+    pub sessions_completed: u64,
+    /// Total number of sessions aborted before completing.
+    #[serde(rename = "sessionsAborted")]
+    pub sessions_aborted: u64,
 }
 
 /// Group is a collection of clients. It is the main unit of communication.
-#[derive(Debug, Deserialize, Serialize)]
+///
+/// Members and sessions are only read by the server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
 pub struct Group {
     /// Unique ID of the group.
     pub id: GroupId,
     /// Parameters of the group.
     pub params: Parameters,
+    /// Single session kind accepted by the group, any kind is accepted if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SessionKind>,
+    /// Seconds after creation the group is removed if it never filled up,
+    /// groups never expire if unset.
+    #[serde(rename = "ttlSecs", default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    /// Code accepted to join the group instead of its id, only set if
+    /// requested when creating the group.
+    #[serde(rename = "joinCode", default, skip_serializing_if = "Option::is_none")]
+    pub join_code: Option<JoinCode>,
+    /// Client that created the group, allowed to perform privileged
+    /// operations on it. Nil for groups restored from a store.
+    #[serde(skip)]
+    pub(crate) creator: ClientId,
     /// Sessions belonging to this group.
     #[serde(skip)]
     pub(crate) sessions: HashMap<SessionId, Session>,
     /// Clients that joined this group.
     #[serde(skip)]
     pub(crate) clients: HashSet<ClientId>,
+    /// Historical participation counters.
+    #[serde(skip)]
+    pub(crate) stats: GroupStats,
+    /// Whether the group ever reached `n` members.
+    #[serde(skip)]
+    pub(crate) filled: bool,
+    /// Moment the group was created.
+    #[serde(skip, default = "Instant::now")]
+    pub(crate) created_at: Instant,
+    /// Moment a group restored from a store is removed if it still has
+    /// no members.
+    #[serde(skip)]
+    pub(crate) reclaim_deadline: Option<Instant>,
 }
 
 impl Group {
     /// Creates a new group with the given parameters.
-    pub fn new(id: GroupId, params: Parameters) -> Self {
+    ///
+    /// If `kind` is set the group is single-purpose and only accepts
+    /// sessions of that kind.
+    pub fn new(
+        id: GroupId,
+        params: Parameters,
+        kind: Option<SessionKind>,
+        ttl_secs: Option<u64>,
+        creator: ClientId,
+    ) -> Self {
         Self {
             id,
             params,
+            kind,
+            ttl_secs,
+            join_code: None,
+            creator,
             sessions: HashMap::new(),
             clients: HashSet::new(),
+            stats: GroupStats::default(),
+            filled: false,
+            created_at: Instant::now(),
+            reclaim_deadline: None,
         }
     }
 
     /// Adds a client to the group.
     #[cfg(feature = "server")]
     pub fn add_client(&mut self, client_id: ClientId) -> anyhow::Result<()> {
+        if self.has_client(&client_id) {
+            return Err(GroupError::AlreadyMember(client_id).into());
+        }
         let clients = self.clients.len();
         if clients >= self.params.n().into() {
             return Err(GroupError::GroupFull.into());
         }
         self.clients.insert(client_id);
+        self.stats.joined += 1;
+        if self.is_full() {
+            self.filled = true;
+        }
         Ok(())
     }
 
-    /// Removes a client from the group.
+    /// Removes a disconnected client from the group.
+    ///
+    /// Party numbers it holds in sessions that didn't reach their threshold
+    /// go to the first standby in the queue. Otherwise they are kept so they
+    /// become vacant and can be claimed by another member of the group.
+    ///
+    /// Returns the promoted standbys with their session and party number.
+    #[cfg(feature = "server")]
+    pub fn drop_client(
+        &mut self,
+        client_id: ClientId,
+    ) -> Vec<(SessionId, ClientId, SessionPartyNumber)> {
+        self.clients.remove(&client_id);
+        self.sessions
+            .iter_mut()
+            .filter_map(|(session_id, session)| {
+                session.remove_standby(&client_id);
+                session.drop_pending_acks(&client_id);
+                if session.reached_threshold || session.standbys.is_empty() {
+                    return None;
+                }
+                let party_number = session.remove_client(&client_id)?;
+                let promoted = session.promote_standby(party_number)?;
+                Some((*session_id, promoted, party_number))
+            })
+            .collect()
+    }
+
+    /// Removes a client from the group and from every session
+    /// it is part of, promoting standbys to the freed party numbers.
+    ///
+    /// Returns the promoted standbys with their session and party number.
     #[cfg(feature = "server")]
-    pub fn drop_client(&mut self, client_id: ClientId) {
+    pub fn remove_client(
+        &mut self,
+        client_id: ClientId,
+    ) -> Vec<(SessionId, ClientId, SessionPartyNumber)> {
         self.clients.remove(&client_id);
-        // FIXME: delete from sessions too
+        self.sessions
+            .iter_mut()
+            .filter_map(|(session_id, session)| {
+                let party_number = session.remove_client(&client_id)?;
+                let promoted = session.promote_standby(party_number)?;
+                Some((*session_id, promoted, party_number))
+            })
+            .collect()
     }
 
     /// Adds a new session and adds it to the group.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the group is single-purpose and the session
+    ///   kind doesn't match the declared one.
+    /// * Returns an error if a fixed signing set is requested for a
+    ///   session other than sign.
+    /// * Returns an error if a required number of parties is set for a
+    ///   session other than custom, or it's not within `1..=n`.
+    /// * Returns an error if a reserved party number is not within `1..=n`.
     #[cfg(feature = "server")]
-    pub fn add_session(&mut self, kind: SessionKind, value: SessionValue) -> Session {
+    pub fn add_session(
+        &mut self,
+        kind: SessionKind,
+        value: SessionValue,
+        options: SessionOptions,
+    ) -> anyhow::Result<Session> {
+        if let Some(expected) = &self.kind {
+            if *expected != kind {
+                return Err(GroupError::SessionKindMismatch(expected.clone(), kind).into());
+            }
+        }
+        if options.fixed_signers && kind != SessionKind::Sign {
+            return Err(GroupError::FixedSignersNotSupported(kind).into());
+        }
+        let n = self.params.n();
+        if let Some(required_parties) = options.required_parties {
+            if !kind.is_custom() {
+                return Err(GroupError::RequiredPartiesNotSupported(kind).into());
+            }
+            if required_parties == 0 || required_parties > n as usize {
+                return Err(GroupError::RequiredPartiesOutOfRange(required_parties, n).into());
+            }
+        }
+        if let Some(&party_number) = options
+            .reserved_parties
+            .iter()
+            .find(|&&party_number| party_number == 0 || party_number > n)
+        {
+            return Err(GroupError::ReservedPartyOutOfRange(party_number, n).into());
+        }
         let session_id = Uuid::new_v4();
-        let session = Session::new(session_id, kind, value);
-        let session_c = session.clone();
+        let mut session = Session::new(session_id, kind, value, options);
+        session.record_event(SessionLifecycle::Created);
+        let session_c = session.sanitized();
         self.sessions.insert(session_id, session);
-        session_c
+        self.stats.sessions_created += 1;
+        Ok(session_c)
+    }
+
+    /// Removes a session from the group, returning it if it existed.
+    ///
+    /// Sessions that were ever ready count as completed, otherwise as
+    /// aborted, regardless of the parties still signed up when removed.
+    #[cfg(feature = "server")]
+    pub fn remove_session(&mut self, session_id: &SessionId) -> Option<Session> {
+        let session = self.sessions.remove(session_id)?;
+        if session.reached_threshold {
+            self.stats.sessions_completed += 1;
+        } else {
+            self.stats.sessions_aborted += 1;
+        }
+        Some(session)
+    }
+
+    /// Removes a session aborted by one of its parties, always counted as
+    /// aborted even if it had reached the threshold.
+    #[cfg(feature = "server")]
+    pub fn abort_session(&mut self, session_id: &SessionId) -> Option<Session> {
+        let session = self.sessions.remove(session_id)?;
+        self.stats.sessions_aborted += 1;
+        Some(session)
     }
 
     /// Returns a session by its ID, if it exists.
@@ -86,6 +295,19 @@ impl Group {
         self.sessions.get(session_id)
     }
 
+    /// Returns the sessions belonging to this group.
+    #[cfg(feature = "server")]
+    pub fn sessions(&self) -> impl Iterator<Item = &Session> {
+        self.sessions.values()
+    }
+
+    /// Returns the approximate number of bytes held by the sessions of
+    /// this group, see [`Session::approximate_size`].
+    #[cfg(feature = "server")]
+    pub fn sessions_size(&self) -> usize {
+        self.sessions().map(Session::approximate_size).sum()
+    }
+
     /// Returns a mutable session by its ID, if it exists.
     #[cfg(feature = "server")]
     pub fn get_session_mut(&mut self, session_id: &SessionId) -> Option<&mut Session> {
@@ -104,12 +326,65 @@ impl Group {
         self.clients.len() == self.params.n() as usize
     }
 
+    /// Returns a boolean indicating if the group outlived its time to live
+    /// without ever reaching `n` members, or was restored from a store and
+    /// nobody joined it before its reclaim deadline.
+    #[cfg(feature = "server")]
+    pub fn is_expired(&self) -> bool {
+        if self
+            .reclaim_deadline
+            .is_some_and(|deadline| self.is_empty() && Instant::now() >= deadline)
+        {
+            return true;
+        }
+        match self.ttl_secs {
+            Some(ttl_secs) => {
+                !self.filled && self.created_at.elapsed() >= Duration::from_secs(ttl_secs)
+            }
+            None => false,
+        }
+    }
+
     /// Returns the ID of the group.
     #[cfg(feature = "server")]
     pub fn id(&self) -> GroupId {
         self.id
     }
 
+    /// Returns the client that created the group.
+    #[cfg(feature = "server")]
+    pub fn creator(&self) -> ClientId {
+        self.creator
+    }
+
+    /// Returns a boolean indicating if the client created this group and is
+    /// still part of it.
+    #[cfg(feature = "server")]
+    pub fn is_creator(&self, client_id: &ClientId) -> bool {
+        !client_id.is_nil() && self.creator == *client_id && self.has_client(client_id)
+    }
+
+    /// Returns a boolean indicating if the client joined this group.
+    #[cfg(feature = "server")]
+    pub fn has_client(&self, client_id: &ClientId) -> bool {
+        self.clients.contains(client_id)
+    }
+
+    /// Returns the historical participation counters of the group.
+    #[cfg(feature = "server")]
+    pub fn stats(&self) -> &GroupStats {
+        &self.stats
+    }
+
+    /// Returns the member of the group with the given public id.
+    #[cfg(feature = "server")]
+    pub fn find_client(&self, public_id: &PublicClientId) -> Option<ClientId> {
+        self.clients
+            .iter()
+            .find(|client_id| client_id.public_id() == *public_id)
+            .copied()
+    }
+
     /// Returns the client ids associated with the group.
     #[cfg(feature = "server")]
     pub fn clients(&self) -> &HashSet<ClientId> {
@@ -117,16 +392,317 @@ impl Group {
     }
 }
 
-impl Clone for Group {
-    /// Clones group parameters, disregarding sensitive information.
+impl Group {
+    /// Returns a copy of the group parameters, disregarding sensitive
+    /// information such as its creator, members and sessions.
     ///
-    /// Should be used only for logging purposes.
-    fn clone(&self) -> Self {
+    /// Should be used whenever the group leaves the state, e.g. for
+    /// responses, notifications or logging.
+    pub fn sanitized(&self) -> Self {
         Self {
             id: self.id,
             params: self.params.clone(),
+            kind: self.kind.clone(),
+            ttl_secs: self.ttl_secs,
+            join_code: self.join_code.clone(),
+            creator: ClientId::nil(),
             sessions: HashMap::new(),
             clients: HashSet::new(),
+            stats: GroupStats::default(),
+            filled: self.filled,
+            created_at: self.created_at,
+            reclaim_deadline: self.reclaim_deadline,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::state::session::SessionFullPolicy;
+
+    fn group_with_session(n: u16, on_full: SessionFullPolicy) -> (Group, SessionId) {
+        let mut group = Group::new(
+            Uuid::new_v4(),
+            Parameters::new(n, 1).unwrap(),
+            None,
+            None,
+            ClientId::new_v4(),
+        );
+        let options = SessionOptions {
+            on_full,
+            ..Default::default()
+        };
+        let session_id = group
+            .add_session(SessionKind::Keygen, None, options)
+            .unwrap()
+            .id;
+        (group, session_id)
+    }
+
+    #[test]
+    fn standbys_are_promoted_to_freed_party_numbers() {
+        let (mut group, session_id) = group_with_session(2, SessionFullPolicy::Standby);
+        let clients: Vec<ClientId> = (0..3).map(|_| ClientId::new_v4()).collect();
+        let session = group.get_session_mut(&session_id).unwrap();
+        session.signup(clients[0], 2).unwrap();
+        let party_number = session.signup(clients[1], 2).unwrap();
+        assert_eq!(session.add_standby(clients[2]).unwrap(), 1);
+
+        let promotions = group.remove_client(clients[1]);
+        assert_eq!(promotions, [(session_id, clients[2], party_number)]);
+        let session = group.get_session(&session_id).unwrap();
+        assert_eq!(session.get_party_number(&clients[2]), Some(party_number));
+        assert!(!session.is_client_in_standby(&clients[2]));
+    }
+
+    #[test]
+    fn dropped_parties_are_only_taken_over_before_the_threshold() {
+        let (mut group, session_id) = group_with_session(2, SessionFullPolicy::Standby);
+        let clients: Vec<ClientId> = (0..4).map(|_| ClientId::new_v4()).collect();
+        let session = group.get_session_mut(&session_id).unwrap();
+        session.signup(clients[0], 2).unwrap();
+        session.signup(clients[1], 2).unwrap();
+        session.add_standby(clients[2]).unwrap();
+
+        let promotions = group.drop_client(clients[0]);
+        assert_eq!(promotions, [(session_id, clients[2], 1)]);
+
+        // Party numbers of ready sessions are kept vacant to be claimed
+        let session = group.get_session_mut(&session_id).unwrap();
+        session.mark_ready();
+        session.add_standby(clients[3]).unwrap();
+        assert!(group.drop_client(clients[1]).is_empty());
+        let session = group.get_session(&session_id).unwrap();
+        assert_eq!(session.get_party_number(&clients[1]), Some(2));
+        assert!(session.is_client_in_standby(&clients[3]));
+    }
+
+    #[test]
+    fn standbys_cant_sign_up_twice() {
+        let (mut group, session_id) = group_with_session(2, SessionFullPolicy::Standby);
+        let client_id = ClientId::new_v4();
+        let session = group.get_session_mut(&session_id).unwrap();
+        session.add_standby(client_id).unwrap();
+
+        assert!(session.add_standby(client_id).is_err());
+        assert!(session.signup(client_id, 2).is_err());
+    }
+
+    #[test]
+    fn dropped_standbys_leave_the_queue() {
+        let (mut group, session_id) = group_with_session(2, SessionFullPolicy::Standby);
+        let client_id = ClientId::new_v4();
+        group
+            .get_session_mut(&session_id)
+            .unwrap()
+            .add_standby(client_id)
+            .unwrap();
+
+        group.drop_client(client_id);
+        let session = group.get_session(&session_id).unwrap();
+        assert!(!session.is_client_in_standby(&client_id));
+    }
+
+    #[test]
+    fn stats_count_every_join_and_session() {
+        let (mut group, _) = group_with_session(3, SessionFullPolicy::Reject);
+        let clients: Vec<ClientId> = (0..2).map(|_| ClientId::new_v4()).collect();
+        for client_id in &clients {
+            group.add_client(*client_id).unwrap();
+        }
+        group
+            .add_session(SessionKind::Sign, None, SessionOptions::default())
+            .unwrap();
+        // Members that left still count as joined
+        group.remove_client(clients[1]);
+
+        assert_eq!(group.stats().joined, 2);
+        assert_eq!(group.stats().sessions_created, 2);
+    }
+
+    #[test]
+    fn sessions_that_were_ready_count_as_completed() {
+        let (mut group, session_id) = group_with_session(2, SessionFullPolicy::Reject);
+        let clients: Vec<ClientId> = (0..2).map(|_| ClientId::new_v4()).collect();
+        let session = group.get_session_mut(&session_id).unwrap();
+        for client_id in &clients {
+            session.signup(*client_id, 2).unwrap();
         }
+        session.mark_ready();
+        // Parties leaving after the threshold don't turn it into an abort
+        group.remove_client(clients[1]);
+        group.remove_session(&session_id);
+
+        // Signed up parties alone never make a session completed
+        let session_id = group
+            .add_session(SessionKind::Keygen, None, SessionOptions::default())
+            .unwrap()
+            .id;
+        let session = group.get_session_mut(&session_id).unwrap();
+        for client_id in &clients {
+            session.signup(*client_id, 2).unwrap();
+        }
+        group.remove_session(&session_id);
+
+        assert_eq!(group.stats().sessions_completed, 1);
+        assert_eq!(group.stats().sessions_aborted, 1);
+    }
+
+    #[test]
+    fn sanitized_groups_drop_members_and_sessions() {
+        let (mut group, session_id) = group_with_session(2, SessionFullPolicy::Reject);
+        let client_id = ClientId::new_v4();
+        group.add_client(client_id).unwrap();
+
+        let sanitized = group.sanitized();
+        assert_eq!(sanitized.id, group.id);
+        assert!(!sanitized.has_client(&client_id));
+        assert!(sanitized.get_session(&session_id).is_none());
+        // Plain clones keep everything
+        let cloned = group.clone();
+        assert!(cloned.has_client(&client_id));
+        assert!(cloned.get_session(&session_id).is_some());
+    }
+
+    #[test]
+    fn reserved_party_numbers_must_be_in_range() {
+        let (mut group, _) = group_with_session(3, SessionFullPolicy::Reject);
+        let options = |reserved_parties| SessionOptions {
+            reserved_parties,
+            ..Default::default()
+        };
+
+        let err = group
+            .add_session(SessionKind::Keygen, None, options(vec![1, 4]))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(GroupError::ReservedPartyOutOfRange(4, 3))
+        ));
+        assert!(group
+            .add_session(SessionKind::Keygen, None, options(vec![0]))
+            .is_err());
+        assert!(group
+            .add_session(SessionKind::Keygen, None, options(vec![1, 3]))
+            .is_ok());
+    }
+
+    #[test]
+    fn only_custom_sessions_set_their_required_parties() {
+        let (mut group, _) = group_with_session(3, SessionFullPolicy::Reject);
+        let options = |required_parties| SessionOptions {
+            required_parties: Some(required_parties),
+            ..Default::default()
+        };
+
+        let err = group
+            .add_session(SessionKind::Keygen, None, options(2))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(GroupError::RequiredPartiesNotSupported(SessionKind::Keygen))
+        ));
+        let custom = SessionKind::Custom("frost".into());
+        let err = group
+            .add_session(custom.clone(), None, options(4))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(GroupError::RequiredPartiesOutOfRange(4, 3))
+        ));
+        assert!(group.add_session(custom, None, options(2)).is_ok());
+    }
+
+    #[test]
+    fn sessions_must_match_the_group_kind() {
+        let params = Parameters::new(3, 1).unwrap();
+        let mut group = Group::new(
+            Uuid::new_v4(),
+            params,
+            Some(SessionKind::Sign),
+            None,
+            ClientId::new_v4(),
+        );
+
+        let err = group
+            .add_session(SessionKind::Keygen, None, SessionOptions::default())
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(GroupError::SessionKindMismatch(
+                SessionKind::Sign,
+                SessionKind::Keygen
+            ))
+        ));
+        assert!(group
+            .add_session(SessionKind::Sign, None, SessionOptions::default())
+            .is_ok());
+        assert_eq!(group.stats().sessions_created, 1);
+    }
+
+    #[test]
+    fn fixed_signers_are_only_supported_by_sign_sessions() {
+        let (mut group, _) = group_with_session(3, SessionFullPolicy::Reject);
+        let options = SessionOptions {
+            fixed_signers: true,
+            ..Default::default()
+        };
+
+        let err = group
+            .add_session(SessionKind::Keygen, None, options.clone())
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(GroupError::FixedSignersNotSupported(SessionKind::Keygen))
+        ));
+        assert!(group.add_session(SessionKind::Sign, None, options).is_ok());
+    }
+
+    #[test]
+    fn groups_expire_unless_they_filled_up() {
+        let params = Parameters::new(2, 1).unwrap();
+        let mut group = Group::new(
+            Uuid::new_v4(),
+            params.clone(),
+            None,
+            Some(0),
+            ClientId::new_v4(),
+        );
+        group.add_client(ClientId::new_v4()).unwrap();
+        assert!(group.is_expired());
+
+        group.add_client(ClientId::new_v4()).unwrap();
+        group.remove_client(*group.clients().iter().next().unwrap());
+        assert!(!group.is_expired());
+        assert!(!Group::new(Uuid::new_v4(), params, None, None, ClientId::new_v4()).is_expired());
+    }
+
+    #[test]
+    fn only_creators_still_in_the_group_are_creators() {
+        let creator = ClientId::new_v4();
+        let mut group = Group::new(
+            Uuid::new_v4(),
+            Parameters::new(2, 1).unwrap(),
+            None,
+            None,
+            creator,
+        );
+        assert_eq!(group.creator(), creator);
+        assert!(!group.is_creator(&creator));
+        group.add_client(creator).unwrap();
+        assert!(group.is_creator(&creator));
+        group.drop_client(creator);
+        assert!(!group.is_creator(&creator));
+
+        let mut restored = Group::new(
+            Uuid::new_v4(),
+            Parameters::new(2, 1).unwrap(),
+            None,
+            None,
+            ClientId::nil(),
+        );
+        restored.add_client(ClientId::nil()).unwrap();
+        assert!(!restored.is_creator(&ClientId::nil()));
     }
 }
@@ -0,0 +1,80 @@
+//! Memory accounting
+//!
+//! This module contains a coarse tracker of the bytes held by the server
+//! on behalf of clients, used to shed load before running out of memory.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Approximate bytes held in relay queues and session storage,
+/// bounded by an optional budget.
+#[derive(Debug, Default)]
+pub struct MemoryTracker {
+    /// Bytes currently accounted.
+    used: AtomicUsize,
+    /// Maximum bytes allowed before shedding load, unlimited if `None`.
+    budget: Option<usize>,
+}
+
+impl MemoryTracker {
+    /// Creates a new tracker with the given budget.
+    pub fn new(budget: Option<usize>) -> Self {
+        Self {
+            used: AtomicUsize::new(0),
+            budget,
+        }
+    }
+
+    /// Accounts bytes that are now held by the server.
+    pub fn reserve(&self, bytes: usize) {
+        self.used.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Releases bytes previously reserved.
+    pub fn release(&self, bytes: usize) {
+        // Saturate so accounting mistakes never wrap around.
+        let _ = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+    }
+
+    /// Returns the bytes currently accounted.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns a boolean indicating if the budget is exhausted, in which case
+    /// new sessions and messages should be rejected until usage recovers.
+    pub fn is_exhausted(&self) -> bool {
+        matches!(self.budget, Some(budget) if self.used() >= budget)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_is_exhausted_until_usage_recovers() {
+        let memory = MemoryTracker::new(Some(100));
+        memory.reserve(60);
+        assert!(!memory.is_exhausted());
+        memory.reserve(40);
+        assert!(memory.is_exhausted());
+        memory.release(40);
+        assert!(!memory.is_exhausted());
+        assert_eq!(memory.used(), 60);
+    }
+
+    #[test]
+    fn releases_saturate_at_zero() {
+        let memory = MemoryTracker::default();
+        memory.reserve(10);
+        memory.release(20);
+        assert_eq!(memory.used(), 0);
+        // Unlimited budgets are never exhausted
+        memory.reserve(usize::MAX);
+        assert!(!memory.is_exhausted());
+    }
+}
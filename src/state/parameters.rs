@@ -63,11 +63,51 @@ impl Parameters {
         Ok(())
     }
 
+    /// Returns the minimum number of parties a session of the given kind
+    /// needs to reach the threshold.
+    ///
+    /// Custom sessions need every party unless they set their own count,
+    /// see [`Session::required_parties`](super::session::Session::required_parties).
+    pub fn required_parties(&self, kind: &SessionKind) -> usize {
+        match kind {
+            SessionKind::Keygen | SessionKind::Reshare | SessionKind::Custom(_) => self.n as usize,
+            SessionKind::Sign => self.t as usize + 1,
+        }
+    }
+
     /// Returns boolean indicating if threshold has been reached.
-    pub fn threshold_reached(&self, kind: SessionKind, parties: usize) -> bool {
+    ///
+    /// Resharing needs every party of the new set plus at least `t + 1`
+    /// parties holding the old shares. Both sets are members of the group,
+    /// so it needs all `n` parties like keygen, which already includes
+    /// `t + 1` of them as `n > t` holds by validation.
+    pub fn threshold_reached(&self, kind: &SessionKind, parties: usize) -> bool {
         match kind {
-            SessionKind::Keygen => parties == self.n as usize,
+            SessionKind::Keygen | SessionKind::Reshare | SessionKind::Custom(_) => {
+                parties == self.n as usize
+            }
             SessionKind::Sign => parties > self.t as usize,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reshare_needs_every_party() {
+        let params = Parameters::new(3, 1).unwrap();
+        assert!(!params.threshold_reached(&SessionKind::Reshare, 2));
+        assert!(params.threshold_reached(&SessionKind::Reshare, 3));
+        // Enough parties to sign are not enough to reshare
+        assert!(params.threshold_reached(&SessionKind::Sign, 2));
+    }
+
+    #[test]
+    fn signing_requires_t_plus_one_parties() {
+        let params = Parameters::new(5, 2).unwrap();
+        assert_eq!(params.required_parties(&SessionKind::Keygen), 5);
+        assert_eq!(params.required_parties(&SessionKind::Sign), 3);
+    }
+}
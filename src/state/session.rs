@@ -2,11 +2,16 @@
 //!
 //! This module contains all the logic related to session management.
 
+#[cfg(feature = "server")]
+use super::parameters::Parameters;
 use super::ClientId;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::{HashMap, HashSet};
-use strum::EnumString;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+#[cfg(feature = "server")]
+use std::time::Duration;
+use std::time::Instant;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -24,23 +29,240 @@ pub enum SessionError {
     PartyNumberAlreadyOccupied(SessionPartyNumber),
     #[error("client `{0}` is already signed up")]
     ClientAlreadySignedUp(ClientId),
+    #[error("client `{0}` is already logged in with party number `{1}`")]
+    PartyNumberConflict(ClientId, SessionPartyNumber),
+    #[error("party number `{0}` is out of range, expected a value between 1 and `{1}`")]
+    PartyNumberOutOfRange(SessionPartyNumber, u16),
+    #[error("message `{0}` is not pending an acknowledgement from client `{1}`")]
+    MessageNotPending(MessageId, ClientId),
+    #[error("party number `{0}` is not vacant")]
+    PartyNumberNotVacant(SessionPartyNumber),
+    #[error("session is full, every party number between 1 and `{0}` is occupied")]
+    Full(u16),
+    #[error("session value can't be changed once the session is ready")]
+    ValueLocked,
+    #[error("event history is not enabled for the session")]
+    EventHistoryDisabled,
+}
+
+/// Maximum number of lifecycle events kept by a session with event
+/// history enabled, older events are discarded first.
+pub const EVENT_HISTORY_CAPACITY: usize = 16;
+
+/// Lifecycle event of a session, never carrying message payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SessionLifecycle {
+    /// The session was created.
+    Created,
+    /// The session reached the threshold.
+    Ready,
+    /// The session value was replaced.
+    ValueUpdated,
+}
+
+/// Lifecycle event kept in the history of a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SessionHistoryEvent {
+    /// Sequence number of the event within the session, starting at 1.
+    pub seq: u64,
+    pub event: SessionLifecycle,
+}
+
+/// Unique ID of a message within a session, assigned to messages
+/// that request delivery acknowledgements.
+pub type MessageId = u64;
+
+/// Sequence number stamped by the server on every message relayed in a
+/// session, starting at 1.
+pub type MessageSeq = u64;
+
+/// Maximum number of messages of a single sender waiting for delivery
+/// acknowledgements, its oldest one stops being tracked once exceeded.
+pub const MAX_PENDING_ACKS_PER_SENDER: usize = 32;
+
+/// Message waiting for delivery acknowledgements.
+#[derive(Debug, Clone)]
+pub struct PendingAck {
+    /// Client that sent the message.
+    pub sender: ClientId,
+    /// Clients that haven't acknowledged the message yet.
+    pub receivers: HashSet<ClientId>,
+}
+
+/// Behavior applied when a client signs up to a session that is already full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionFullPolicy {
+    /// Reject the signup with an error.
+    #[default]
+    Reject,
+    /// Queue the client as a standby, promoting it to a party
+    /// as soon as a slot is freed.
+    Standby,
+}
+
+/// Parties taken into account when checking if a session is ready.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SessionReadiness {
+    /// Count every party that signed up or logged in.
+    #[default]
+    SignedUp,
+    /// Count only parties whose client is currently connected.
+    Connected,
+}
+
+/// Policy used to pick the party number of a client signing up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PartyAssignment {
+    /// Assign the lowest free party number, reusing the ones freed by
+    /// parties that left.
+    #[default]
+    FillGaps,
+    /// Assign party numbers strictly in arrival order, a freed party
+    /// number is never handed out again by a signup.
+    Monotonic,
+}
+
+/// Encoding of an opaque payload carried in a session value or message.
+///
+/// The server never decodes CBOR payloads, they are relayed as the string
+/// the sender provided so big integers in shares aren't mangled by JSON.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MessageEncoding {
+    /// Plain JSON value.
+    #[default]
+    Json,
+    /// CBOR carried as a base64 string, standard alphabet with padding.
+    CborBase64,
+    /// CBOR carried as a hex string.
+    CborHex,
+}
+
+impl MessageEncoding {
+    /// Every encoding supported by the server.
+    pub const ALL: [Self; 3] = [Self::Json, Self::CborBase64, Self::CborHex];
+
+    /// Returns a boolean indicating if the payload is plain JSON.
+    pub fn is_json(&self) -> bool {
+        *self == Self::Json
+    }
+}
+
+/// Options that can be set when creating a session.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SessionOptions {
+    /// Policy applied when a client signs up to a full session.
+    #[serde(rename = "onFull", default)]
+    pub on_full: SessionFullPolicy,
+    /// Parties counted towards the session threshold.
+    #[serde(default)]
+    pub readiness: SessionReadiness,
+    /// Whether a shared random seed is distributed when the session is ready.
+    #[serde(rename = "provideSeed", default)]
+    pub provide_seed: bool,
+    /// Whether a sign session is capped at exactly `t + 1` parties,
+    /// rejecting further signups once the signing set is fixed.
+    #[serde(rename = "fixedSigners", default)]
+    pub fixed_signers: bool,
+    /// Seconds after creation the session is removed if it hasn't reached
+    /// the threshold yet, sessions never expire if unset.
+    #[serde(rename = "ttlSecs", default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    /// Encoding of the session value.
+    #[serde(rename = "valueEncoding", default)]
+    pub value_encoding: MessageEncoding,
+    /// Party numbers held back from signups, they can only be taken
+    /// by logging in with them.
+    #[serde(
+        rename = "reservedParties",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub reserved_parties: Vec<SessionPartyNumber>,
+    /// Policy used to assign party numbers on signup.
+    #[serde(rename = "partyAssignment", default)]
+    pub party_assignment: PartyAssignment,
+    /// Parties a custom session needs to be ready, every party of the
+    /// group if unset. Only allowed for custom session kinds.
+    #[serde(
+        rename = "requiredParties",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub required_parties: Option<usize>,
+    /// Whether lifecycle events are kept so members that missed them can
+    /// fetch them later, see [`EVENT_HISTORY_CAPACITY`].
+    #[serde(rename = "eventHistory", default)]
+    pub event_history: bool,
+}
+
+/// Result of signing up to a session.
+#[derive(Debug, Clone, Copy)]
+pub enum SessionSignup {
+    /// Client was assigned a party number.
+    Party(SessionPartyNumber),
+    /// Client was queued as a standby at the given position, starting at 1.
+    Standby(usize),
+}
+
+/// Progress of a session towards its threshold.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct SessionProgress {
+    /// Parties counted towards the threshold, according to the readiness mode.
+    pub parties: usize,
+    /// Parties required by the session kind to reach the threshold.
+    pub required: usize,
+    /// Whether the threshold has been reached.
+    pub ready: bool,
 }
 
 /// Session kinds available in this implementation.
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, EnumString)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum SessionKind {
     /// Key generation session.
     #[serde(rename = "keygen")]
-    #[strum(serialize = "keygen")]
     Keygen,
     /// Signing session.
     #[serde(rename = "sign")]
-    #[strum(serialize = "sign")]
     Sign,
+    /// Key resharing session, redistributing shares to a new party set.
+    #[serde(rename = "reshare")]
+    Reshare,
+    /// Session of a protocol unknown to the server, identified by name.
+    ///
+    /// It's ready once `requiredParties` parties joined, or every party of
+    /// the group if the session doesn't set it.
+    #[serde(rename = "custom")]
+    Custom(String),
+}
+
+impl fmt::Display for SessionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Keygen => write!(f, "keygen"),
+            Self::Sign => write!(f, "sign"),
+            Self::Reshare => write!(f, "reshare"),
+            Self::Custom(name) => write!(f, "custom:{name}"),
+        }
+    }
+}
+
+impl SessionKind {
+    /// Every session kind supported by the server.
+    pub const ALL: [Self; 3] = [Self::Keygen, Self::Sign, Self::Reshare];
+
+    /// Returns a boolean indicating if the session kind is a custom one.
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Self::Custom(_))
+    }
 }
 
 /// Session is subgroup of clients intended to be used for a specific purpose.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Session {
     /// Unique ID of the session.
     pub id: SessionId,
@@ -52,65 +274,370 @@ pub struct Session {
     /// It can be a message or transaction intended
     /// to be signed by the session.
     pub value: SessionValue,
+    /// Encoding of the session value.
+    #[serde(
+        rename = "valueEncoding",
+        default,
+        skip_serializing_if = "MessageEncoding::is_json"
+    )]
+    pub value_encoding: MessageEncoding,
     /// Map party number to client id, starting at 1.
     #[serde(skip)]
     pub party_signups: HashMap<SessionPartyNumber, ClientId>,
     /// Occupied party numbers, starting at 1.
     #[serde(skip)]
     pub occupied_party_numbers: Vec<SessionPartyNumber>,
+    /// Reserved party numbers that weren't claimed yet, they are occupied
+    /// without being assigned to any client.
+    #[serde(skip)]
+    pub reserved: HashSet<SessionPartyNumber>,
+    /// Highest party number ever assigned, signups continue after it
+    /// when party numbers are assigned monotonically.
+    #[serde(skip)]
+    pub last_party_number: SessionPartyNumber,
     ///
     /// Party numbers of finished clients
     #[serde(skip)]
     pub finished: HashSet<u16>,
+    /// Options set at the moment of creation.
+    #[serde(skip)]
+    pub options: SessionOptions,
+    /// Clients waiting for a party slot to be freed, in arrival order.
+    #[serde(skip)]
+    pub standbys: VecDeque<ClientId>,
+    /// Hex encoded random seed shared by the parties, generated once
+    /// when the session is ready if `provide_seed` is set.
+    #[serde(skip)]
+    pub seed: Option<String>,
+    /// Id assigned to the next message requesting acknowledgements.
+    #[serde(skip)]
+    pub next_message_id: MessageId,
+    /// Sequence number assigned to the next relayed message.
+    #[serde(skip)]
+    pub next_seq: MessageSeq,
+    /// Messages waiting for delivery acknowledgements.
+    #[serde(skip)]
+    pub pending_acks: HashMap<MessageId, PendingAck>,
+    /// Latest lifecycle events, only kept if `event_history` is set.
+    #[serde(skip)]
+    pub history: VecDeque<SessionHistoryEvent>,
+    /// Sequence number assigned to the next lifecycle event.
+    #[serde(skip)]
+    pub next_event_seq: u64,
+    /// Whether the session already reached its threshold once.
+    #[serde(skip)]
+    pub reached_threshold: bool,
+    /// Moment the session was created.
+    #[serde(skip, default = "Instant::now")]
+    pub created_at: Instant,
 }
 
 impl Session {
     /// Creates a new session with the given parameters.
-    pub fn new(id: Uuid, kind: SessionKind, value: SessionValue) -> Self {
+    ///
+    /// Reserved party numbers are occupied from the start without being
+    /// assigned to any client.
+    pub fn new(id: Uuid, kind: SessionKind, value: SessionValue, options: SessionOptions) -> Self {
+        let mut occupied_party_numbers = options.reserved_parties.clone();
+        occupied_party_numbers.sort();
+        occupied_party_numbers.dedup();
         Self {
             id,
             kind,
             value,
+            value_encoding: options.value_encoding,
             party_signups: HashMap::new(),
-            occupied_party_numbers: Vec::new(),
+            reserved: occupied_party_numbers.iter().copied().collect(),
+            occupied_party_numbers,
+            last_party_number: 0,
             finished: HashSet::new(),
+            options,
+            standbys: VecDeque::new(),
+            seed: None,
+            next_message_id: 1,
+            next_seq: 1,
+            pending_acks: HashMap::new(),
+            history: VecDeque::new(),
+            next_event_seq: 1,
+            reached_threshold: false,
+            created_at: Instant::now(),
         }
     }
 
     /// Registers a client in the session and returns its party number.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the client already signed up.
+    /// * Returns an error if every party number within `1..=n` is occupied.
     #[cfg(feature = "server")]
-    pub fn signup(&mut self, client_id: ClientId) -> anyhow::Result<SessionPartyNumber> {
-        if self.is_client_in_session(&client_id) {
+    pub fn signup(&mut self, client_id: ClientId, n: u16) -> anyhow::Result<SessionPartyNumber> {
+        if self.is_client_in_session(&client_id) || self.is_client_in_standby(&client_id) {
             return Err(SessionError::ClientAlreadySignedUp(client_id).into());
         }
-        let party_number = self.get_next_party_number();
+        let party_number = self.get_next_party_number(n).ok_or(SessionError::Full(n))?;
         self.add_party(client_id, party_number);
         Ok(party_number)
     }
 
+    /// Queues a client as a standby and returns its position in the queue.
+    #[cfg(feature = "server")]
+    pub fn add_standby(&mut self, client_id: ClientId) -> anyhow::Result<usize> {
+        if self.is_client_in_session(&client_id) || self.is_client_in_standby(&client_id) {
+            return Err(SessionError::ClientAlreadySignedUp(client_id).into());
+        }
+        self.standbys.push_back(client_id);
+        Ok(self.standbys.len())
+    }
+
+    /// Removes a client from the standby queue.
+    #[cfg(feature = "server")]
+    pub fn remove_standby(&mut self, client_id: &ClientId) {
+        self.standbys.retain(|id| id != client_id);
+    }
+
+    /// Assigns a freed party number to the first standby in the queue,
+    /// returning the promoted client id.
+    ///
+    /// Standbys that took a party number meanwhile are skipped, a client
+    /// never holds more than one party number.
+    #[cfg(feature = "server")]
+    pub fn promote_standby(&mut self, party_number: SessionPartyNumber) -> Option<ClientId> {
+        while let Some(client_id) = self.standbys.pop_front() {
+            if !self.is_client_in_session(&client_id) {
+                self.add_party(client_id, party_number);
+                return Some(client_id);
+            }
+        }
+        None
+    }
+
+    /// Returns boolean indicating if the client is waiting as a standby.
+    #[cfg(feature = "server")]
+    pub fn is_client_in_standby(&self, client_id: &ClientId) -> bool {
+        self.standbys.contains(client_id)
+    }
+
     /// Signs in a client in the session with a given party number.
+    ///
+    /// Logging in again with the same party number is a no-op, so clients
+    /// can safely retry. Standbys logging in leave the standby queue, so
+    /// they aren't promoted to a second party number later on.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the client already holds a different party number.
+    /// * Returns an error if the party number is occupied by another client,
+    ///   reserved party numbers can be taken as long as nobody claimed them.
     #[cfg(feature = "server")]
     pub fn login(
         &mut self,
         client_id: ClientId,
         party_number: SessionPartyNumber,
     ) -> anyhow::Result<()> {
-        if self.is_client_in_session(&client_id) {
-            return Ok(()); //TODO: think of a better way to handle this (should we return an error?)
+        if let Some(current) = self.get_party_number(&client_id) {
+            if current != party_number {
+                return Err(SessionError::PartyNumberConflict(client_id, current).into());
+            }
+            return Ok(());
         }
-        if self.occupied_party_numbers.contains(&party_number) {
+        if self.party_signups.contains_key(&party_number) {
             return Err(SessionError::PartyNumberAlreadyOccupied(party_number).into());
         }
+        self.remove_standby(&client_id);
         self.add_party(client_id, party_number);
         Ok(())
     }
 
-    /// Adds new party assuming `party_number` doesn't exist already.
+    /// Reassigns an occupied party number to a client, returning the
+    /// client that was holding it.
+    ///
+    /// The caller is responsible for checking that the previous holder
+    /// left, the party number is otherwise taken over from a live party.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if the client already holds a party number.
+    /// * Returns an error if the party number isn't occupied, in which case
+    ///   it must be taken with a regular login.
+    #[cfg(feature = "server")]
+    pub fn claim(
+        &mut self,
+        client_id: ClientId,
+        party_number: SessionPartyNumber,
+    ) -> anyhow::Result<ClientId> {
+        if let Some(current) = self.get_party_number(&client_id) {
+            return Err(SessionError::PartyNumberConflict(client_id, current).into());
+        }
+        let previous = self
+            .party_signups
+            .get_mut(&party_number)
+            .map(|holder| std::mem::replace(holder, client_id))
+            .ok_or(SessionError::PartyNumberNotVacant(party_number))?;
+        self.remove_standby(&client_id);
+        self.drop_pending_acks(&previous);
+        self.finished.remove(&party_number);
+        Ok(previous)
+    }
+
+    /// Adds new party assuming `party_number` isn't assigned already, it
+    /// may be occupied by a reservation, and the client holds no other
+    /// party number.
     #[cfg(feature = "server")]
     fn add_party(&mut self, client_id: ClientId, party_number: SessionPartyNumber) {
-        self.occupied_party_numbers.push(party_number);
-        self.occupied_party_numbers.sort();
+        if !self.reserved.remove(&party_number) {
+            self.occupied_party_numbers.push(party_number);
+            self.occupied_party_numbers.sort();
+        }
         self.party_signups.insert(party_number, client_id);
+        self.last_party_number = self.last_party_number.max(party_number);
+    }
+
+    /// Removes a client from the session, freeing its party number.
+    ///
+    /// Returns the party number the client was holding, if any.
+    #[cfg(feature = "server")]
+    pub fn remove_client(&mut self, client_id: &ClientId) -> Option<SessionPartyNumber> {
+        self.remove_standby(client_id);
+        self.drop_pending_acks(client_id);
+        let party_number = self.get_party_number(client_id)?;
+        self.party_signups.remove(&party_number);
+        self.occupied_party_numbers
+            .retain(|party| *party != party_number);
+        self.finished.remove(&party_number);
+        Some(party_number)
+    }
+
+    /// Returns the sequence number of a new relayed message.
+    #[cfg(feature = "server")]
+    pub fn next_seq(&mut self) -> MessageSeq {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    /// Registers a message waiting for acknowledgements of the given
+    /// receivers, returning its id.
+    ///
+    /// The oldest message of the sender stops being tracked if it already
+    /// has [`MAX_PENDING_ACKS_PER_SENDER`] messages pending.
+    #[cfg(feature = "server")]
+    pub fn add_pending_ack(&mut self, sender: ClientId, receivers: Vec<ClientId>) -> MessageId {
+        let sent = self
+            .pending_acks
+            .iter()
+            .filter(|(_, pending)| pending.sender == sender);
+        if sent.clone().count() >= MAX_PENDING_ACKS_PER_SENDER {
+            if let Some(oldest) = sent.map(|(message_id, _)| *message_id).min() {
+                self.pending_acks.remove(&oldest);
+            }
+        }
+        let message_id = self.next_message_id;
+        self.next_message_id += 1;
+        self.pending_acks.insert(
+            message_id,
+            PendingAck {
+                sender,
+                receivers: receivers.into_iter().collect(),
+            },
+        );
+        message_id
+    }
+
+    /// Acknowledges a message on behalf of a receiver, returning the sender.
+    ///
+    /// The message stops being tracked once every receiver acknowledged it.
+    #[cfg(feature = "server")]
+    pub fn acknowledge(
+        &mut self,
+        message_id: MessageId,
+        client_id: &ClientId,
+    ) -> anyhow::Result<ClientId> {
+        let pending = self
+            .pending_acks
+            .get_mut(&message_id)
+            .ok_or(SessionError::MessageNotPending(message_id, *client_id))?;
+        if !pending.receivers.remove(client_id) {
+            return Err(SessionError::MessageNotPending(message_id, *client_id).into());
+        }
+        let sender = pending.sender;
+        if pending.receivers.is_empty() {
+            self.pending_acks.remove(&message_id);
+        }
+        Ok(sender)
+    }
+
+    /// Stops tracking acknowledgements of messages sent by a client and
+    /// removes it from the receivers of the remaining ones.
+    #[cfg(feature = "server")]
+    pub fn drop_pending_acks(&mut self, client_id: &ClientId) {
+        self.pending_acks.retain(|_, pending| {
+            pending.receivers.remove(client_id);
+            pending.sender != *client_id && !pending.receivers.is_empty()
+        });
+    }
+
+    /// Records a lifecycle event if event history is enabled, discarding
+    /// the oldest one once the history is full.
+    #[cfg(feature = "server")]
+    pub fn record_event(&mut self, event: SessionLifecycle) {
+        if !self.options.event_history {
+            return;
+        }
+        if self.history.len() == EVENT_HISTORY_CAPACITY {
+            self.history.pop_front();
+        }
+        self.history.push_back(SessionHistoryEvent {
+            seq: self.next_event_seq,
+            event,
+        });
+        self.next_event_seq += 1;
+    }
+
+    /// Returns the recorded lifecycle events after `since`, and a boolean
+    /// indicating if some of them were already discarded.
+    ///
+    /// # Errors
+    ///
+    /// * Returns an error if event history is not enabled for the session.
+    #[cfg(feature = "server")]
+    pub fn events_since(&self, since: u64) -> anyhow::Result<(Vec<SessionHistoryEvent>, bool)> {
+        if !self.options.event_history {
+            return Err(SessionError::EventHistoryDisabled.into());
+        }
+        let truncated = self
+            .history
+            .front()
+            .is_some_and(|oldest| oldest.seq > since.saturating_add(1));
+        let events = self
+            .history
+            .iter()
+            .filter(|event| event.seq > since)
+            .copied()
+            .collect();
+        Ok((events, truncated))
+    }
+
+    /// Marks the session as ready, recording the [`SessionLifecycle::Ready`]
+    /// event the first time its threshold is reached.
+    #[cfg(feature = "server")]
+    pub fn mark_ready(&mut self) {
+        self.init_seed();
+        if !self.reached_threshold {
+            self.reached_threshold = true;
+            self.record_event(SessionLifecycle::Ready);
+        }
+    }
+
+    /// Generates the shared random seed if the session provides one and it
+    /// wasn't generated yet, so every ready notification carries the same seed.
+    #[cfg(feature = "server")]
+    pub fn init_seed(&mut self) {
+        if !self.options.provide_seed || self.seed.is_some() {
+            return;
+        }
+        let bytes: [u8; 32] = rand::random();
+        self.seed = Some(bytes.iter().map(|byte| format!("{byte:02x}")).collect());
     }
 
     /// Gets the party number of a client.
@@ -143,46 +670,375 @@ impl Session {
         self.party_signups.values().copied().collect()
     }
 
+    /// Returns the approximate number of bytes held by the session,
+    /// accounting its value, pending acknowledgements and fixed metadata.
+    #[cfg(feature = "server")]
+    pub fn approximate_size(&self) -> usize {
+        let value = self
+            .value
+            .as_ref()
+            .map_or(0, |value| value.to_string().len());
+        let pending_acks: usize = self
+            .pending_acks
+            .values()
+            .map(|pending| {
+                std::mem::size_of::<(MessageId, PendingAck)>()
+                    + pending.receivers.len() * std::mem::size_of::<ClientId>()
+            })
+            .sum();
+        std::mem::size_of::<Self>() + value + pending_acks
+    }
+
+    /// Returns the maximum number of parties of the session, `t + 1` for sign
+    /// sessions with a fixed signing set and `n` otherwise.
+    #[cfg(feature = "server")]
+    pub fn capacity(&self, params: &Parameters) -> usize {
+        match self.kind {
+            SessionKind::Sign if self.options.fixed_signers => params.t() as usize + 1,
+            _ => params.n() as usize,
+        }
+    }
+
+    /// Returns the minimum number of parties the session needs to reach
+    /// the threshold, the own count of custom sessions if they set one.
+    #[cfg(feature = "server")]
+    pub fn required_parties(&self, params: &Parameters) -> usize {
+        match (&self.kind, self.options.required_parties) {
+            (SessionKind::Custom(_), Some(required)) => required,
+            (kind, _) => params.required_parties(kind),
+        }
+    }
+
+    /// Returns a boolean indicating if the given number of parties reach
+    /// the threshold of the session.
+    #[cfg(feature = "server")]
+    pub fn threshold_reached(&self, params: &Parameters, parties: usize) -> bool {
+        match (&self.kind, self.options.required_parties) {
+            (SessionKind::Custom(_), Some(required)) => parties >= required,
+            (kind, _) => params.threshold_reached(kind, parties),
+        }
+    }
+
+    /// Returns the party numbers within `1..=n` that are not taken by a
+    /// client yet, including unclaimed reservations.
+    #[cfg(feature = "server")]
+    pub fn get_missing_party_numbers(&self, n: u16) -> Vec<SessionPartyNumber> {
+        (1..=n)
+            .filter(|party| !self.party_signups.contains_key(party))
+            .collect()
+    }
+
+    /// Returns a boolean indicating if the session outlived its time to live.
+    #[cfg(feature = "server")]
+    pub fn is_expired(&self) -> bool {
+        match self.options.ttl_secs {
+            Some(ttl_secs) => self.created_at.elapsed() >= Duration::from_secs(ttl_secs),
+            None => false,
+        }
+    }
+
+    /// Returns a boolean indicating if the session can't take more signups,
+    /// either because it reached its capacity or because every party number
+    /// is occupied by a party or a reservation.
+    #[cfg(feature = "server")]
+    pub fn is_full(&self, params: &Parameters) -> bool {
+        self.get_number_of_clients() >= self.capacity(params)
+            || self.get_next_party_number(params.n()).is_none()
+    }
+
     /// Returns the number of clients associated with this session.
     #[cfg(feature = "server")]
     pub fn get_number_of_clients(&self) -> usize {
         self.party_signups.len()
     }
 
-    /// Gets the next missing party number, assuming `occupied_party_numbers`
-    /// is a sorted array.
+    /// Gets the next missing party number within `1..=n`, assuming
+    /// `occupied_party_numbers` is a sorted array.
     ///
     /// # Examples
     ///
-    /// - if `[1,2,3,4]` it will return 5
-    /// - if `[1,4,5,6]` it will return 2
+    /// - if `[1,2,3,4]` and `n = 5` it will return 5
+    /// - if `[1,4,5,6]` and `n = 6` it will return 2
+    /// - if `[1,2,3]` and `n = 3` it will return `None`
+    ///
+    /// With [`PartyAssignment::Monotonic`] gaps are never filled, the next
+    /// unreserved party number after the highest one ever assigned is
+    /// returned instead.
     #[cfg(feature = "server")]
-    fn get_next_party_number(&self) -> SessionPartyNumber {
+    fn get_next_party_number(&self, n: u16) -> Option<SessionPartyNumber> {
+        if self.options.party_assignment == PartyAssignment::Monotonic {
+            return (self.last_party_number.saturating_add(1)..=n)
+                .find(|party| !self.reserved.contains(party));
+        }
         for (i, party) in self.occupied_party_numbers.iter().enumerate() {
             if (i + 1) != *party as usize {
-                return (i + 1) as SessionPartyNumber;
+                return Some((i + 1) as SessionPartyNumber).filter(|party| *party <= n);
             }
         }
 
-        match self.occupied_party_numbers.last() {
+        let next = match self.occupied_party_numbers.last() {
             Some(party) => party + 1,
             None => 1,
-        }
+        };
+        Some(next).filter(|party| *party <= n)
     }
 }
 
-impl Clone for Session {
-    /// Clones session parameters, disregarding sensitive information.
+impl Session {
+    /// Returns a copy of the session parameters, disregarding sensitive
+    /// information such as the client ids of its parties.
     ///
-    /// Should be used only for logging purposes.
-    fn clone(&self) -> Self {
+    /// Should be used whenever the session leaves the state, e.g. for
+    /// responses, notifications or logging.
+    pub fn sanitized(&self) -> Self {
         Self {
             id: self.id,
-            kind: self.kind,
+            kind: self.kind.clone(),
             value: self.value.clone(),
+            value_encoding: self.value_encoding,
             party_signups: HashMap::new(),
-            occupied_party_numbers: Vec::new(),
+            // Party numbers don't identify clients, they are kept so the
+            // parties can learn the participating set. Unclaimed reservations
+            // are not part of it.
+            occupied_party_numbers: self
+                .occupied_party_numbers
+                .iter()
+                .copied()
+                .filter(|party| !self.reserved.contains(party))
+                .collect(),
+            reserved: HashSet::new(),
+            last_party_number: self.last_party_number,
             finished: HashSet::new(),
+            options: self.options.clone(),
+            standbys: VecDeque::new(),
+            seed: self.seed.clone(),
+            next_message_id: 1,
+            next_seq: self.next_seq,
+            pending_acks: HashMap::new(),
+            history: VecDeque::new(),
+            next_event_seq: self.next_event_seq,
+            reached_threshold: self.reached_threshold,
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+
+    fn session(options: SessionOptions) -> Session {
+        Session::new(Uuid::new_v4(), SessionKind::Sign, None, options)
+    }
+
+    #[test]
+    fn seeds_are_generated_once() {
+        let mut session = session(SessionOptions {
+            provide_seed: true,
+            ..Default::default()
+        });
+        session.init_seed();
+        let seed = session.seed.clone().unwrap();
+        assert_eq!(seed.len(), 64);
+        assert!(seed.chars().all(|c| c.is_ascii_hexdigit()));
+
+        session.init_seed();
+        assert_eq!(session.seed, Some(seed));
+    }
+
+    #[test]
+    fn seeds_are_only_provided_if_requested() {
+        let mut session = session(SessionOptions::default());
+        session.init_seed();
+        assert!(session.seed.is_none());
+    }
+
+    #[test]
+    fn sanitized_sessions_keep_party_numbers_but_not_clients() {
+        let mut session = session(SessionOptions::default());
+        let client_id = ClientId::new_v4();
+        let party_number = session.signup(client_id, 3).unwrap();
+
+        let sanitized = session.sanitized();
+        assert!(!sanitized.is_client_in_session(&client_id));
+        assert!(sanitized.occupied_party_numbers.contains(&party_number));
+        assert!(session.clone().is_client_in_session(&client_id));
+    }
+
+    #[test]
+    fn reserved_party_numbers_are_only_taken_by_login() {
+        let mut session = session(SessionOptions {
+            reserved_parties: vec![2, 1, 2],
+            ..Default::default()
+        });
+        let clients: Vec<ClientId> = (0..3).map(|_| ClientId::new_v4()).collect();
+
+        assert_eq!(session.signup(clients[0], 3).unwrap(), 3);
+        assert_eq!(session.get_missing_party_numbers(3), [1, 2]);
+        assert_eq!(session.sanitized().occupied_party_numbers, [3]);
+        session.login(clients[1], 2).unwrap();
+        assert!(session.login(clients[2], 2).is_err());
+        assert_eq!(session.occupied_party_numbers, [1, 2, 3]);
+        assert_eq!(session.sanitized().occupied_party_numbers, [2, 3]);
+    }
+
+    #[test]
+    fn signups_never_exceed_n() {
+        let mut session = session(SessionOptions {
+            reserved_parties: vec![2],
+            ..Default::default()
+        });
+        let params = Parameters::new(3, 1).unwrap();
+
+        assert_eq!(session.signup(ClientId::new_v4(), 3).unwrap(), 1);
+        assert_eq!(session.signup(ClientId::new_v4(), 3).unwrap(), 3);
+        // The reservation keeps the last party number out of signups
+        assert!(session.is_full(&params));
+        let err = session.signup(ClientId::new_v4(), 3).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(SessionError::Full(3))));
+    }
+
+    #[test]
+    fn monotonic_assignment_never_reuses_party_numbers() {
+        let options = |party_assignment| SessionOptions {
+            party_assignment,
+            ..Default::default()
+        };
+        for (party_assignment, expected) in [
+            (PartyAssignment::FillGaps, Some(1)),
+            (PartyAssignment::Monotonic, Some(3)),
+        ] {
+            let mut session = session(options(party_assignment));
+            let first = ClientId::new_v4();
+            session.signup(first, 3).unwrap();
+            session.signup(ClientId::new_v4(), 3).unwrap();
+            session.remove_client(&first);
+
+            let party_number = session.signup(ClientId::new_v4(), 3).ok();
+            assert_eq!(party_number, expected);
+        }
+
+        // Freed party numbers stay unassigned once n is reached
+        let mut session = session(options(PartyAssignment::Monotonic));
+        let first = ClientId::new_v4();
+        session.signup(first, 1).unwrap();
+        session.remove_client(&first);
+        assert!(session.signup(ClientId::new_v4(), 1).is_err());
+    }
+
+    #[test]
+    fn acks_are_tracked_until_every_receiver_acknowledged() {
+        let mut session = session(SessionOptions::default());
+        let (sender, receivers) = (ClientId::new_v4(), [ClientId::new_v4(), ClientId::new_v4()]);
+        let message_id = session.add_pending_ack(sender, receivers.to_vec());
+
+        assert_eq!(
+            session.acknowledge(message_id, &receivers[0]).unwrap(),
+            sender
+        );
+        // Receivers can acknowledge only once
+        assert!(session.acknowledge(message_id, &receivers[0]).is_err());
+        assert_eq!(
+            session.acknowledge(message_id, &receivers[1]).unwrap(),
+            sender
+        );
+        assert!(session.pending_acks.is_empty());
+    }
+
+    #[test]
+    fn acks_are_dropped_with_their_sender() {
+        let mut session = session(SessionOptions::default());
+        let (sender, receiver) = (ClientId::new_v4(), ClientId::new_v4());
+        let first = session.add_pending_ack(sender, vec![receiver]);
+        let second = session.add_pending_ack(receiver, vec![sender]);
+        assert_ne!(first, second);
+
+        session.drop_pending_acks(&sender);
+        assert!(session.pending_acks.is_empty());
+    }
+
+    #[test]
+    fn acks_are_capped_per_sender() {
+        let mut session = session(SessionOptions::default());
+        let empty = session.approximate_size();
+        let (sender, receiver) = (ClientId::new_v4(), ClientId::new_v4());
+        let other = session.add_pending_ack(receiver, vec![sender]);
+        let first = session.add_pending_ack(sender, vec![receiver]);
+        assert!(session.approximate_size() > empty);
+
+        for _ in 0..MAX_PENDING_ACKS_PER_SENDER {
+            session.add_pending_ack(sender, vec![receiver]);
         }
+        // The oldest message of the sender stops being tracked
+        assert_eq!(session.pending_acks.len(), MAX_PENDING_ACKS_PER_SENDER + 1);
+        assert!(session.acknowledge(first, &receiver).is_err());
+        assert_eq!(session.acknowledge(other, &sender).unwrap(), receiver);
+
+        session.drop_pending_acks(&sender);
+        assert_eq!(session.approximate_size(), empty);
+    }
+
+    #[test]
+    fn reshare_is_serialized_by_name() {
+        let kind = serde_json::to_value(SessionKind::Reshare).unwrap();
+        assert_eq!(kind, "reshare");
+        assert_eq!(
+            serde_json::from_value::<SessionKind>(kind).unwrap(),
+            SessionKind::Reshare
+        );
+        assert_eq!(SessionKind::Reshare.to_string(), "reshare");
+    }
+
+    #[test]
+    fn custom_sessions_are_ready_with_their_required_parties() {
+        let kind = SessionKind::Custom("dkg-v2".into());
+        assert_eq!(
+            serde_json::to_value(&kind).unwrap(),
+            serde_json::json!({"custom": "dkg-v2"})
+        );
+        assert_eq!(kind.to_string(), "custom:dkg-v2");
+        let params = Parameters::new(4, 1).unwrap();
+
+        let every_party = Session::new(Uuid::new_v4(), kind.clone(), None, Default::default());
+        assert_eq!(every_party.required_parties(&params), 4);
+        assert!(!every_party.threshold_reached(&params, 3));
+        let options = SessionOptions {
+            required_parties: Some(2),
+            ..Default::default()
+        };
+        let two_parties = Session::new(Uuid::new_v4(), kind, None, options);
+        assert_eq!(two_parties.required_parties(&params), 2);
+        assert!(two_parties.threshold_reached(&params, 2));
+    }
+
+    #[test]
+    fn logins_conflicting_with_the_held_party_number_are_rejected() {
+        let mut session = session(SessionOptions::default());
+        let (client_id, other) = (ClientId::new_v4(), ClientId::new_v4());
+        session.login(client_id, 2).unwrap();
+
+        // Retrying with the same party number is a no-op
+        session.login(client_id, 2).unwrap();
+        let err = session.login(client_id, 3).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(SessionError::PartyNumberConflict(_, 2))
+        ));
+        assert!(session.login(other, 2).is_err());
+        assert_eq!(session.get_party_number(&client_id), Some(2));
+    }
+
+    #[test]
+    fn standbys_logging_in_are_not_promoted_to_a_second_party_number() {
+        let mut session = session(SessionOptions::default());
+        let (party, standby) = (ClientId::new_v4(), ClientId::new_v4());
+        session.signup(party, 3).unwrap();
+        session.add_standby(standby).unwrap();
+        session.login(standby, 3).unwrap();
+        assert!(!session.is_client_in_standby(&standby));
+
+        session.remove_client(&party);
+        assert_eq!(session.promote_standby(1), None);
+        assert_eq!(session.get_party_number(&standby), Some(3));
     }
 }
@@ -0,0 +1,266 @@
+//! State store
+//!
+//! This module contains the persistence of coordination metadata, used to
+//! recover groups and sessions after the server restarts.
+//!
+//! Only structural data is ever written: ids, parameters, session kinds and
+//! options, and the number of members of each group. Client ids, party
+//! number assignments, session values, messages and seeds are never
+//! persisted, so a recovered group must be joined again by its clients.
+
+use super::{
+    group::{Group, GroupId},
+    parameters::Parameters,
+    session::{Session, SessionId, SessionKind, SessionOptions},
+    ClientId,
+};
+use anyhow::Result;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Debug,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Non-sensitive metadata of a group.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GroupRecord {
+    pub id: GroupId,
+    pub params: Parameters,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SessionKind>,
+    #[serde(rename = "ttlSecs", default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+    /// Whether the group ever reached `n` members, so it doesn't expire.
+    pub filled: bool,
+    /// Number of members when the record was saved.
+    pub clients: usize,
+    pub sessions: Vec<SessionRecord>,
+}
+
+/// Non-sensitive metadata of a session.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SessionRecord {
+    pub id: SessionId,
+    pub kind: SessionKind,
+    pub options: SessionOptions,
+}
+
+impl From<&Group> for GroupRecord {
+    fn from(group: &Group) -> Self {
+        Self {
+            id: group.id,
+            params: group.params.clone(),
+            kind: group.kind.clone(),
+            ttl_secs: group.ttl_secs,
+            filled: group.filled,
+            clients: group.clients.len(),
+            sessions: group.sessions().map(SessionRecord::from).collect(),
+        }
+    }
+}
+
+impl From<&Session> for SessionRecord {
+    fn from(session: &Session) -> Self {
+        Self {
+            id: session.id,
+            kind: session.kind.clone(),
+            options: session.options.clone(),
+        }
+    }
+}
+
+impl GroupRecord {
+    /// Rebuilds an empty group from the record, its sessions have no
+    /// parties and its time to live starts over.
+    ///
+    /// Client ids are never persisted, so the group has no creator and
+    /// nobody is allowed to kick its members.
+    pub fn into_group(self) -> Group {
+        let mut group = Group::new(
+            self.id,
+            self.params,
+            self.kind,
+            self.ttl_secs,
+            ClientId::nil(),
+        );
+        group.filled = self.filled;
+        group.sessions = self
+            .sessions
+            .into_iter()
+            .map(|session| {
+                let session = Session::new(session.id, session.kind, None, session.options);
+                (session.id, session)
+            })
+            .collect();
+        group
+    }
+}
+
+/// Storage of group metadata, notified on every structural change.
+///
+/// Methods are called while the group is locked, implementations must
+/// not block for long.
+pub trait StateStore: Debug + Send + Sync {
+    /// Saves the metadata of a group, replacing any previous record.
+    fn save_group(&self, record: &GroupRecord) -> Result<()>;
+
+    /// Removes the metadata of a group.
+    fn remove_group(&self, group_id: &GroupId) -> Result<()>;
+
+    /// Returns the records of every saved group, records that can't be
+    /// read are skipped.
+    fn load_groups(&self) -> Result<Vec<GroupRecord>>;
+}
+
+/// Store keeping records in memory, the state is lost on restart.
+#[derive(Debug, Default)]
+pub struct MemoryStateStore {
+    groups: DashMap<GroupId, GroupRecord>,
+}
+
+impl StateStore for MemoryStateStore {
+    fn save_group(&self, record: &GroupRecord) -> Result<()> {
+        self.groups.insert(record.id, record.clone());
+        Ok(())
+    }
+
+    fn remove_group(&self, group_id: &GroupId) -> Result<()> {
+        self.groups.remove(group_id);
+        Ok(())
+    }
+
+    fn load_groups(&self) -> Result<Vec<GroupRecord>> {
+        Ok(self
+            .groups
+            .iter()
+            .map(|record| record.value().clone())
+            .collect())
+    }
+}
+
+/// Store writing a JSON file per group to a directory.
+///
+/// Files are written to a temporary path and renamed, so a crash never
+/// leaves a partially written record behind.
+#[derive(Debug)]
+pub struct FileStateStore {
+    dir: PathBuf,
+}
+
+impl FileStateStore {
+    /// Opens a store in the given directory, creating it if needed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    fn path(&self, group_id: &GroupId) -> PathBuf {
+        self.dir.join(format!("{group_id}.json"))
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn save_group(&self, record: &GroupRecord) -> Result<()> {
+        let path = self.path(&record.id);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(record)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    fn remove_group(&self, group_id: &GroupId) -> Result<()> {
+        match fs::remove_file(self.path(group_id)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn load_groups(&self) -> Result<Vec<GroupRecord>> {
+        let mut records = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                let record = fs::read(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|bytes| Ok(serde_json::from_slice(&bytes)?));
+                match record {
+                    Ok(record) => records.push(record),
+                    Err(error) => tracing::warn!(
+                        error = %error,
+                        path = %path.display(),
+                        "Skipping unreadable group record"
+                    ),
+                }
+            }
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn record(clients: usize) -> GroupRecord {
+        GroupRecord {
+            id: Uuid::new_v4(),
+            params: Parameters::new(3, 1).unwrap(),
+            kind: None,
+            ttl_secs: None,
+            filled: false,
+            clients,
+            sessions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn records_never_hold_members() {
+        let mut group = Group::new(
+            Uuid::new_v4(),
+            Parameters::new(3, 1).unwrap(),
+            None,
+            None,
+            ClientId::new_v4(),
+        );
+        let session_id = group
+            .add_session(SessionKind::Keygen, None, SessionOptions::default())
+            .unwrap()
+            .id;
+        let client_id = ClientId::new_v4();
+        group.add_client(client_id).unwrap();
+        group
+            .get_session_mut(&session_id)
+            .unwrap()
+            .signup(client_id, 3)
+            .unwrap();
+
+        let record = GroupRecord::from(&group);
+        assert_eq!(record.clients, 1);
+        let restored = record.into_group();
+        assert!(restored.is_empty());
+        let session = restored.get_session(&session_id).unwrap();
+        assert_eq!(session.get_number_of_clients(), 0);
+    }
+
+    #[test]
+    fn corrupt_records_are_skipped() {
+        let dir = std::env::temp_dir().join(format!("mpc-manager-{}", Uuid::new_v4()));
+        let store = FileStateStore::open(&dir).unwrap();
+        let saved = record(2);
+        store.save_group(&saved).unwrap();
+        fs::write(dir.join(format!("{}.json", Uuid::new_v4())), b"{\"id\":").unwrap();
+
+        let records = store.load_groups().unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, saved.id);
+    }
+}
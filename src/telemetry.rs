@@ -2,11 +2,27 @@
 //!
 //! This module contains the telemetry system of the server.
 
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 use tracing::{subscriber::set_global_default, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
 use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
 
+/// Gauge of connected clients.
+pub const CONNECTED_CLIENTS: &str = "mpc_manager_connected_clients";
+/// Gauge of groups currently alive.
+pub const ACTIVE_GROUPS: &str = "mpc_manager_active_groups";
+/// Gauge of sessions currently alive.
+pub const ACTIVE_SESSIONS: &str = "mpc_manager_active_sessions";
+/// Counter of session messages relayed to clients.
+pub const MESSAGES_RELAYED: &str = "mpc_manager_messages_relayed_total";
+/// Counter of bytes of session messages relayed to clients.
+pub const BYTES_RELAYED: &str = "mpc_manager_relayed_bytes_total";
+/// Counter of notifications sent to clients.
+pub const NOTIFICATIONS_SENT: &str = "mpc_manager_notifications_sent_total";
+/// Counter of clients disconnected for not keeping up with their messages.
+pub const SLOW_CLIENTS_DROPPED: &str = "mpc_manager_slow_clients_dropped_total";
+
 /// Compose multiple layers into a `tracing`'s subscriber.
 ///
 /// # Implementation notes
@@ -41,3 +57,28 @@ pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     LogTracer::init().expect("Failed to initialize logger.");
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
+
+/// Install a Prometheus recorder as global metrics recorder, returning
+/// a handle used to render the metrics.
+///
+/// Note: this should only be called once.
+pub fn init_metrics() -> PrometheusHandle {
+    let handle = PrometheusBuilder::new()
+        .install_recorder()
+        .expect("Failed to install metrics recorder");
+    metrics::describe_gauge!(CONNECTED_CLIENTS, "Number of connected clients");
+    metrics::describe_gauge!(ACTIVE_GROUPS, "Number of groups alive");
+    metrics::describe_gauge!(ACTIVE_SESSIONS, "Number of sessions alive");
+    metrics::describe_counter!(MESSAGES_RELAYED, "Session messages relayed to clients");
+    metrics::describe_counter!(
+        BYTES_RELAYED,
+        metrics::Unit::Bytes,
+        "Bytes of session messages relayed to clients"
+    );
+    metrics::describe_counter!(NOTIFICATIONS_SENT, "Notifications sent to clients");
+    metrics::describe_counter!(
+        SLOW_CLIENTS_DROPPED,
+        "Clients disconnected because their send buffer was full"
+    );
+    handle
+}
@@ -0,0 +1,77 @@
+//! # TLS configuration
+//!
+//! This module builds the rustls configuration used by the server,
+//! optionally requiring clients to authenticate with a certificate (mTLS).
+
+use anyhow::{anyhow, Context, Result};
+use axum_server::tls_rustls::RustlsConfig;
+use rustls::{
+    server::AllowAnyAuthenticatedClient, Certificate, PrivateKey, RootCertStore, ServerConfig,
+};
+use rustls_pemfile::Item;
+use std::{path::Path, sync::Arc};
+
+/// Builds the rustls configuration from PEM encoded files.
+///
+/// When `client_ca_path` is set, clients are required to present a certificate
+/// signed by one of the certificate authorities in that file, otherwise the
+/// handshake is rejected. The certificate only gates the connection, its
+/// subject is not passed to the server: clients are still assigned random
+/// ids and authorized by the bearer token, if any.
+///
+/// # Errors
+///
+/// * Returns an error if any of the files can't be read or parsed.
+/// * Returns an error if the private key doesn't match the certificate.
+pub async fn rustls_config(
+    cert_path: impl AsRef<Path>,
+    key_path: impl AsRef<Path>,
+    client_ca_path: Option<impl AsRef<Path>>,
+) -> Result<RustlsConfig> {
+    let certs = read_certs(cert_path.as_ref()).await?;
+    let key = read_private_key(key_path.as_ref()).await?;
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match client_ca_path {
+        Some(client_ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(client_ca_path.as_ref()).await? {
+                roots.add(&cert)?;
+            }
+            builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder.with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(RustlsConfig::from_config(Arc::new(config)))
+}
+
+/// Reads all the certificates of a PEM file.
+async fn read_certs(path: &Path) -> Result<Vec<Certificate>> {
+    let pem = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut pem.as_ref())?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificates found in `{}`", path.display()));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// Reads the first private key of a PEM file.
+async fn read_private_key(path: &Path) -> Result<PrivateKey> {
+    let pem = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    match rustls_pemfile::read_one(&mut pem.as_ref())? {
+        Some(Item::RSAKey(key)) | Some(Item::PKCS8Key(key)) | Some(Item::ECKey(key)) => {
+            Ok(PrivateKey(key))
+        }
+        _ => Err(anyhow!(
+            "no supported private key found in `{}`",
+            path.display()
+        )),
+    }
+}
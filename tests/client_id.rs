@@ -0,0 +1,55 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{connect, create_group};
+use mpc_manager::{
+    server::{test_harness::TestClient, Server},
+    service::ServiceHandler,
+    state::{ClientId, State},
+};
+use std::sync::Arc;
+
+fn provisioned(state: &Arc<State>, client_id: ClientId) -> Server {
+    Server::with_client_id(state.clone(), Arc::new(ServiceHandler::new()), client_id)
+}
+
+#[tokio::test]
+async fn provisioned_client_ids_are_used() {
+    let state = Arc::new(State::new());
+    let client_id = state.new_client_id();
+    let mut client = TestClient::connect(provisioned(&state, client_id))
+        .await
+        .unwrap();
+    assert_eq!(client.client_id(), client_id.public_id());
+
+    let group = create_group(&mut client, 2, 1).await;
+    assert!(state
+        .resolve_client_id(group.id, client_id.public_id())
+        .await
+        .is_ok_and(|resolved| resolved == client_id));
+}
+
+#[tokio::test]
+async fn provisioned_client_ids_in_use_are_rejected() {
+    let state = Arc::new(State::new());
+    let client_id = state.new_client_id();
+    let mut client = TestClient::connect(provisioned(&state, client_id))
+        .await
+        .unwrap();
+
+    assert!(TestClient::connect(provisioned(&state, client_id))
+        .await
+        .is_err());
+    // The connection already using the id is kept
+    create_group(&mut client, 2, 1).await;
+    assert_eq!(state.get_number_of_clients().await, 1);
+}
+
+#[tokio::test]
+async fn generated_client_ids_are_distinct() {
+    let state = Arc::new(State::new());
+    let first = connect(&state).await;
+    let second = connect(&state).await;
+    assert_ne!(first.client_id(), second.client_id());
+}
@@ -0,0 +1,141 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use mpc_manager::{
+    server::{test_harness::TestClient, Server},
+    service::{
+        group_service::{
+            GroupCreateRequest, GroupCreateResponse, GroupJoinRequest, GroupJoinResponse,
+        },
+        session_service::{
+            SessionCreateRequest, SessionCreateResponse, SessionSignupRequest,
+            SessionSignupResponse,
+        },
+        ServiceHandler,
+    },
+    state::{
+        group::{Group, GroupId},
+        parameters::Parameters,
+        session::{Session, SessionKind, SessionOptions, SessionPartyNumber},
+        State,
+    },
+};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Builds a server sharing `state`, with the default options.
+pub fn server(state: &Arc<State>) -> Server {
+    Server::new(state.clone(), Arc::new(ServiceHandler::new()))
+}
+
+/// Connects a client to a server sharing `state`.
+pub async fn connect(state: &Arc<State>) -> TestClient {
+    TestClient::connect(server(state)).await.unwrap()
+}
+
+/// Creates a group of `n` parties with threshold `t`.
+pub async fn create_group(client: &mut TestClient, n: u16, t: u16) -> Group {
+    let res: GroupCreateResponse = client
+        .request(
+            "group_create",
+            GroupCreateRequest {
+                parameters: Parameters::new(n, t).unwrap(),
+                kind: None,
+                ttl_secs: None,
+                join_code: false,
+            },
+        )
+        .await
+        .unwrap();
+    res.group
+}
+
+/// Joins a group by its id.
+pub async fn join_group(client: &mut TestClient, group_id: GroupId) -> Group {
+    let res: GroupJoinResponse = client
+        .request(
+            "group_join",
+            GroupJoinRequest {
+                group_id: Some(group_id),
+                join_code: None,
+            },
+        )
+        .await
+        .unwrap();
+    res.group
+}
+
+/// Creates a session in a group.
+pub async fn create_session(
+    client: &mut TestClient,
+    group_id: GroupId,
+    kind: SessionKind,
+    options: SessionOptions,
+) -> Session {
+    let res: SessionCreateResponse = client
+        .request(
+            "session_create",
+            SessionCreateRequest {
+                group_id,
+                kind,
+                value: None,
+                options,
+                invitees: None,
+            },
+        )
+        .await
+        .unwrap();
+    res.session
+}
+
+/// Signs up to a session, returning the assigned party number.
+pub async fn signup(
+    client: &mut TestClient,
+    group_id: GroupId,
+    session: &Session,
+) -> SessionPartyNumber {
+    let res: SessionSignupResponse = client
+        .request(
+            "session_signup",
+            SessionSignupRequest {
+                group_id,
+                session_id: session.id,
+            },
+        )
+        .await
+        .unwrap();
+    res.party_number
+}
+
+/// Connects `n` clients to a new `n` parties group and signs all of them
+/// up to a new session.
+pub async fn session_parties(
+    state: &Arc<State>,
+    n: u16,
+    kind: SessionKind,
+    options: SessionOptions,
+) -> (Group, Session, Vec<TestClient>) {
+    let mut clients = Vec::new();
+    for _ in 0..n {
+        clients.push(connect(state).await);
+    }
+    let group = create_group(&mut clients[0], n, 1).await;
+    for client in &mut clients[1..] {
+        join_group(client, group.id).await;
+    }
+    let session = create_session(&mut clients[0], group.id, kind, options).await;
+    for client in &mut clients {
+        signup(client, group.id, &session).await;
+    }
+    (group, session, clients)
+}
+
+/// Sends a request expected to fail, returning its error code.
+pub async fn error_code<P: Serialize>(client: &mut TestClient, method: &str, params: P) -> isize {
+    let res = client.request_raw(method, params).await.unwrap();
+    res.error()
+        .as_ref()
+        .unwrap_or_else(|| panic!("{method} succeeded"))
+        .code
+}
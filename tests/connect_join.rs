@@ -0,0 +1,56 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{connect, create_group, server};
+use mpc_manager::{
+    server::{test_harness::TestClient, GROUP_JOIN_REQUEST_ID},
+    service::group_service::GroupJoinRequest,
+    state::State,
+};
+use serde_json::Value;
+use std::sync::Arc;
+use uuid::Uuid;
+
+async fn connect_joining(state: &Arc<State>, group_join: GroupJoinRequest) -> TestClient {
+    TestClient::connect(server(state).with_group_join(group_join))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn clients_join_the_requested_group_when_connecting() {
+    let state = Arc::new(State::new());
+    let mut creator = connect(&state).await;
+    let group = create_group(&mut creator, 2, 1).await;
+
+    let mut client = connect_joining(
+        &state,
+        GroupJoinRequest {
+            group_id: Some(group.id),
+            join_code: None,
+        },
+    )
+    .await;
+    let res = client.response().await.unwrap();
+    assert_eq!(res.id().clone(), Some(Value::from(GROUP_JOIN_REQUEST_ID)));
+    assert!(res.error().is_none());
+    let joined = creator.notification("group_client_joined").await.unwrap();
+    assert_eq!(joined["group"]["id"], group.id.to_string());
+}
+
+#[tokio::test]
+async fn failed_joins_keep_the_connection() {
+    let state = Arc::new(State::new());
+    let mut client = connect_joining(
+        &state,
+        GroupJoinRequest {
+            group_id: Some(Uuid::new_v4()),
+            join_code: None,
+        },
+    )
+    .await;
+    let res = client.response().await.unwrap();
+    assert_eq!(res.error().as_ref().unwrap().code, -32010);
+    create_group(&mut client, 2, 1).await;
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{connect, create_group, error_code, join_group};
+use mpc_manager::{
+    service::group_service::{GroupCloseRequest, GroupCloseResponse, GroupInfoRequest},
+    state::State,
+};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn creators_close_groups_notifying_the_members() {
+    let state = Arc::new(State::new());
+    let mut clients = [
+        connect(&state).await,
+        connect(&state).await,
+        connect(&state).await,
+    ];
+    let group = create_group(&mut clients[0], 3, 1).await;
+    for client in &mut clients[1..] {
+        join_group(client, group.id).await;
+    }
+
+    let res: GroupCloseResponse = clients[0]
+        .request("group_close", GroupCloseRequest { group_id: group.id })
+        .await
+        .unwrap();
+    assert_eq!(res.group.id, group.id);
+    for client in &mut clients[1..] {
+        let closed = client.notification("group_closed").await.unwrap();
+        assert_eq!(closed["group"]["id"], group.id.to_string());
+        assert_eq!(closed["reason"], "closed");
+    }
+    assert_eq!(
+        error_code(
+            &mut clients[1],
+            "group_info",
+            GroupInfoRequest { group_id: group.id }
+        )
+        .await,
+        -32010
+    );
+}
+
+#[tokio::test]
+async fn only_the_creator_closes_a_group() {
+    let state = Arc::new(State::new());
+    let mut clients = [connect(&state).await, connect(&state).await];
+    let group = create_group(&mut clients[0], 2, 1).await;
+    join_group(&mut clients[1], group.id).await;
+
+    assert_eq!(
+        error_code(
+            &mut clients[1],
+            "group_close",
+            GroupCloseRequest { group_id: group.id }
+        )
+        .await,
+        -32024
+    );
+    // The group is still there
+    let _: GroupCloseResponse = clients[0]
+        .request("group_close", GroupCloseRequest { group_id: group.id })
+        .await
+        .unwrap();
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{connect, create_group, error_code, join_group, session_parties};
+use mpc_manager::{
+    service::group_service::{GroupKickRequest, GroupKickResponse, KickTarget},
+    state::{
+        session::{SessionKind, SessionOptions},
+        State,
+    },
+};
+use serde_json::json;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn kicked_clients_are_removed_and_notified() {
+    let state = Arc::new(State::new());
+    let mut clients = [
+        connect(&state).await,
+        connect(&state).await,
+        connect(&state).await,
+    ];
+    let group = create_group(&mut clients[0], 3, 1).await;
+    for client in &mut clients[1..] {
+        join_group(client, group.id).await;
+    }
+
+    let res: GroupKickResponse = clients[0]
+        .request(
+            "group_kick",
+            GroupKickRequest {
+                group_id: group.id,
+                target: KickTarget::Client {
+                    client_id: clients[1].client_id(),
+                },
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.group.id, group.id);
+    let kicked = clients[1].notification("group_kicked").await.unwrap();
+    assert_eq!(kicked["group"]["id"], group.id.to_string());
+    let left = clients[2].notification("group_client_left").await.unwrap();
+    assert_eq!(left["clientCount"], 2);
+
+    // The kicked client is no longer a member
+    assert_eq!(
+        error_code(
+            &mut clients[1],
+            "session_list",
+            json!({ "groupId": group.id })
+        )
+        .await,
+        -32020
+    );
+}
+
+#[tokio::test]
+async fn parties_are_kicked_by_party_number() {
+    let state = Arc::new(State::new());
+    let (group, session, mut clients) =
+        session_parties(&state, 3, SessionKind::Keygen, SessionOptions::default()).await;
+
+    let _: GroupKickResponse = clients[0]
+        .request(
+            "group_kick",
+            GroupKickRequest {
+                group_id: group.id,
+                target: KickTarget::Party {
+                    session_id: session.id,
+                    party_number: 3,
+                },
+            },
+        )
+        .await
+        .unwrap();
+    clients[2].notification("group_kicked").await.unwrap();
+}
+
+#[tokio::test]
+async fn only_the_creator_kicks_other_members() {
+    let state = Arc::new(State::new());
+    let mut clients = [connect(&state).await, connect(&state).await];
+    let group = create_group(&mut clients[0], 2, 1).await;
+    join_group(&mut clients[1], group.id).await;
+    let kick = |client_id| GroupKickRequest {
+        group_id: group.id,
+        target: KickTarget::Client { client_id },
+    };
+
+    let creator = clients[0].client_id();
+    assert_eq!(
+        error_code(&mut clients[1], "group_kick", kick(creator)).await,
+        -32024
+    );
+    assert_eq!(
+        error_code(&mut clients[0], "group_kick", kick(creator)).await,
+        -32602
+    );
+    let outsider = connect(&state).await.client_id();
+    assert_eq!(
+        error_code(&mut clients[0], "group_kick", kick(outsider)).await,
+        -32025
+    );
+}
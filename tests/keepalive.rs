@@ -0,0 +1,42 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{create_group, server};
+use mpc_manager::{
+    server::{test_harness::TestClient, ServerOptions},
+    state::State,
+};
+use std::{sync::Arc, time::Duration};
+
+async fn pinged(state: &Arc<State>) -> TestClient {
+    let options = ServerOptions {
+        ping_interval: Some(Duration::from_millis(20)),
+        ..Default::default()
+    };
+    TestClient::connect(server(state).with_options(options))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn silent_clients_answering_pings_are_kept() {
+    let state = Arc::new(State::new());
+    let mut client = pinged(&state)
+        .await
+        .with_timeout(Duration::from_millis(150));
+
+    // Pings are answered while waiting
+    assert!(client.notification("group_client_joined").await.is_err());
+    assert_eq!(state.get_number_of_clients().await, 1);
+    create_group(&mut client, 2, 1).await;
+}
+
+#[tokio::test]
+async fn clients_not_answering_pings_are_closed() {
+    let state = Arc::new(State::new());
+    let _client = pinged(&state).await;
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(state.get_number_of_clients().await, 0);
+}
@@ -0,0 +1,69 @@
+#![cfg(all(feature = "server", feature = "client"))]
+
+use futures_util::SinkExt;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use mpc_manager::{
+    server::{router, AppState, ServerOptions},
+    service::ServiceHandler,
+    state::State,
+};
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{http::StatusCode, Error},
+};
+use tokio_util::sync::CancellationToken;
+
+/// Serves the router over TCP on a random local port.
+async fn serve(state: Arc<State>) -> SocketAddr {
+    let app_state = Arc::new(AppState {
+        state,
+        service_handler: Arc::new(ServiceHandler::new()),
+        server_options: ServerOptions::default(),
+        shutdown: CancellationToken::new(),
+        metrics: PrometheusBuilder::new().build_recorder().handle(),
+        started_at: Instant::now(),
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = axum::Server::from_tcp(listener.into_std().unwrap())
+        .unwrap()
+        .serve(router(app_state).into_make_service());
+    tokio::spawn(server);
+    address
+}
+
+#[tokio::test]
+async fn upgrades_past_the_maximum_connections_are_rejected() {
+    let state = Arc::new(State::new().with_max_connections(Some(1)));
+    let url = format!("ws://{}/", serve(state.clone()).await);
+
+    let (mut first, _) = connect_async(&url).await.unwrap();
+    match connect_async(&url).await {
+        Err(Error::Http(res)) => assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE),
+        res => panic!("expected the upgrade to be rejected, got {res:?}"),
+    }
+
+    // The slot is released once the connection is closed
+    first.close(None).await.unwrap();
+    first.flush().await.unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while state.get_number_of_clients().await > 0 {
+        assert!(Instant::now() < deadline, "connection was never released");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    loop {
+        match connect_async(&url).await {
+            Ok(_) => break,
+            Err(Error::Http(res)) if res.status() == StatusCode::SERVICE_UNAVAILABLE => {
+                assert!(Instant::now() < deadline, "slot was never freed");
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Err(error) => panic!("unexpected error: {error}"),
+        }
+    }
+}
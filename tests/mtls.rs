@@ -0,0 +1,94 @@
+#![cfg(feature = "server")]
+
+use mpc_manager::tls::rustls_config;
+use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use std::{path::Path, sync::Arc};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/mtls");
+
+fn certs(name: &str) -> Vec<Certificate> {
+    let pem = std::fs::read(Path::new(FIXTURES).join(name)).unwrap();
+    rustls_pemfile::certs(&mut pem.as_ref())
+        .unwrap()
+        .into_iter()
+        .map(Certificate)
+        .collect()
+}
+
+fn private_key(name: &str) -> PrivateKey {
+    let pem = std::fs::read(Path::new(FIXTURES).join(name)).unwrap();
+    PrivateKey(
+        rustls_pemfile::pkcs8_private_keys(&mut pem.as_ref())
+            .unwrap()
+            .remove(0),
+    )
+}
+
+fn client_config(with_cert: bool) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    for cert in certs("ca.pem") {
+        roots.add(&cert).unwrap();
+    }
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+    if with_cert {
+        builder
+            .with_client_auth_cert(certs("client.pem"), private_key("client-key.pem"))
+            .unwrap()
+    } else {
+        builder.with_no_client_auth()
+    }
+}
+
+/// Performs a handshake against a server requiring client certificates,
+/// returning whether the server accepted it and echoed a message back.
+async fn handshake(with_cert: bool) -> bool {
+    let config = rustls_config(
+        Path::new(FIXTURES).join("server.pem"),
+        Path::new(FIXTURES).join("server-key.pem"),
+        Some(Path::new(FIXTURES).join("ca.pem")),
+    )
+    .await
+    .unwrap();
+    let acceptor = TlsAcceptor::from(config.get_inner());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = acceptor.accept(stream).await?;
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).await?;
+        stream.write_all(&buf).await?;
+        stream.shutdown().await
+    });
+
+    let connector = TlsConnector::from(Arc::new(client_config(with_cert)));
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let domain = ServerName::try_from("localhost").unwrap();
+    let echoed = async {
+        let mut stream = connector.connect(domain, stream).await?;
+        stream.write_all(b"ping").await?;
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).await?;
+        std::io::Result::Ok(buf)
+    }
+    .await;
+    let accepted = server.await.unwrap().is_ok();
+    accepted && echoed.is_ok_and(|buf| &buf == b"ping")
+}
+
+#[tokio::test]
+async fn mtls_accepts_certificates_signed_by_the_client_ca() {
+    assert!(handshake(true).await);
+}
+
+#[tokio::test]
+async fn mtls_rejects_clients_without_certificate() {
+    assert!(!handshake(false).await);
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{connect, error_code, session_parties};
+use mpc_manager::{
+    service::session_service::{SessionAbortRequest, SessionAbortResponse, SessionStatusRequest},
+    state::{
+        session::{SessionKind, SessionOptions},
+        State,
+    },
+};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn aborts_are_broadcast_to_the_other_parties() {
+    let state = Arc::new(State::new());
+    let (group, session, mut clients) =
+        session_parties(&state, 3, SessionKind::Keygen, SessionOptions::default()).await;
+
+    let res: SessionAbortResponse = clients[1]
+        .request(
+            "session_abort",
+            SessionAbortRequest {
+                group_id: group.id,
+                session_id: session.id,
+                reason: "bad share".into(),
+                close: true,
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.session.id, session.id);
+    for i in [0, 2] {
+        let aborted = clients[i].notification("session_aborted").await.unwrap();
+        assert_eq!(aborted["session"]["id"], session.id.to_string());
+        assert_eq!(aborted["partyNumber"], 2);
+        assert_eq!(aborted["reason"], "bad share");
+        assert_eq!(aborted["closed"], true);
+    }
+    assert!(clients[1].notification("session_aborted").await.is_err());
+
+    let status = SessionStatusRequest {
+        group_id: group.id,
+        session_id: session.id,
+    };
+    assert_eq!(
+        error_code(&mut clients[0], "session_status", status).await,
+        -32011
+    );
+}
+
+#[tokio::test]
+async fn only_parties_abort_sessions_that_are_kept_unless_closed() {
+    let state = Arc::new(State::new());
+    let (group, session, mut clients) =
+        session_parties(&state, 2, SessionKind::Keygen, SessionOptions::default()).await;
+    let abort = SessionAbortRequest {
+        group_id: group.id,
+        session_id: session.id,
+        reason: "retry".into(),
+        close: false,
+    };
+
+    let mut outsider = connect(&state).await;
+    assert_eq!(
+        error_code(&mut outsider, "session_abort", &abort).await,
+        -32020
+    );
+    let _: SessionAbortResponse = clients[0].request("session_abort", &abort).await.unwrap();
+    let aborted = clients[1].notification("session_aborted").await.unwrap();
+    assert_eq!(aborted["closed"], false);
+    let status = SessionStatusRequest {
+        group_id: group.id,
+        session_id: session.id,
+    };
+    let _: serde_json::Value = clients[1].request("session_status", status).await.unwrap();
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{connect, create_group, create_session, join_group, signup};
+use mpc_manager::server::test_harness::TestClient;
+use mpc_manager::{
+    service::session_service::{
+        SessionEventsSinceRequest, SessionEventsSinceResponse, SessionSetValueRequest,
+    },
+    state::{
+        group::GroupId,
+        session::{Session, SessionKind, SessionLifecycle, SessionOptions},
+        State,
+    },
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn history_options() -> SessionOptions {
+    SessionOptions {
+        event_history: true,
+        ..Default::default()
+    }
+}
+
+async fn events_since(
+    client: &mut TestClient,
+    group_id: GroupId,
+    session: &Session,
+    since: u64,
+) -> SessionEventsSinceResponse {
+    client
+        .request(
+            "session_events_since",
+            SessionEventsSinceRequest {
+                group_id,
+                session_id: session.id,
+                since,
+            },
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn ready_is_recorded_once() {
+    let state = Arc::new(State::new());
+    let mut clients = vec![
+        connect(&state).await,
+        connect(&state).await,
+        connect(&state).await,
+    ];
+    let group = create_group(&mut clients[0], 3, 1).await;
+    for client in &mut clients[1..] {
+        join_group(client, group.id).await;
+    }
+    let session = create_session(
+        &mut clients[0],
+        group.id,
+        SessionKind::Sign,
+        history_options(),
+    )
+    .await;
+    // The threshold is reached by the second signup and still met by the third
+    for client in &mut clients {
+        signup(client, group.id, &session).await;
+    }
+
+    let res = events_since(&mut clients[0], group.id, &session, 0).await;
+    let events: Vec<_> = res.events.iter().map(|event| event.event).collect();
+    assert_eq!(events, [SessionLifecycle::Created, SessionLifecycle::Ready]);
+    assert!(!res.truncated);
+}
+
+#[tokio::test]
+async fn history_is_truncated_to_its_capacity() {
+    let state = Arc::new(State::new());
+    let mut client = connect(&state).await;
+    let group = create_group(&mut client, 3, 2).await;
+    let session = create_session(&mut client, group.id, SessionKind::Sign, history_options()).await;
+    signup(&mut client, group.id, &session).await;
+    for i in 0..20 {
+        let _: Value = client
+            .request(
+                "session_set_value",
+                SessionSetValueRequest {
+                    group_id: group.id,
+                    session_id: session.id,
+                    value: Some(json!(i)),
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    // 21 events were recorded and only the latest 16 are kept
+    let res = events_since(&mut client, group.id, &session, 0).await;
+    let seqs: Vec<_> = res.events.iter().map(|event| event.seq).collect();
+    assert_eq!(seqs, (6..=21).collect::<Vec<_>>());
+    assert!(res.truncated);
+
+    let res = events_since(&mut client, group.id, &session, 5).await;
+    assert_eq!(res.events.len(), 16);
+    assert!(!res.truncated);
+
+    let res = events_since(&mut client, group.id, &session, 19).await;
+    let seqs: Vec<_> = res.events.iter().map(|event| event.seq).collect();
+    assert_eq!(seqs, [20, 21]);
+    assert!(!res.truncated);
+}
+
+#[tokio::test]
+async fn events_since_the_last_sequence_number() {
+    let state = Arc::new(State::new());
+    let mut client = connect(&state).await;
+    let group = create_group(&mut client, 3, 2).await;
+    let session = create_session(&mut client, group.id, SessionKind::Sign, history_options()).await;
+
+    let res = events_since(&mut client, group.id, &session, u64::MAX).await;
+    assert!(res.events.is_empty());
+    assert!(!res.truncated);
+}
+
+#[tokio::test]
+async fn events_since_requires_event_history() {
+    let state = Arc::new(State::new());
+    let mut client = connect(&state).await;
+    let group = create_group(&mut client, 3, 2).await;
+    let session = create_session(
+        &mut client,
+        group.id,
+        SessionKind::Sign,
+        SessionOptions::default(),
+    )
+    .await;
+
+    let res = client
+        .request_raw(
+            "session_events_since",
+            SessionEventsSinceRequest {
+                group_id: group.id,
+                session_id: session.id,
+                since: 0,
+            },
+        )
+        .await
+        .unwrap();
+    assert!(res.error().is_some());
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{connect, create_group, create_session, join_group};
+use mpc_manager::{
+    service::session_service::{SessionSignupRequest, SessionSignupResponse},
+    state::{
+        session::{SessionKind, SessionOptions},
+        State,
+    },
+};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn signups_return_the_occupied_party_numbers() {
+    let state = Arc::new(State::new());
+    let mut clients = [
+        connect(&state).await,
+        connect(&state).await,
+        connect(&state).await,
+    ];
+    let group = create_group(&mut clients[0], 3, 1).await;
+    for client in &mut clients[1..] {
+        join_group(client, group.id).await;
+    }
+    let session = create_session(
+        &mut clients[0],
+        group.id,
+        SessionKind::Keygen,
+        SessionOptions::default(),
+    )
+    .await;
+
+    let mut parties = Vec::new();
+    for client in &mut clients {
+        let res: SessionSignupResponse = client
+            .request(
+                "session_signup",
+                SessionSignupRequest {
+                    group_id: group.id,
+                    session_id: session.id,
+                },
+            )
+            .await
+            .unwrap();
+        parties.push(res.parties);
+    }
+    assert_eq!(parties, [vec![1], vec![1, 2], vec![1, 2, 3]]);
+}
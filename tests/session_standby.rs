@@ -0,0 +1,108 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{connect, create_group, create_session, join_group, server, signup};
+use mpc_manager::{
+    server::{test_harness::TestClient, Resume, ServerOptions},
+    service::session_service::SessionSignupRequest,
+    state::{
+        group::GroupId,
+        session::{Session, SessionFullPolicy, SessionKind, SessionOptions, SessionReadiness},
+        State,
+    },
+};
+use std::{sync::Arc, time::Duration};
+
+/// Signs up to a full session, returning the position in the standby queue.
+async fn standby(client: &mut TestClient, group_id: GroupId, session: &Session) -> u64 {
+    let res = client
+        .request_raw(
+            "session_signup",
+            SessionSignupRequest {
+                group_id,
+                session_id: session.id,
+            },
+        )
+        .await
+        .unwrap();
+    res.result().as_ref().unwrap()["standby"].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn standbys_take_over_parties_disconnecting_before_the_threshold() {
+    let state = Arc::new(State::new());
+    let mut clients = vec![
+        connect(&state).await,
+        connect(&state).await,
+        connect(&state).await,
+    ];
+    let group = create_group(&mut clients[0], 3, 1).await;
+    for client in &mut clients[1..] {
+        join_group(client, group.id).await;
+    }
+    // The reserved party number keeps the session full but not ready
+    let options = SessionOptions {
+        on_full: SessionFullPolicy::Standby,
+        reserved_parties: vec![3],
+        ..Default::default()
+    };
+    let session = create_session(&mut clients[0], group.id, SessionKind::Keygen, options).await;
+    signup(&mut clients[0], group.id, &session).await;
+    signup(&mut clients[1], group.id, &session).await;
+    assert_eq!(standby(&mut clients[2], group.id, &session).await, 1);
+
+    drop(clients.remove(0));
+    let promoted = clients[1].notification("session_promoted").await.unwrap();
+    assert_eq!(promoted["session"]["id"], session.id.to_string());
+    assert_eq!(promoted["partyNumber"], 1);
+    assert_eq!(
+        state
+            .get_client_ids_from_session(&group.id, &session.id)
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn promoted_standbys_complete_the_threshold() {
+    let state = Arc::new(State::new());
+    let resume = Resume::new(Duration::from_millis(50));
+    let options = ServerOptions {
+        resume: Some(resume),
+        ..Default::default()
+    };
+    let mut leaving = TestClient::connect(server(&state).with_options(options))
+        .await
+        .unwrap();
+    let mut clients = vec![connect(&state).await, connect(&state).await];
+    let group = create_group(&mut leaving, 3, 1).await;
+    for client in &mut clients {
+        join_group(client, group.id).await;
+    }
+    let options = SessionOptions {
+        on_full: SessionFullPolicy::Standby,
+        readiness: SessionReadiness::Connected,
+        fixed_signers: true,
+        ..Default::default()
+    };
+    let session = create_session(&mut leaving, group.id, SessionKind::Sign, options).await;
+    signup(&mut leaving, group.id, &session).await;
+    // The disconnected party keeps its party number until its reservation expires
+    drop(leaving);
+    while state.get_number_of_clients().await > 2 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    signup(&mut clients[0], group.id, &session).await;
+    assert_eq!(standby(&mut clients[1], group.id, &session).await, 1);
+
+    let promoted = clients[1].notification("session_promoted").await.unwrap();
+    assert_eq!(promoted["partyNumber"], 1);
+    for client in &mut clients {
+        let ready = client.notification("session_ready").await.unwrap();
+        assert_eq!(ready["session"]["id"], session.id.to_string());
+        assert_eq!(ready["parties"], serde_json::json!([1, 2]));
+    }
+}
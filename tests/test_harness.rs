@@ -0,0 +1,38 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{connect, create_group, error_code, join_group};
+use mpc_manager::state::State;
+use serde_json::json;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn clients_share_the_state_of_their_servers() {
+    let state = Arc::new(State::new());
+    let mut creator = connect(&state).await;
+    let mut member = connect(&state).await;
+
+    let group = create_group(&mut creator, 2, 1).await;
+    join_group(&mut member, group.id).await;
+    let joined = creator.notification("group_client_joined").await.unwrap();
+    assert_eq!(joined["group"]["id"], group.id.to_string());
+    assert_eq!(state.get_number_of_clients().await, 2);
+}
+
+#[tokio::test]
+async fn errors_and_raw_messages_get_their_response() {
+    let state = Arc::new(State::new());
+    let mut client = connect(&state).await;
+
+    let code = error_code(
+        &mut client,
+        "group_join",
+        json!({"groupId": uuid::Uuid::new_v4()}),
+    )
+    .await;
+    assert_eq!(code, -32010);
+    client.send_raw("{").unwrap();
+    let res = client.response().await.unwrap();
+    assert_eq!(res.error().as_ref().unwrap().code, -32700);
+}